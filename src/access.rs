@@ -0,0 +1,390 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::dev::{BodySize, MessageBody, Service, ServiceRequest, ServiceResponse, Transform};
+use actix_web::http::header::{REFERER, USER_AGENT};
+use actix_web::http::HeaderMap;
+use actix_web::Error;
+
+use chrono::{DateTime, Local};
+
+use futures::future::{self, FutureResult};
+use futures::{Future, Poll};
+
+use parking_lot::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use std::convert::TryFrom;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// The response header carrying the resolved sleep time in milliseconds.
+static SLEEP_TIME_MS_HEADER: &str = "X-Slumber-Time-Millis";
+
+/// The response header carrying the request id, which is returned whatever the configured request id header.
+static REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// The fields which can be used in a custom access log pattern, named as in the JSON format.
+const FIELDS: &[&str] = &[
+    "time",
+    "client",
+    "method",
+    "path",
+    "query",
+    "protocol",
+    "status",
+    "requested_ms",
+    "duration_ms",
+    "bytes",
+    "user_agent",
+    "referer",
+    "request_id",
+];
+
+/// The format of access log lines.
+#[derive(Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub enum AccessLogFormat {
+    /// The Common Log Format.
+    Common,
+    /// The Combined Log Format, which adds the referer and user agent to the Common Log Format.
+    Combined,
+    /// A JSON object per line.
+    Json,
+    /// A custom pattern, where fields are substituted for `{name}` placeholders.
+    Pattern(String),
+}
+
+impl AccessLogFormat {
+    /// Check that every placeholder in a pattern names a known field.
+    fn validate_pattern(pattern: &str) -> Result<(), String> {
+        let mut rest = pattern;
+
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..].find('}').ok_or_else(|| {
+                format!("unclosed placeholder in access log pattern '{}'", pattern)
+            })?;
+
+            let name = &rest[start + 1..start + end];
+
+            if !FIELDS.contains(&name) {
+                return Err(format!(
+                    "unknown field '{}' in access log pattern, expected one of {}",
+                    name,
+                    FIELDS.join(", ")
+                ));
+            }
+
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for AccessLogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "common" => Ok(AccessLogFormat::Common),
+            "combined" => Ok(AccessLogFormat::Combined),
+            "json" => Ok(AccessLogFormat::Json),
+            _ if s.contains('{') => {
+                AccessLogFormat::validate_pattern(s)?;
+
+                Ok(AccessLogFormat::Pattern(s.to_string()))
+            }
+            _ => Err(format!(
+                "unknown access log format '{}', expected common, combined, json, or a pattern",
+                s
+            )),
+        }
+    }
+}
+
+impl TryFrom<String> for AccessLogFormat {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<AccessLogFormat> for String {
+    fn from(format: AccessLogFormat) -> Self {
+        format.to_string()
+    }
+}
+
+impl fmt::Display for AccessLogFormat {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            AccessLogFormat::Common => f.write_str("common"),
+            AccessLogFormat::Combined => f.write_str("combined"),
+            AccessLogFormat::Json => f.write_str("json"),
+            AccessLogFormat::Pattern(pattern) => f.write_str(pattern),
+        }
+    }
+}
+
+/// A record of a single request in the access log.
+#[derive(Clone, Debug, Serialize)]
+pub struct AccessRecord {
+    pub time: DateTime<Local>,
+    pub client: Option<String>,
+    pub method: String,
+    pub path: String,
+    pub query: Option<String>,
+    pub protocol: String,
+    pub status: u16,
+    /// The sleep time resolved for the request, if it was a sleep request.
+    pub requested_ms: Option<u64>,
+    /// The time taken to respond, including any sleep.
+    pub duration_ms: f64,
+    /// The size of the response body, if known up front.
+    pub bytes: Option<u64>,
+    pub user_agent: Option<String>,
+    pub referer: Option<String>,
+    pub request_id: Option<String>,
+}
+
+impl AccessRecord {
+    /// Build a record from a response and the time it took to produce.
+    pub fn new<B: MessageBody>(res: &ServiceResponse<B>, elapsed: Duration) -> Self {
+        let req = res.request();
+        let headers = res.headers();
+
+        let bytes = match res.response().body().size() {
+            BodySize::Empty | BodySize::None => Some(0),
+            BodySize::Sized(size) => Some(size as u64),
+            BodySize::Sized64(size) => Some(size),
+            BodySize::Stream => None,
+        };
+
+        Self {
+            time: Local::now(),
+            client: req.peer_addr().map(|addr| addr.ip().to_string()),
+            method: req.method().to_string(),
+            path: req.path().to_string(),
+            query: Some(req.query_string().to_string()).filter(|q| !q.is_empty()),
+            protocol: format!("{:?}", req.version()),
+            status: res.status().as_u16(),
+            requested_ms: header(headers, SLEEP_TIME_MS_HEADER).and_then(|v| v.parse().ok()),
+            // rounded to the microsecond
+            duration_ms: (elapsed.as_secs_f64() * 1_000_000.0).round() / 1000.0,
+            bytes,
+            user_agent: header(req.headers(), USER_AGENT.as_str()),
+            referer: header(req.headers(), REFERER.as_str()),
+            request_id: header(headers, REQUEST_ID_HEADER),
+        }
+    }
+
+    /// The value of a field as it appears in a pattern, with `-` for missing values.
+    fn field(&self, name: &str) -> String {
+        let value = match name {
+            "time" => Some(self.time.to_rfc3339()),
+            "client" => self.client.clone(),
+            "method" => Some(self.method.clone()),
+            "path" => Some(self.path.clone()),
+            "query" => self.query.clone(),
+            "protocol" => Some(self.protocol.clone()),
+            "status" => Some(self.status.to_string()),
+            "requested_ms" => self.requested_ms.map(|ms| ms.to_string()),
+            "duration_ms" => Some(self.duration_ms.to_string()),
+            "bytes" => self.bytes.map(|bytes| bytes.to_string()),
+            "user_agent" => self.user_agent.clone(),
+            "referer" => self.referer.clone(),
+            "request_id" => self.request_id.clone(),
+            _ => None,
+        };
+
+        value.unwrap_or_else(|| "-".to_string())
+    }
+
+    /// The request line, as sent by the client.
+    fn request_line(&self) -> String {
+        match &self.query {
+            Some(query) => format!("{} {}?{} {}", self.method, self.path, query, self.protocol),
+            None => format!("{} {} {}", self.method, self.path, self.protocol),
+        }
+    }
+
+    /// Render the record as a single line in the given format, without a trailing newline.
+    pub fn render(&self, format: &AccessLogFormat) -> String {
+        match format {
+            AccessLogFormat::Common => self.common(),
+            AccessLogFormat::Combined => format!(
+                "{} \"{}\" \"{}\"",
+                self.common(),
+                self.field("referer"),
+                self.field("user_agent")
+            ),
+            // a record only holds strings and numbers, so it always serializes
+            AccessLogFormat::Json => serde_json::to_string(self).unwrap(),
+            AccessLogFormat::Pattern(pattern) => self.pattern(pattern),
+        }
+    }
+
+    fn common(&self) -> String {
+        format!(
+            "{} - - [{}] \"{}\" {} {}",
+            self.field("client"),
+            self.time.format("%d/%b/%Y:%H:%M:%S %z"),
+            self.request_line(),
+            self.status,
+            self.bytes
+                .filter(|bytes| *bytes > 0)
+                .map(|bytes| bytes.to_string())
+                .unwrap_or_else(|| "-".to_string())
+        )
+    }
+
+    fn pattern(&self, pattern: &str) -> String {
+        let mut line = String::with_capacity(pattern.len());
+        let mut rest = pattern;
+
+        while let Some(start) = rest.find('{') {
+            match rest[start..].find('}') {
+                Some(end) => {
+                    line.push_str(&rest[..start]);
+                    line.push_str(&self.field(&rest[start + 1..start + end]));
+                    rest = &rest[start + end + 1..];
+                }
+                None => break,
+            }
+        }
+
+        line.push_str(rest);
+        line
+    }
+}
+
+/// Read a header as a string, if present and valid.
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Middleware which writes a line to the access log for every response, unless disabled.
+#[derive(Clone)]
+pub struct AccessLog(Option<Arc<Inner>>);
+
+struct Inner {
+    format: AccessLogFormat,
+    writer: Mutex<Box<dyn Write + Send>>,
+}
+
+impl AccessLog {
+    /// Log to a writer in the given format.
+    pub fn new<W>(format: AccessLogFormat, writer: W) -> Self
+    where
+        W: Write + Send + 'static,
+    {
+        AccessLog(Some(Arc::new(Inner {
+            format,
+            writer: Mutex::new(Box::new(writer)),
+        })))
+    }
+
+    /// A log which doesn't record anything, so that the middleware can always be installed.
+    pub fn disabled() -> Self {
+        AccessLog(None)
+    }
+
+    /// Log to `stdout`, `stderr`, or otherwise the file at the given path, which is appended to.
+    pub fn open(target: &str, format: AccessLogFormat) -> io::Result<Self> {
+        Ok(match target {
+            "stdout" => AccessLog::new(format, io::stdout()),
+            "stderr" => AccessLog::new(format, io::stderr()),
+            path => AccessLog::new(
+                format,
+                OpenOptions::new().create(true).append(true).open(path)?,
+            ),
+        })
+    }
+
+    /// Write a record to the log.
+    pub fn record(&self, record: &AccessRecord) {
+        let inner = match &self.0 {
+            Some(inner) => inner,
+            None => return,
+        };
+
+        let mut line = record.render(&inner.format);
+        line.push('\n');
+
+        let mut writer = inner.writer.lock();
+
+        if let Err(e) = writer
+            .write_all(line.as_bytes())
+            .and_then(|_| writer.flush())
+        {
+            tracing::warn!("Unable to write to the access log: {}", e);
+        }
+    }
+}
+
+impl<S, B> Transform<S> for AccessLog
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type InitError = ();
+    type Transform = AccessLogMiddleware<S>;
+    type Future = FutureResult<Self::Transform, Self::InitError>;
+
+    fn new_transform(&self, service: S) -> Self::Future {
+        future::ok(AccessLogMiddleware {
+            service,
+            log: self.clone(),
+        })
+    }
+}
+
+/// The service which times each request for the access log.
+pub struct AccessLogMiddleware<S> {
+    service: S,
+    log: AccessLog,
+}
+
+impl<S, B> Service for AccessLogMiddleware<S>
+where
+    S: Service<Request = ServiceRequest, Response = ServiceResponse<B>, Error = Error>,
+    S::Future: 'static,
+    B: MessageBody,
+{
+    type Request = ServiceRequest;
+    type Response = ServiceResponse<B>;
+    type Error = Error;
+    type Future = Box<dyn Future<Item = Self::Response, Error = Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.service.poll_ready()
+    }
+
+    fn call(&mut self, req: ServiceRequest) -> Self::Future {
+        if self.log.0.is_none() {
+            return Box::new(self.service.call(req));
+        }
+
+        let started = Instant::now();
+        let log = self.log.clone();
+
+        Box::new(self.service.call(req).map(move |res| {
+            log.record(&AccessRecord::new(&res, started.elapsed()));
+            res
+        }))
+    }
+}
@@ -0,0 +1,170 @@
+use super::{AccessLog, AccessLogFormat, AccessRecord};
+
+use actix_web::test::{self, TestRequest};
+use actix_web::{web, App, HttpResponse};
+
+use chrono::Local;
+
+use parking_lot::Mutex;
+
+use std::io;
+use std::sync::Arc;
+
+/// A writer which collects everything written to it.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().clone())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+fn record() -> AccessRecord {
+    AccessRecord {
+        time: Local::now(),
+        client: Some("127.0.0.1".to_string()),
+        method: "GET".to_string(),
+        path: "/sleep/15".to_string(),
+        query: Some("dry_run=1".to_string()),
+        protocol: "HTTP/1.1".to_string(),
+        status: 200,
+        requested_ms: Some(15),
+        duration_ms: 15.25,
+        bytes: Some(123),
+        user_agent: Some("curl/7.68.0".to_string()),
+        referer: None,
+        request_id: Some("abc".to_string()),
+    }
+}
+
+#[test]
+fn test_parse_format() {
+    assert_eq!(AccessLogFormat::Common, "common".parse().unwrap());
+    assert_eq!(AccessLogFormat::Combined, "Combined".parse().unwrap());
+    assert_eq!(AccessLogFormat::Json, "JSON".parse().unwrap());
+    assert_eq!(
+        AccessLogFormat::Pattern("{method} {path} took {duration_ms}ms".to_string()),
+        "{method} {path} took {duration_ms}ms".parse().unwrap()
+    );
+
+    assert!("apache".parse::<AccessLogFormat>().is_err());
+    assert!("{method} {verb}".parse::<AccessLogFormat>().is_err());
+    assert!("{method} {path".parse::<AccessLogFormat>().is_err());
+}
+
+#[test]
+fn test_render() {
+    let record = record();
+
+    let common = record.render(&AccessLogFormat::Common);
+
+    assert!(common.starts_with("127.0.0.1 - - ["));
+    assert!(common.ends_with("] \"GET /sleep/15?dry_run=1 HTTP/1.1\" 200 123"));
+
+    let combined = record.render(&AccessLogFormat::Combined);
+
+    assert!(combined.starts_with(&common));
+    assert!(combined.ends_with(" 200 123 \"-\" \"curl/7.68.0\""));
+
+    let pattern = AccessLogFormat::Pattern(
+        "{request_id} {method} {path}?{query} {status} {requested_ms}/{duration_ms} {referer}"
+            .to_string(),
+    );
+
+    assert_eq!(
+        "abc GET /sleep/15?dry_run=1 200 15/15.25 -",
+        record.render(&pattern)
+    );
+
+    let json: serde_json::Value =
+        serde_json::from_str(&record.render(&AccessLogFormat::Json)).unwrap();
+
+    assert_eq!("127.0.0.1", json["client"]);
+    assert_eq!("/sleep/15", json["path"]);
+    assert_eq!(200, json["status"]);
+    assert_eq!(15, json["requested_ms"]);
+    assert_eq!(123, json["bytes"]);
+    assert!(json["referer"].is_null());
+    assert!(json["time"].is_string());
+}
+
+#[test]
+fn test_middleware() {
+    let buffer = Buffer::default();
+
+    let mut app = test::init_service(
+        App::new()
+            .wrap(AccessLog::new(AccessLogFormat::Json, buffer.clone()))
+            .route(
+                "/sleep/{millis}",
+                web::to(|| {
+                    HttpResponse::Ok()
+                        .header("X-Slumber-Time-Millis", "15")
+                        .header("X-Request-Id", "abc")
+                        .body("hello")
+                }),
+            ),
+    );
+
+    let req = TestRequest::with_header("User-Agent", "curl/7.68.0")
+        .uri("/sleep/15?dry_run=1")
+        .to_request();
+
+    test::call_service(&mut app, req);
+
+    let req = TestRequest::with_uri("/missing").to_request();
+
+    test::call_service(&mut app, req);
+
+    let lines = buffer.lines();
+
+    assert_eq!(2, lines.len());
+
+    let json: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+
+    assert_eq!("GET", json["method"]);
+    assert_eq!("/sleep/15", json["path"]);
+    assert_eq!("dry_run=1", json["query"]);
+    assert_eq!(200, json["status"]);
+    assert_eq!(15, json["requested_ms"]);
+    assert_eq!(5, json["bytes"]);
+    assert_eq!("curl/7.68.0", json["user_agent"]);
+    assert_eq!("abc", json["request_id"]);
+    assert!(json["duration_ms"].as_f64().unwrap() >= 0.0);
+
+    let json: serde_json::Value = serde_json::from_str(&lines[1]).unwrap();
+
+    assert_eq!(404, json["status"]);
+    assert!(json["requested_ms"].is_null());
+    assert!(json["request_id"].is_null());
+}
+
+#[test]
+fn test_disabled() {
+    let mut app = test::init_service(
+        App::new()
+            .wrap(AccessLog::disabled())
+            .route("/", web::to(HttpResponse::Ok)),
+    );
+
+    let res = test::call_service(&mut app, TestRequest::default().to_request());
+
+    assert!(res.status().is_success());
+}
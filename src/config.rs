@@ -1,12 +1,243 @@
+// structopt fills in repeatable options with `unwrap_or_else(Vec::new)`
+#![allow(clippy::unwrap_or_default)]
+
+#[cfg(test)]
+mod tests;
+
+mod file;
+mod rule;
+
+pub use self::file::{ConfigError, ConfigFile, ConfigFormat, FaultSettings, Profile, REDACTED};
+pub use self::rule::{Pattern, Rule};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
 use std::default::Default;
+use std::ffi::OsString;
+use std::fmt;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
 use std::time::Duration;
 
+use actix_web::http::HeaderName;
+
+use crate::access::AccessLogFormat;
+
+use parking_lot::RwLock;
+
+use structopt::clap::ArgMatches;
 use structopt::StructOpt;
 
+/// The prefix of environment variables which configure slumberd.
+pub const ENV_PREFIX: &str = "SLUMBERD_";
+
+/// The environment variable naming the configuration file.
+const CONFIG_ENV_VAR: &str = "SLUMBERD_CONFIG";
+
+/// Where an effective configuration value came from.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ConfigSource {
+    Default,
+    File,
+    Env,
+    Flag,
+    Admin,
+}
+
+impl fmt::Display for ConfigSource {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match self {
+            ConfigSource::Default => "default",
+            ConfigSource::File => "configuration file",
+            ConfigSource::Env => "environment",
+            ConfigSource::Flag => "command-line",
+            ConfigSource::Admin => "admin API",
+        })
+    }
+}
+
+/// The protocol used to export traces over OTLP.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OtlpProtocol {
+    Grpc,
+    Http,
+}
+
+impl FromStr for OtlpProtocol {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "grpc" => Ok(OtlpProtocol::Grpc),
+            "http" => Ok(OtlpProtocol::Http),
+            _ => Err(format!(
+                "unknown OTLP protocol '{}', expected grpc or http",
+                s
+            )),
+        }
+    }
+}
+
+/// The dialect of StatsD which metrics are pushed in.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatsdFlavor {
+    /// Plain StatsD, without tags.
+    Statsd,
+    /// StatsD with DogStatsD tags.
+    Dogstatsd,
+}
+
+impl FromStr for StatsdFlavor {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "statsd" => Ok(StatsdFlavor::Statsd),
+            "dogstatsd" => Ok(StatsdFlavor::Dogstatsd),
+            _ => Err(format!(
+                "unknown StatsD flavor '{}', expected statsd or dogstatsd",
+                s
+            )),
+        }
+    }
+}
+
+/// The encoder used to format log events.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Pattern,
+    /// A JSON object per line.
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "pattern" | "text" => Ok(LogFormat::Pattern),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!(
+                "unknown log format '{}', expected pattern or json",
+                s
+            )),
+        }
+    }
+}
+
+/// How often the log file is rotated, regardless of its size.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Never,
+    Minutely,
+    Hourly,
+    Daily,
+}
+
+impl LogRotation {
+    /// The length of each period in seconds, if the log file is rotated periodically.
+    pub fn period(self) -> Option<u64> {
+        match self {
+            LogRotation::Never => None,
+            LogRotation::Minutely => Some(60),
+            LogRotation::Hourly => Some(60 * 60),
+            LogRotation::Daily => Some(24 * 60 * 60),
+        }
+    }
+}
+
+impl FromStr for LogRotation {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "never" => Ok(LogRotation::Never),
+            "minutely" => Ok(LogRotation::Minutely),
+            "hourly" => Ok(LogRotation::Hourly),
+            "daily" => Ok(LogRotation::Daily),
+            _ => Err(format!(
+                "unknown log rotation '{}', expected never, minutely, hourly, or daily",
+                s
+            )),
+        }
+    }
+}
+
+/// A size in bytes, written with an optional `K`, `M`, or `G` suffix.
+#[derive(Copy, Clone, Debug, PartialEq, Deserialize, Serialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct ByteSize(pub u64);
+
+impl FromStr for ByteSize {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let trimmed = s.trim().trim_end_matches(['B', 'b']);
+
+        let (digits, scale) = match trimmed.chars().last().map(|c| c.to_ascii_uppercase()) {
+            Some('K') => (&trimmed[..trimmed.len() - 1], 1 << 10),
+            Some('M') => (&trimmed[..trimmed.len() - 1], 1 << 20),
+            Some('G') => (&trimmed[..trimmed.len() - 1], 1 << 30),
+            _ => (trimmed, 1),
+        };
+
+        digits
+            .trim()
+            .parse::<u64>()
+            .ok()
+            .and_then(|n| n.checked_mul(scale))
+            .filter(|bytes| *bytes > 0)
+            .map(ByteSize)
+            .ok_or_else(|| {
+                format!(
+                    "invalid size '{}', expected a number such as 512K or 10M",
+                    s
+                )
+            })
+    }
+}
+
+impl TryFrom<String> for ByteSize {
+    type Error = String;
+
+    fn try_from(value: String) -> Result<Self, Self::Error> {
+        value.parse()
+    }
+}
+
+impl From<ByteSize> for String {
+    fn from(size: ByteSize) -> Self {
+        size.to_string()
+    }
+}
+
+impl fmt::Display for ByteSize {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // use the largest suffix which represents the size exactly
+        for (suffix, scale) in &[("G", 1u64 << 30), ("M", 1 << 20), ("K", 1 << 10)] {
+            if self.0.is_multiple_of(*scale) {
+                return write!(f, "{}{}", self.0 / scale, suffix);
+            }
+        }
+
+        write!(f, "{}", self.0)
+    }
+}
+
 /// An HTTP server which sleeps for a specific or random amount of time.
 ///
 /// Usage information is available over HTTP at /_help or /_usage; use --disable-help to disable this endpoint.
-#[derive(StructOpt)]
+#[derive(Clone, StructOpt)]
 #[structopt(name = "slumberd")]
 pub struct CliArgs {
     /// The amount of time to sleep in milliseconds on each request by default. This value is ignored in random mode.
@@ -41,9 +272,398 @@ pub struct CliArgs {
     /// information from USAGE.md which is compiled into in the binary.
     #[structopt(long = "disable-help")]
     pub disable_help: bool,
+    /// A factor by which every sleep duration is multiplied after being bounded by the minimum and maximum sleep
+    /// times. Responses report both the nominal and the scaled durations. This can be changed at runtime.
+    #[structopt(long = "time-scale", default_value = "1.0")]
+    pub time_scale: f64,
+    /// A TOML or YAML configuration file. Options passed on the command-line or set in SLUMBERD_* environment variables
+    /// take precedence over those in the file.
+    #[structopt(short = "c", long = "config", parse(from_os_str))]
+    pub config: Option<PathBuf>,
+    /// Watch the configuration file for changes and reload it automatically. Configuration can also be reloaded by
+    /// sending SIGHUP or with a POST to /_admin/reload.
+    #[structopt(long = "watch-config")]
+    pub watch_config: bool,
+    /// A bearer token required to use the admin API at /_admin and /_config. If unset, the admin API is refused
+    /// unless --admin-insecure is passed.
+    #[structopt(long = "admin-token")]
+    pub admin_token: Option<String>,
+    /// Allow unauthenticated use of the admin API when no admin token is set.
+    #[structopt(long = "admin-insecure")]
+    pub admin_insecure: bool,
+    /// Serve the admin API on a separate port instead of alongside the sleep handlers.
+    #[structopt(long = "admin-port")]
+    pub admin_port: Option<u64>,
+    /// Serve Prometheus metrics at /_metrics on a separate port instead of alongside the sleep handlers.
+    #[structopt(long = "metrics-port")]
+    pub metrics_port: Option<u64>,
+    /// The number of recent requests kept for /_requests, or 0 to keep none.
+    #[structopt(long = "history-size", default_value = "1000")]
+    pub history_size: usize,
+    /// A header whose value is redacted when echoing requests, in addition to Authorization, Proxy-Authorization,
+    /// Cookie, and Set-Cookie. Can be passed multiple times.
+    #[structopt(long = "redact-header", value_name = "header", number_of_values = 1)]
+    pub redact_headers: Vec<String>,
+    /// A host, or host:port, which requests may ask to be called back at with ?callback=URL. Can be passed multiple
+    /// times. Callbacks are rejected unless their host is allowed.
+    #[structopt(long = "callback-allow", value_name = "host", number_of_values = 1)]
+    pub callback_allow: Vec<String>,
+    /// The number of times a failed callback is retried, backing off exponentially from 100ms.
+    #[structopt(long = "callback-retries", default_value = "3")]
+    pub callback_retries: u32,
+    /// The request header carrying an upstream request id. If present, its value is used as the request id instead of
+    /// a generated one, falling back to the trace id of an incoming traceparent header.
+    #[structopt(long = "request-id-header", default_value = "X-Request-Id")]
+    pub request_id_header: String,
+    /// Export traces over OTLP to a collector, such as http://localhost:4317. Requires slumberd to be built with the
+    /// otlp feature.
+    #[structopt(long = "otlp-endpoint")]
+    pub otlp_endpoint: Option<String>,
+    /// The protocol used to export traces, either grpc or http.
+    #[structopt(long = "otlp-protocol", default_value = "grpc")]
+    pub otlp_protocol: OtlpProtocol,
+    /// The service name reported with exported traces.
+    #[structopt(long = "otlp-service-name", default_value = "slumberd")]
+    pub otlp_service_name: String,
+    /// The ratio of new traces which are exported, between 0 and 1. Traces continued from an incoming traceparent
+    /// header follow the caller's sampling decision.
+    #[structopt(long = "otlp-sample-ratio", default_value = "1.0")]
+    pub otlp_sample_ratio: f64,
+    /// Push metrics over UDP to the StatsD server at the given address, such as 127.0.0.1:8125.
+    #[structopt(long = "statsd", value_name = "address")]
+    pub statsd: Option<String>,
+    /// The prefix of every metric pushed to StatsD.
+    #[structopt(long = "statsd-prefix", default_value = "slumberd")]
+    pub statsd_prefix: String,
+    /// A key:value tag added to every metric pushed to StatsD. Can be passed multiple times.
+    #[structopt(long = "statsd-tag", value_name = "tag", number_of_values = 1)]
+    pub statsd_tags: Vec<String>,
+    /// The dialect of StatsD to push, either statsd, which doesn't support tags, or dogstatsd.
+    #[structopt(long = "statsd-flavor", default_value = "dogstatsd")]
+    pub statsd_flavor: StatsdFlavor,
+    /// Write an access log line for every request to stdout, stderr, or the file at the given path, which is appended
+    /// to.
+    #[structopt(long = "access-log", value_name = "target")]
+    pub access_log: Option<String>,
+    /// The format of access log lines: common, combined, json, or a pattern such as "{method} {path} {status}".
+    #[structopt(long = "access-log-format", default_value = "combined")]
+    pub access_log_format: AccessLogFormat,
+    /// Also write logs to the file at the given path, which is appended to. Logs are still written to stderr.
+    #[structopt(long = "log-file", parse(from_os_str))]
+    pub log_file: Option<PathBuf>,
+    /// The format of the log file, either pattern or json. Defaults to json if --json is passed, or pattern otherwise.
+    #[structopt(long = "log-file-format")]
+    pub log_file_format: Option<LogFormat>,
+    /// Rotate the log file once it would grow beyond this size, such as 10M.
+    #[structopt(long = "log-max-size")]
+    pub log_max_size: Option<ByteSize>,
+    /// Rotate the log file periodically, either never, minutely, hourly, or daily. Periods start on UTC boundaries.
+    #[structopt(long = "log-rotation", default_value = "never")]
+    pub log_rotation: LogRotation,
+    /// The number of rotated log files to keep, deleting the oldest beyond that.
+    #[structopt(long = "log-keep", default_value = "5")]
+    pub log_keep: usize,
+    /// Compress rotated log files with gzip.
+    #[structopt(long = "log-compress")]
+    pub log_compress: bool,
+    #[structopt(subcommand)]
+    pub command: Option<Command>,
+    /// Named latency profiles, only configurable from a configuration file.
+    #[structopt(skip)]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Rules for the catch-all handler, only configurable from a configuration file.
+    #[structopt(skip)]
+    pub rules: Vec<Rule>,
+    /// Where each option which isn't a default came from.
+    #[structopt(skip)]
+    pub sources: BTreeMap<&'static str, ConfigSource>,
+}
+
+#[derive(Clone, StructOpt)]
+pub enum Command {
+    /// Work with configuration files.
+    #[structopt(name = "config")]
+    Config(ConfigCommand),
+}
+
+#[derive(Clone, StructOpt)]
+pub enum ConfigCommand {
+    /// Validate the configuration file and print the effective configuration.
+    #[structopt(name = "check")]
+    Check,
 }
 
 impl CliArgs {
+    /// Load configuration from the command-line, the environment, and the configuration file, if any.
+    pub fn load() -> Result<CliArgs, ConfigError> {
+        Self::load_from(std::env::args_os(), std::env::vars())
+    }
+
+    /// Load configuration from the given arguments, environment variables, and the configuration file they name.
+    ///
+    /// Values are taken from the command-line first, then the environment, then the configuration file, then the
+    /// CLI defaults.
+    pub fn load_from<I, T, V>(args: I, vars: V) -> Result<CliArgs, ConfigError>
+    where
+        I: IntoIterator<Item = T>,
+        T: Into<OsString> + Clone,
+        V: IntoIterator<Item = (String, String)>,
+    {
+        let matches = Self::clap().get_matches_from(args);
+        let flags = Self::from_clap(&matches);
+
+        let vars: BTreeMap<String, String> = vars
+            .into_iter()
+            .filter(|(name, _)| name.starts_with(ENV_PREFIX))
+            .collect();
+
+        let mut config = CliArgs::default();
+
+        let path = flags
+            .config
+            .clone()
+            .or_else(|| vars.get(CONFIG_ENV_VAR).map(PathBuf::from));
+
+        if let Some(path) = &path {
+            config.apply(ConfigFile::load(path)?, ConfigSource::File);
+        }
+
+        config.apply(ConfigFile::from_env(&vars)?, ConfigSource::Env);
+        config.apply_flags(flags, &matches);
+
+        // unlike the sleep settings, which are normalized, there's nothing sensible to fall back to
+        if HeaderName::from_bytes(config.request_id_header.as_bytes()).is_err() {
+            return Err(ConfigError::Invalid(format!(
+                "request id header '{}' is not a valid header name",
+                config.request_id_header
+            )));
+        }
+
+        if !(0.0..=1.0).contains(&config.otlp_sample_ratio) {
+            return Err(ConfigError::Invalid(format!(
+                "OTLP sample ratio ({}) must be between 0 and 1",
+                config.otlp_sample_ratio
+            )));
+        }
+
+        config.config = path;
+
+        Ok(config)
+    }
+
+    /// Overlay the values set in a configuration layer, recording where they came from.
+    fn apply(&mut self, layer: ConfigFile, source: ConfigSource) {
+        macro_rules! overlay {
+            ($($field:ident),*) => {$(
+                if let Some(value) = layer.$field {
+                    self.$field = value;
+                    self.sources.insert(stringify!($field), source);
+                }
+            )*};
+        }
+
+        overlay!(
+            sleep_ms,
+            host,
+            json,
+            min_sleep_ms,
+            max_sleep_ms,
+            port,
+            random,
+            verbosity,
+            disable_help,
+            time_scale,
+            watch_config,
+            admin_insecure,
+            redact_headers,
+            callback_allow,
+            callback_retries,
+            history_size,
+            request_id_header,
+            otlp_protocol,
+            otlp_service_name,
+            otlp_sample_ratio,
+            access_log_format,
+            log_rotation,
+            log_keep,
+            log_compress,
+            statsd_prefix,
+            statsd_tags,
+            statsd_flavor
+        );
+
+        if let Some(statsd) = layer.statsd {
+            self.statsd = Some(statsd);
+            self.sources.insert("statsd", source);
+        }
+
+        if let Some(log_file) = layer.log_file {
+            self.log_file = Some(log_file);
+            self.sources.insert("log_file", source);
+        }
+
+        if let Some(log_file_format) = layer.log_file_format {
+            self.log_file_format = Some(log_file_format);
+            self.sources.insert("log_file_format", source);
+        }
+
+        if let Some(log_max_size) = layer.log_max_size {
+            self.log_max_size = Some(log_max_size);
+            self.sources.insert("log_max_size", source);
+        }
+
+        if let Some(access_log) = layer.access_log {
+            self.access_log = Some(access_log);
+            self.sources.insert("access_log", source);
+        }
+
+        if let Some(otlp_endpoint) = layer.otlp_endpoint {
+            self.otlp_endpoint = Some(otlp_endpoint);
+            self.sources.insert("otlp_endpoint", source);
+        }
+
+        if let Some(admin_token) = layer.admin_token {
+            self.admin_token = Some(admin_token);
+            self.sources.insert("admin_token", source);
+        }
+
+        if let Some(admin_port) = layer.admin_port {
+            self.admin_port = Some(admin_port);
+            self.sources.insert("admin_port", source);
+        }
+
+        if let Some(metrics_port) = layer.metrics_port {
+            self.metrics_port = Some(metrics_port);
+            self.sources.insert("metrics_port", source);
+        }
+
+        if !layer.profiles.is_empty() {
+            self.profiles = layer.profiles;
+        }
+
+        if !layer.rules.is_empty() {
+            self.rules = layer.rules;
+        }
+    }
+
+    /// Overlay the values explicitly passed on the command-line.
+    fn apply_flags(&mut self, flags: CliArgs, matches: &ArgMatches) {
+        // structopt names arguments after their fields in kebab-case
+        macro_rules! overlay {
+            ($($field:ident => $name:expr),*) => {$(
+                if matches.occurrences_of($name) > 0 {
+                    self.$field = flags.$field;
+                    self.sources.insert(stringify!($field), ConfigSource::Flag);
+                }
+            )*};
+        }
+
+        overlay!(
+            sleep_ms => "sleep-ms",
+            host => "host",
+            json => "json",
+            min_sleep_ms => "min-sleep-ms",
+            max_sleep_ms => "max-sleep-ms",
+            port => "port",
+            random => "random",
+            verbosity => "verbosity",
+            disable_help => "disable-help",
+            time_scale => "time-scale",
+            watch_config => "watch-config",
+            admin_token => "admin-token",
+            admin_insecure => "admin-insecure",
+            admin_port => "admin-port",
+            metrics_port => "metrics-port",
+            history_size => "history-size",
+            redact_headers => "redact-headers",
+            callback_allow => "callback-allow",
+            callback_retries => "callback-retries",
+            request_id_header => "request-id-header",
+            otlp_endpoint => "otlp-endpoint",
+            otlp_protocol => "otlp-protocol",
+            otlp_service_name => "otlp-service-name",
+            otlp_sample_ratio => "otlp-sample-ratio",
+            access_log => "access-log",
+            access_log_format => "access-log-format",
+            log_file => "log-file",
+            log_file_format => "log-file-format",
+            log_max_size => "log-max-size",
+            log_rotation => "log-rotation",
+            log_keep => "log-keep",
+            log_compress => "log-compress",
+            statsd => "statsd",
+            statsd_prefix => "statsd-prefix",
+            statsd_tags => "statsd-tags",
+            statsd_flavor => "statsd-flavor"
+        );
+
+        self.command = flags.command;
+    }
+
+    /// The effective value of each option, along with where it came from.
+    pub fn describe(&self) -> Vec<(String, String, ConfigSource)> {
+        let values = match serde_json::to_value(ConfigFile::from(self).redacted()) {
+            Ok(serde_json::Value::Object(values)) => values,
+            _ => return Vec::new(),
+        };
+
+        values
+            .into_iter()
+            .filter(|(_, value)| !value.is_object() && !value.is_array())
+            .map(|(name, value)| {
+                let source = self.source(&name);
+
+                (name, value.to_string(), source)
+            })
+            .collect()
+    }
+
+    /// Where the effective value of an option came from.
+    pub fn source(&self, name: &str) -> ConfigSource {
+        self.sources
+            .get(name)
+            .cloned()
+            .unwrap_or(ConfigSource::Default)
+    }
+
+    /// Check that the sleep settings are consistent, rather than normalizing them as the CLI does.
+    pub fn validate(&self) -> Result<(), String> {
+        self.validate_sleep()?;
+        self.validate_time_scale()
+    }
+
+    /// Check that the minimum, maximum, and default sleep times are consistent.
+    pub fn validate_sleep(&self) -> Result<(), String> {
+        if self.min_sleep_ms > self.max_sleep_ms {
+            return Err(format!(
+                "Minimum sleep time ({}ms) is greater than maximum sleep time ({}ms).",
+                self.min_sleep_ms, self.max_sleep_ms
+            ));
+        }
+
+        if self.sleep_ms < self.min_sleep_ms || self.sleep_ms > self.max_sleep_ms {
+            return Err(format!(
+                "Sleep time ({}ms) is outside of minimum/maximum range ({}ms-{}ms).",
+                self.sleep_ms, self.min_sleep_ms, self.max_sleep_ms
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Check that the time scale is a usable factor.
+    pub fn validate_time_scale(&self) -> Result<(), String> {
+        if !TimeScale::is_valid(self.time_scale) {
+            return Err(format!(
+                "Time scale ({}) must be a positive number no greater than {}.",
+                self.time_scale,
+                TimeScale::MAX
+            ));
+        }
+
+        Ok(())
+    }
+
     /// The default sleep duration.
     pub fn sleep(&self) -> Duration {
         let (min, max) = (self.min_sleep(), self.max_sleep());
@@ -74,21 +694,106 @@ impl CliArgs {
 
         max.max(min)
     }
+
+    /// The factor by which sleep durations are scaled.
+    pub fn time_scale(&self) -> f64 {
+        // prevent footshot: the scale must be a positive number, otherwise sleeps are meaningless, and bounded, otherwise
+        // they can't be represented
+        if TimeScale::is_valid(self.time_scale) {
+            self.time_scale
+        } else {
+            1.0
+        }
+    }
 }
 
 impl Default for CliArgs {
     fn default() -> Self {
-        // FIXME this is duplicated code due to default structopt values listed above, not sure how to combat this
-        Self {
-            sleep_ms: 5000,
-            host: "127.0.0.1".to_string(),
-            json: false,
-            min_sleep_ms: 15,
-            max_sleep_ms: 30000,
-            port: 8080,
-            verbosity: 0,
-            disable_help: false,
-            random: false,
+        // use the structopt defaults as the single source of default values
+        Self::from_iter(&["slumberd"])
+    }
+}
+
+/// The configuration shared between workers, which can be swapped out atomically on reload.
+///
+/// Requests take a snapshot of the configuration when they start, so in-flight requests are unaffected by reloads.
+pub struct SharedConfig(RwLock<Arc<CliArgs>>);
+
+impl SharedConfig {
+    pub fn new(config: CliArgs) -> Self {
+        Self(RwLock::new(Arc::new(config)))
+    }
+
+    /// A snapshot of the current configuration.
+    pub fn current(&self) -> Arc<CliArgs> {
+        self.0.read().clone()
+    }
+
+    /// Replace the configuration for new requests, returning the previous configuration.
+    pub fn replace(&self, config: CliArgs) -> Arc<CliArgs> {
+        std::mem::replace(&mut *self.0.write(), Arc::new(config))
+    }
+
+    /// Atomically modify a copy of the configuration and swap it in, unless the modification fails.
+    pub fn update<F, E>(&self, f: F) -> Result<Arc<CliArgs>, E>
+    where
+        F: FnOnce(&mut CliArgs) -> Result<(), E>,
+    {
+        let mut current = self.0.write();
+        let mut next = (**current).clone();
+
+        f(&mut next)?;
+
+        *current = Arc::new(next);
+
+        Ok(current.clone())
+    }
+}
+
+/// The runtime-adjustable factor by which sleep durations are scaled.
+pub struct TimeScale(AtomicU64);
+
+impl TimeScale {
+    pub fn new(factor: f64) -> Self {
+        Self(AtomicU64::new(factor.to_bits()))
+    }
+
+    /// The largest factor sleep durations can be scaled by.
+    pub const MAX: f64 = 1000.0;
+
+    /// Whether a factor can be used to scale sleep durations.
+    pub fn is_valid(factor: f64) -> bool {
+        factor > 0.0 && factor <= Self::MAX
+    }
+
+    /// Scale a duration by a factor, saturating rather than overflowing for durations too long to represent.
+    pub fn scale(factor: f64, duration: Duration) -> Duration {
+        Duration::try_from_secs_f64(duration.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+    }
+
+    /// The current scale factor.
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::SeqCst))
+    }
+
+    /// Change the scale factor, rejecting factors which aren't positive or are too large.
+    pub fn set(&self, factor: f64) -> Result<(), String> {
+        if !Self::is_valid(factor) {
+            return Err(format!(
+                "Time scale must be a positive number no greater than {}, got {}.",
+                Self::MAX,
+                factor
+            ));
         }
+
+        self.0.store(factor.to_bits(), Ordering::SeqCst);
+
+        Ok(())
+    }
+}
+
+impl Default for TimeScale {
+    fn default() -> Self {
+        Self::new(1.0)
     }
 }
@@ -0,0 +1,441 @@
+use crate::access::AccessLogFormat;
+use crate::handlers::SlumberKind;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use super::{
+    ByteSize, CliArgs, LogFormat, LogRotation, OtlpProtocol, Rule, StatsdFlavor, ENV_PREFIX,
+};
+
+/// The placeholder for secrets in redacted configuration.
+pub const REDACTED: &str = "********";
+
+/// An error encountered while loading or validating configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    Io(io::Error),
+    Toml(toml::de::Error),
+    Yaml(serde_yaml::Error),
+    Invalid(String),
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConfigError::Io(e) => write!(f, "unable to read configuration: {}", e),
+            ConfigError::Toml(e) => write!(f, "invalid TOML configuration: {}", e),
+            ConfigError::Yaml(e) => write!(f, "invalid YAML configuration: {}", e),
+            ConfigError::Invalid(e) => write!(f, "invalid configuration: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<io::Error> for ConfigError {
+    fn from(e: io::Error) -> Self {
+        ConfigError::Io(e)
+    }
+}
+
+/// The format of a configuration file, determined by its extension.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum ConfigFormat {
+    Toml,
+    Yaml,
+}
+
+impl ConfigFormat {
+    pub fn from_path(path: &Path) -> ConfigFormat {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("yaml") | Some("yml") => ConfigFormat::Yaml,
+            _ => ConfigFormat::Toml,
+        }
+    }
+}
+
+/// The schema of a configuration file.
+///
+/// Every CLI option can be set here using the name of the corresponding `CliArgs` field. Unset options fall back to
+/// their CLI defaults, and options passed on the command-line or in the environment always take precedence.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ConfigFile {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sleep_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_sleep_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sleep_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub verbosity: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_help: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_scale: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub watch_config: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_token: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_insecure: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub admin_port: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metrics_port: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub history_size: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub redact_headers: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_allow: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub callback_retries: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id_header: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_endpoint: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_protocol: Option<OtlpProtocol>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_service_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub otlp_sample_ratio: Option<f64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statsd: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statsd_prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statsd_tags: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub statsd_flavor: Option<StatsdFlavor>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_log: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub access_log_format: Option<AccessLogFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file: Option<PathBuf>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_file_format: Option<LogFormat>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_max_size: Option<ByteSize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_rotation: Option<LogRotation>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_keep: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub log_compress: Option<bool>,
+    /// Named latency profiles.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub profiles: BTreeMap<String, Profile>,
+    /// Rules for requests to the catch-all handler, evaluated in order.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub rules: Vec<Rule>,
+}
+
+impl ConfigFile {
+    /// Load and validate a configuration file, choosing the format by its extension.
+    pub fn load(path: &Path) -> Result<ConfigFile, ConfigError> {
+        let contents = fs::read_to_string(path)?;
+
+        let mut file = ConfigFile::parse(&contents, ConfigFormat::from_path(path))?;
+
+        // fixtures are relative to the configuration file rather than the working directory
+        if let Some(base) = path.parent() {
+            for rule in &mut file.rules {
+                rule.resolve_fixture(base);
+            }
+        }
+
+        // fixtures are read and templates compiled once here, and again whenever the configuration is reloaded
+        for (index, rule) in file.rules.iter_mut().enumerate() {
+            rule.load_fixture()
+                .and_then(|_| rule.register_template())
+                .map_err(|e| {
+                    ConfigError::Invalid(format!("rule '{}': {}", rule.label(index), e))
+                })?;
+        }
+
+        file.validate()?;
+
+        Ok(file)
+    }
+
+    /// Build a configuration layer from `SLUMBERD_*` environment variables named after each option.
+    pub fn from_env(vars: &BTreeMap<String, String>) -> Result<ConfigFile, ConfigError> {
+        Ok(ConfigFile {
+            sleep_ms: env_value(vars, "sleep_ms")?,
+            host: env_value(vars, "host")?,
+            json: env_flag(vars, "json")?,
+            min_sleep_ms: env_value(vars, "min_sleep_ms")?,
+            max_sleep_ms: env_value(vars, "max_sleep_ms")?,
+            port: env_value(vars, "port")?,
+            random: env_flag(vars, "random")?,
+            verbosity: env_value(vars, "verbosity")?,
+            disable_help: env_flag(vars, "disable_help")?,
+            time_scale: env_value(vars, "time_scale")?,
+            watch_config: env_flag(vars, "watch_config")?,
+            admin_token: env_value(vars, "admin_token")?,
+            admin_insecure: env_flag(vars, "admin_insecure")?,
+            admin_port: env_value(vars, "admin_port")?,
+            metrics_port: env_value(vars, "metrics_port")?,
+            history_size: env_value(vars, "history_size")?,
+            redact_headers: env_list(vars, "redact_headers"),
+            callback_allow: env_list(vars, "callback_allow"),
+            callback_retries: env_value(vars, "callback_retries")?,
+            request_id_header: env_value(vars, "request_id_header")?,
+            otlp_endpoint: env_value(vars, "otlp_endpoint")?,
+            otlp_protocol: env_value(vars, "otlp_protocol")?,
+            otlp_service_name: env_value(vars, "otlp_service_name")?,
+            otlp_sample_ratio: env_value(vars, "otlp_sample_ratio")?,
+            statsd: env_value(vars, "statsd")?,
+            statsd_prefix: env_value(vars, "statsd_prefix")?,
+            statsd_tags: env_list(vars, "statsd_tags"),
+            statsd_flavor: env_value(vars, "statsd_flavor")?,
+            access_log: env_value(vars, "access_log")?,
+            access_log_format: env_value(vars, "access_log_format")?,
+            log_file: env_value(vars, "log_file")?,
+            log_file_format: env_value(vars, "log_file_format")?,
+            log_max_size: env_value(vars, "log_max_size")?,
+            log_rotation: env_value(vars, "log_rotation")?,
+            log_keep: env_value(vars, "log_keep")?,
+            log_compress: env_flag(vars, "log_compress")?,
+            ..Default::default()
+        })
+    }
+
+    /// Parse configuration in the given format without validating it.
+    pub fn parse(contents: &str, format: ConfigFormat) -> Result<ConfigFile, ConfigError> {
+        match format {
+            ConfigFormat::Toml => toml::from_str(contents).map_err(ConfigError::Toml),
+            ConfigFormat::Yaml => serde_yaml::from_str(contents).map_err(ConfigError::Yaml),
+        }
+    }
+
+    /// Check the configuration for values which can't be normalized.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        for (name, profile) in &self.profiles {
+            if let Some(fault) = &profile.fault {
+                fault
+                    .validate()
+                    .map_err(|e| ConfigError::Invalid(format!("profile '{}': {}", name, e)))?;
+            }
+        }
+
+        for (index, rule) in self.rules.iter().enumerate() {
+            let name = rule.label(index);
+
+            if let Some(profile) = &rule.profile {
+                if !self.profiles.contains_key(profile) {
+                    return Err(ConfigError::Invalid(format!(
+                        "rule '{}' refers to unknown profile '{}'",
+                        name, profile
+                    )));
+                }
+            }
+
+            rule.validate()
+                .map_err(|e| ConfigError::Invalid(format!("rule '{}': {}", name, e)))?;
+        }
+
+        Ok(())
+    }
+
+    /// Hide secrets so that the configuration can be logged or served.
+    pub fn redacted(mut self) -> Self {
+        if self.admin_token.is_some() {
+            self.admin_token = Some(REDACTED.to_string());
+        }
+
+        self
+    }
+
+    /// Report the sleep settings as the server uses them, after normalization.
+    pub fn normalized(mut self, args: &CliArgs) -> Self {
+        self.sleep_ms = Some(args.sleep().as_millis() as u64);
+        self.min_sleep_ms = Some(args.min_sleep().as_millis() as u64);
+        self.max_sleep_ms = Some(args.max_sleep().as_millis() as u64);
+        self.time_scale = Some(args.time_scale());
+
+        self
+    }
+
+    /// Serialize the configuration in the given format.
+    pub fn render(&self, format: ConfigFormat) -> Result<String, ConfigError> {
+        match format {
+            ConfigFormat::Toml => toml::to_string_pretty(self)
+                .map_err(|e| ConfigError::Invalid(format!("unable to render TOML: {}", e))),
+            ConfigFormat::Yaml => serde_yaml::to_string(self).map_err(ConfigError::Yaml),
+        }
+    }
+}
+
+impl From<&CliArgs> for ConfigFile {
+    fn from(args: &CliArgs) -> Self {
+        Self {
+            sleep_ms: Some(args.sleep_ms),
+            host: Some(args.host.clone()),
+            json: Some(args.json),
+            min_sleep_ms: Some(args.min_sleep_ms),
+            max_sleep_ms: Some(args.max_sleep_ms),
+            port: Some(args.port),
+            random: Some(args.random),
+            verbosity: Some(args.verbosity),
+            disable_help: Some(args.disable_help),
+            time_scale: Some(args.time_scale),
+            watch_config: Some(args.watch_config),
+            admin_token: args.admin_token.clone(),
+            admin_insecure: Some(args.admin_insecure),
+            admin_port: args.admin_port,
+            metrics_port: args.metrics_port,
+            history_size: Some(args.history_size),
+            redact_headers: Some(args.redact_headers.clone()),
+            callback_allow: Some(args.callback_allow.clone()),
+            callback_retries: Some(args.callback_retries),
+            request_id_header: Some(args.request_id_header.clone()),
+            otlp_endpoint: args.otlp_endpoint.clone(),
+            otlp_protocol: Some(args.otlp_protocol),
+            otlp_service_name: Some(args.otlp_service_name.clone()),
+            otlp_sample_ratio: Some(args.otlp_sample_ratio),
+            statsd: args.statsd.clone(),
+            statsd_prefix: Some(args.statsd_prefix.clone()),
+            statsd_tags: Some(args.statsd_tags.clone()),
+            statsd_flavor: Some(args.statsd_flavor),
+            access_log: args.access_log.clone(),
+            access_log_format: Some(args.access_log_format.clone()),
+            log_file: args.log_file.clone(),
+            log_file_format: args.log_file_format,
+            log_max_size: args.log_max_size,
+            log_rotation: Some(args.log_rotation),
+            log_keep: Some(args.log_keep),
+            log_compress: Some(args.log_compress),
+            profiles: args.profiles.clone(),
+            rules: args.rules.clone(),
+        }
+    }
+}
+
+/// Look up the environment variable for an option.
+fn env_var<'a>(vars: &'a BTreeMap<String, String>, name: &str) -> Option<(String, &'a str)> {
+    let var = format!("{}{}", ENV_PREFIX, name.to_uppercase());
+
+    vars.get(&var)
+        .map(|value| value.trim())
+        // kubernetes service links set variables like SLUMBERD_PORT=tcp://10.0.0.1:8080 for a service named
+        // slumberd, which are not meant for us (unlike URLs such as the OTLP endpoint)
+        .filter(|value| !value.is_empty() && !is_service_link(value))
+        .map(|value| (var, value))
+}
+
+/// Whether a value looks like the address of a kubernetes service link.
+fn is_service_link(value: &str) -> bool {
+    ["tcp://", "udp://", "sctp://"]
+        .iter()
+        .any(|scheme| value.starts_with(scheme))
+}
+
+/// Parse an option from its environment variable, if set.
+fn env_value<T>(vars: &BTreeMap<String, String>, name: &str) -> Result<Option<T>, ConfigError>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env_var(vars, name) {
+        Some((var, value)) => value.parse().map(Some).map_err(|e| {
+            ConfigError::Invalid(format!("{} has invalid value '{}': {}", var, value, e))
+        }),
+        None => Ok(None),
+    }
+}
+
+/// Parse a comma-separated list from its environment variable, if set.
+fn env_list(vars: &BTreeMap<String, String>, name: &str) -> Option<Vec<String>> {
+    env_var(vars, name).map(|(_, value)| {
+        value
+            .split(',')
+            .map(|item| item.trim().to_string())
+            .filter(|item| !item.is_empty())
+            .collect()
+    })
+}
+
+/// Parse a boolean flag from its environment variable, if set.
+fn env_flag(vars: &BTreeMap<String, String>, name: &str) -> Result<Option<bool>, ConfigError> {
+    match env_var(vars, name) {
+        Some((var, value)) => match value.to_lowercase().as_str() {
+            "1" | "true" | "yes" | "on" => Ok(Some(true)),
+            "0" | "false" | "no" | "off" => Ok(Some(false)),
+            _ => Err(ConfigError::Invalid(format!(
+                "{} has invalid value '{}': expected true or false",
+                var, value
+            ))),
+        },
+        None => Ok(None),
+    }
+}
+
+/// A named bundle of sleep parameters and fault settings.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Profile {
+    #[serde(rename = "type", skip_serializing_if = "Option::is_none")]
+    pub kind: Option<SlumberKind>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fault: Option<FaultSettings>,
+}
+
+/// Settings for injecting error responses in place of a successful slumber.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct FaultSettings {
+    /// The probability between 0 and 1 that a request will fail.
+    pub probability: f64,
+    /// The status code returned by failed requests.
+    #[serde(default = "FaultSettings::default_status")]
+    pub status: u16,
+    /// The body returned by failed requests.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+}
+
+impl FaultSettings {
+    fn default_status() -> u16 {
+        503
+    }
+
+    pub fn validate(&self) -> Result<(), String> {
+        if !(0.0..=1.0).contains(&self.probability) {
+            return Err(format!(
+                "fault probability must be between 0 and 1, got {}",
+                self.probability
+            ));
+        }
+
+        if !(100..=999).contains(&self.status) {
+            return Err(format!("fault status {} is invalid", self.status));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,364 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::http::{HeaderMap, HeaderName, HeaderValue};
+
+use crate::handlers::template;
+
+use regex::Regex;
+
+use serde::de::{self, Deserializer};
+use serde::{Deserialize, Serialize, Serializer};
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+/// A rule matching requests to the catch-all handler, and what to respond to them with.
+///
+/// Every condition which is set must match for the rule to match.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The request method, matched case-insensitively.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    /// A glob matched against the request path.
+    #[serde(
+        default,
+        deserialize_with = "Pattern::deserialize_path_glob",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub path: Option<Pattern>,
+    /// A regular expression searched for in the request path.
+    #[serde(
+        default,
+        deserialize_with = "Pattern::deserialize_regex",
+        skip_serializing_if = "Option::is_none"
+    )]
+    pub path_regex: Option<Pattern>,
+    /// Globs matched against request header values, by header name.
+    #[serde(
+        default,
+        deserialize_with = "Pattern::deserialize_glob_map",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub headers: BTreeMap<String, Pattern>,
+    /// Globs matched against query-string values, by parameter name.
+    #[serde(
+        default,
+        deserialize_with = "Pattern::deserialize_glob_map",
+        skip_serializing_if = "BTreeMap::is_empty"
+    )]
+    pub query: BTreeMap<String, Pattern>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,
+    /// A plain-text response body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// A file whose contents are returned as the response body.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fixture: Option<PathBuf>,
+    /// The content type of the response body, guessed from the fixture's extension if unset.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<String>,
+    /// Headers added to the response.
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub response_headers: BTreeMap<String, String>,
+    /// Render the body or fixture as a template.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub template: bool,
+    /// The contents of the fixture, read when the configuration is loaded.
+    #[serde(skip)]
+    pub fixture_contents: Option<Arc<[u8]>>,
+    /// The name the body or fixture is registered under, if it's a template.
+    #[serde(skip)]
+    pub template_name: Option<String>,
+}
+
+impl Rule {
+    /// The name used to identify the rule at the given position, falling back to its index.
+    pub fn label(&self, index: usize) -> String {
+        self.name.clone().unwrap_or_else(|| format!("#{}", index))
+    }
+
+    /// The content type of the configured response body.
+    pub fn content_type(&self) -> &str {
+        if let Some(content_type) = &self.content_type {
+            return content_type;
+        }
+
+        let extension = self
+            .fixture
+            .as_ref()
+            .and_then(|path| path.extension())
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_lowercase());
+
+        match extension.as_deref() {
+            None if self.fixture.is_none() => "text/plain; charset=utf-8",
+            Some("json") => "application/json",
+            Some("html") | Some("htm") => "text/html; charset=utf-8",
+            Some("txt") => "text/plain; charset=utf-8",
+            Some("xml") => "application/xml",
+            Some("yaml") | Some("yml") => "application/yaml",
+            Some("css") => "text/css",
+            Some("js") => "application/javascript",
+            Some("svg") => "image/svg+xml",
+            Some("png") => "image/png",
+            Some("jpg") | Some("jpeg") => "image/jpeg",
+            Some("gif") => "image/gif",
+            Some("pdf") => "application/pdf",
+            _ => "application/octet-stream",
+        }
+    }
+
+    /// Check the response settings of the rule.
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(status) = self.status {
+            if !(100..=999).contains(&status) {
+                return Err(format!("invalid status {}", status));
+            }
+        }
+
+        if let Some(fixture) = &self.fixture {
+            if self.body.is_some() {
+                return Err("body and fixture can't both be set".to_string());
+            }
+
+            let contents = self
+                .fixture_contents
+                .as_ref()
+                .ok_or_else(|| format!("fixture {} hasn't been read", fixture.display()))?;
+
+            if self.template {
+                let source = std::str::from_utf8(contents)
+                    .map_err(|e| format!("fixture {} isn't UTF-8: {}", fixture.display(), e))?;
+
+                template::validate(source)?;
+            }
+        } else if let (Some(body), true) = (&self.body, self.template) {
+            template::validate(body)?;
+        }
+
+        for (name, value) in &self.response_headers {
+            HeaderName::from_bytes(name.as_bytes())
+                .map_err(|_| format!("invalid response header name '{}'", name))?;
+            HeaderValue::from_str(value)
+                .map_err(|_| format!("invalid value for response header '{}'", name))?;
+        }
+
+        Ok(())
+    }
+
+    /// Read the fixture, if any, so that requests don't have to read it from disk.
+    pub fn load_fixture(&mut self) -> Result<(), String> {
+        if let Some(fixture) = &self.fixture {
+            if !fixture.is_file() {
+                return Err(format!("fixture {} doesn't exist", fixture.display()));
+            }
+
+            let contents = fs::read(fixture)
+                .map_err(|e| format!("unable to read fixture {}: {}", fixture.display(), e))?;
+
+            self.fixture_contents = Some(contents.into());
+        }
+
+        Ok(())
+    }
+
+    /// Compile the body or fixture once if it's a template, so that requests can render it by name.
+    ///
+    /// Fixtures must have been read first.
+    pub fn register_template(&mut self) -> Result<(), String> {
+        if !self.template {
+            return Ok(());
+        }
+
+        let source = match (&self.fixture_contents, &self.body) {
+            (Some(contents), _) => String::from_utf8_lossy(contents).into_owned(),
+            (None, Some(body)) => body.clone(),
+            (None, None) => return Ok(()),
+        };
+
+        self.template_name = Some(template::register(&source)?);
+
+        Ok(())
+    }
+
+    /// Resolve a relative fixture path against the directory of the configuration file.
+    pub fn resolve_fixture(&mut self, base: &Path) {
+        if let Some(fixture) = &mut self.fixture {
+            if fixture.is_relative() {
+                *fixture = base.join(&fixture);
+            }
+        }
+    }
+
+    /// The named captures of the path regex in a request path.
+    pub fn captures(&self, path: &str) -> BTreeMap<String, String> {
+        let pattern = match &self.path_regex {
+            Some(pattern) => pattern,
+            None => return BTreeMap::new(),
+        };
+
+        let captures = match pattern.regex.captures(path) {
+            Some(captures) => captures,
+            None => return BTreeMap::new(),
+        };
+
+        pattern
+            .regex
+            .capture_names()
+            .flatten()
+            .filter_map(|name| {
+                captures
+                    .name(name)
+                    .map(|m| (name.to_string(), m.as_str().to_string()))
+            })
+            .collect()
+    }
+
+    /// Whether a request matches every condition of the rule.
+    pub fn matches(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        query: &[(String, String)],
+    ) -> bool {
+        if let Some(expected) = &self.method {
+            if !expected.eq_ignore_ascii_case(method) {
+                return false;
+            }
+        }
+
+        if !self
+            .path
+            .iter()
+            .chain(&self.path_regex)
+            .all(|p| p.is_match(path))
+        {
+            return false;
+        }
+
+        let header_matches = |(name, pattern): (&String, &Pattern)| {
+            headers
+                .get_all(name.as_str())
+                .filter_map(|value| value.to_str().ok())
+                .any(|value| pattern.is_match(value))
+        };
+
+        let query_matches = |(name, pattern): (&String, &Pattern)| {
+            query
+                .iter()
+                .any(|(key, value)| key == name && pattern.is_match(value))
+        };
+
+        self.headers.iter().all(header_matches) && self.query.iter().all(query_matches)
+    }
+}
+
+/// A compiled glob or regular expression, which serializes as the pattern it was compiled from.
+#[derive(Clone)]
+pub struct Pattern {
+    source: String,
+    regex: Regex,
+}
+
+impl Pattern {
+    /// Compile a glob where `*` matches anything and `?` matches any single character.
+    pub fn glob(glob: &str) -> Result<Self, regex::Error> {
+        Self::compile_glob(glob, ".*", ".")
+    }
+
+    /// Compile a glob for paths, where `*` and `?` don't cross `/` and `**` matches anything.
+    pub fn path_glob(glob: &str) -> Result<Self, regex::Error> {
+        Self::compile_glob(glob, "[^/]*", "[^/]")
+    }
+
+    /// Compile a regular expression, which matches anywhere in the input unless anchored.
+    pub fn regex(regex: &str) -> Result<Self, regex::Error> {
+        Ok(Self {
+            source: regex.to_string(),
+            regex: Regex::new(regex)?,
+        })
+    }
+
+    fn compile_glob(glob: &str, star: &str, single: &str) -> Result<Self, regex::Error> {
+        let mut regex = String::from("^");
+        let mut chars = glob.chars().peekable();
+
+        while let Some(c) = chars.next() {
+            match c {
+                '*' if chars.peek() == Some(&'*') => {
+                    chars.next();
+                    regex.push_str(".*");
+                }
+                '*' => regex.push_str(star),
+                '?' => regex.push_str(single),
+                c => regex.push_str(&regex::escape(&c.to_string())),
+            }
+        }
+
+        regex.push('$');
+
+        Ok(Self {
+            source: glob.to_string(),
+            regex: Regex::new(&regex)?,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, value: &str) -> bool {
+        self.regex.is_match(value)
+    }
+
+    fn deserialize_path_glob<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Self>, D::Error> {
+        let glob = String::deserialize(d)?;
+
+        Self::path_glob(&glob).map(Some).map_err(de::Error::custom)
+    }
+
+    fn deserialize_regex<'de, D: Deserializer<'de>>(d: D) -> Result<Option<Self>, D::Error> {
+        let regex = String::deserialize(d)?;
+
+        Self::regex(&regex).map(Some).map_err(de::Error::custom)
+    }
+
+    fn deserialize_glob_map<'de, D: Deserializer<'de>>(
+        d: D,
+    ) -> Result<BTreeMap<String, Self>, D::Error> {
+        BTreeMap::<String, String>::deserialize(d)?
+            .into_iter()
+            .map(|(name, glob)| Ok((name, Self::glob(&glob).map_err(de::Error::custom)?)))
+            .collect()
+    }
+}
+
+impl fmt::Debug for Pattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Debug::fmt(&self.source, f)
+    }
+}
+
+impl PartialEq for Pattern {
+    fn eq(&self, other: &Self) -> bool {
+        self.source == other.source
+    }
+}
+
+impl Serialize for Pattern {
+    fn serialize<S: Serializer>(&self, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&self.source)
+    }
+}
@@ -0,0 +1,202 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use super::Pattern;
+use super::Rule;
+
+use crate::config::{ConfigFile, ConfigFormat};
+
+use actix_web::http::{HeaderMap, HeaderName, HeaderValue};
+
+use std::fs;
+
+fn query(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_path_glob() {
+    let pattern = Pattern::path_glob("/api/*/items").unwrap();
+
+    assert!(pattern.is_match("/api/v1/items"));
+    assert!(!pattern.is_match("/api/v1/v2/items"));
+    assert!(!pattern.is_match("/api/v1/items/1"));
+
+    let pattern = Pattern::path_glob("/api/**").unwrap();
+
+    assert!(pattern.is_match("/api/v1/items/1"));
+    assert!(!pattern.is_match("/health"));
+
+    // test that regex syntax is taken literally
+    let pattern = Pattern::path_glob("/a.b?").unwrap();
+
+    assert!(pattern.is_match("/a.bc"));
+    assert!(!pattern.is_match("/axbc"));
+}
+
+#[test]
+fn test_glob() {
+    let pattern = Pattern::glob("application/*").unwrap();
+
+    assert!(pattern.is_match("application/json"));
+    assert!(pattern.is_match("application/vnd.api+json"));
+    assert!(!pattern.is_match("text/html"));
+}
+
+#[test]
+fn test_matches() {
+    let file = ConfigFile::parse(
+        r#"
+        [[rules]]
+        name = "search"
+        method = "get"
+        path = "/api/search/**"
+        path_regex = "/[0-9]+$"
+        headers = { Accept = "application/*" }
+        query = { q = "*" }
+        "#,
+        ConfigFormat::Toml,
+    )
+    .unwrap();
+
+    let rule: &Rule = &file.rules[0];
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("accept"),
+        HeaderValue::from_static("application/json"),
+    );
+
+    let q = query(&[("page", "2"), ("q", "shoes")]);
+
+    assert!(rule.matches("GET", "/api/search/items/42", &headers, &q));
+
+    // test that every condition must match
+    assert!(!rule.matches("POST", "/api/search/items/42", &headers, &q));
+    assert!(!rule.matches("GET", "/api/search/items/latest", &headers, &q));
+    assert!(!rule.matches("GET", "/api/health/42", &headers, &q));
+    assert!(!rule.matches("GET", "/api/search/items/42", &headers, &query(&[])));
+    assert!(!rule.matches("GET", "/api/search/items/42", &HeaderMap::new(), &q));
+
+    // test that a rule without conditions matches everything
+    assert!(Rule::default().matches("DELETE", "/", &HeaderMap::new(), &[]));
+}
+
+#[test]
+fn test_invalid_patterns() {
+    assert!(ConfigFile::parse("[[rules]]\npath_regex = \"(\"", ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_content_type() {
+    let mut rule = Rule::default();
+
+    assert_eq!("text/plain; charset=utf-8", rule.content_type());
+
+    rule.fixture = Some("fixtures/search.JSON".into());
+    assert_eq!("application/json", rule.content_type());
+
+    rule.fixture = Some("fixtures/logo.png".into());
+    assert_eq!("image/png", rule.content_type());
+
+    rule.fixture = Some("fixtures/blob".into());
+    assert_eq!("application/octet-stream", rule.content_type());
+
+    // test that an explicit content type wins
+    rule.content_type = Some("application/vnd.api+json".to_string());
+    assert_eq!("application/vnd.api+json", rule.content_type());
+}
+
+#[test]
+fn test_validate() {
+    let dir = std::env::temp_dir().join(format!("slumberd-{}-fixtures", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join("search.json"), "{\"results\": []}").unwrap();
+
+    let path = dir.join("slumberd.toml");
+    fs::write(
+        &path,
+        "[[rules]]\nfixture = \"search.json\"\nresponse_headers = { Cache-Control = \"no-store\" }\n",
+    )
+    .unwrap();
+
+    // test that fixtures are relative to the configuration file
+    let file = ConfigFile::load(&path).unwrap();
+
+    assert_eq!(Some(dir.join("search.json")), file.rules[0].fixture);
+
+    // test that fixtures are read when the configuration is loaded
+    assert_eq!(
+        Some(&b"{\"results\": []}"[..]),
+        file.rules[0].fixture_contents.as_deref()
+    );
+
+    // test missing fixtures
+    let mut rule = Rule::default();
+    rule.fixture = Some(dir.join("missing.json"));
+
+    assert!(rule.load_fixture().is_err());
+    assert!(rule.validate().is_err());
+
+    fs::write(&path, "[[rules]]\nfixture = \"missing.json\"\n").unwrap();
+    assert!(ConfigFile::load(&path).is_err());
+
+    // test conflicting bodies
+    rule.fixture = Some(dir.join("search.json"));
+    assert!(rule.load_fixture().is_ok());
+    assert!(rule.validate().is_ok());
+
+    rule.body = Some("hello".to_string());
+    assert!(rule.validate().is_err());
+
+    // test invalid headers
+    rule.body = None;
+    rule.response_headers
+        .insert("Bad Header".to_string(), "x".to_string());
+
+    assert!(rule.validate().is_err());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_captures() {
+    let mut rule = Rule::default();
+
+    assert!(rule.captures("/users/42").is_empty());
+
+    rule.path_regex = Some(Pattern::regex("^/users/(?P<id>[0-9]+)(/(?P<tab>[a-z]+))?$").unwrap());
+
+    let captures = rule.captures("/users/42");
+
+    assert_eq!(Some("42"), captures.get("id").map(String::as_str));
+    assert!(!captures.contains_key("tab"));
+
+    assert_eq!(
+        Some("posts"),
+        rule.captures("/users/42/posts")
+            .get("tab")
+            .map(String::as_str)
+    );
+}
+
+#[test]
+fn test_validate_template() {
+    let mut rule = Rule::default();
+    rule.body = Some("{{#if request.path}}".to_string());
+
+    // test that bodies are only compiled as templates when asked
+    assert!(rule.validate().is_ok());
+
+    rule.template = true;
+    assert!(rule.validate().is_err());
+
+    rule.body = Some("{{request.path}}".to_string());
+    assert!(rule.validate().is_ok());
+
+    // test that a template is registered once, so that it can be rendered by name
+    assert!(rule.register_template().is_ok());
+    assert!(rule.template_name.is_some());
+}
@@ -0,0 +1,450 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use super::ByteSize;
+use super::CliArgs;
+use super::ConfigFile;
+use super::ConfigFormat;
+use super::ConfigSource;
+use super::FaultSettings;
+use super::LogFormat;
+use super::LogRotation;
+use super::OtlpProtocol;
+use super::StatsdFlavor;
+
+use crate::handlers::SlumberKind;
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Write a configuration file to a unique temporary path.
+fn write_config(name: &str, contents: &str) -> PathBuf {
+    let path = std::env::temp_dir().join(format!("slumberd-{}-{}", std::process::id(), name));
+
+    fs::write(&path, contents).unwrap();
+
+    path
+}
+
+#[test]
+fn test_defaults() {
+    let args = CliArgs::default();
+
+    assert_eq!(5000, args.sleep_ms);
+    assert_eq!("127.0.0.1", args.host);
+    assert!(!args.json);
+    assert_eq!(15, args.min_sleep_ms);
+    assert_eq!(30000, args.max_sleep_ms);
+    assert_eq!(8080, args.port);
+    assert!(!args.random);
+    assert_eq!(0, args.verbosity);
+    assert!(!args.disable_help);
+    assert!((args.time_scale - 1.0).abs() < f64::EPSILON);
+    assert!(args.config.is_none());
+    assert!(args.profiles.is_empty());
+    assert!(args.rules.is_empty());
+}
+
+#[test]
+fn test_parse_toml() {
+    let file = ConfigFile::parse(
+        r#"
+        sleep_ms = 100
+        random = true
+
+        [profiles.db-slow]
+        type = "random"
+        min_ms = 1000
+        max_ms = 3000
+
+        [profiles.db-slow.fault]
+        probability = 0.25
+
+        [[rules]]
+        name = "search"
+        path = "/api/search"
+        profile = "db-slow"
+        "#,
+        ConfigFormat::Toml,
+    )
+    .unwrap();
+
+    assert_eq!(Some(100), file.sleep_ms);
+    assert_eq!(Some(true), file.random);
+    assert_eq!(None, file.host);
+
+    let profile = &file.profiles["db-slow"];
+
+    assert_eq!(Some(SlumberKind::Random), profile.kind);
+    assert_eq!(Some(1000), profile.min_ms);
+    assert_eq!(
+        Some(FaultSettings {
+            probability: 0.25,
+            status: 503,
+            body: None,
+        }),
+        profile.fault
+    );
+
+    assert_eq!(1, file.rules.len());
+    assert_eq!(Some("db-slow".to_string()), file.rules[0].profile);
+    assert!(file.validate().is_ok());
+}
+
+#[test]
+fn test_parse_yaml() {
+    let file = ConfigFile::parse(
+        "max_sleep_ms: 2000\nprofiles:\n  cdn-fast:\n    type: fixed\n    time_ms: 20\n",
+        ConfigFormat::Yaml,
+    )
+    .unwrap();
+
+    assert_eq!(Some(2000), file.max_sleep_ms);
+    assert_eq!(Some(20), file.profiles["cdn-fast"].time_ms);
+}
+
+#[test]
+fn test_parse_rejects_unknown_fields() {
+    assert!(ConfigFile::parse("sleep = 100", ConfigFormat::Toml).is_err());
+    assert!(ConfigFile::parse("[profiles.x]\nfoo = 1", ConfigFormat::Toml).is_err());
+}
+
+#[test]
+fn test_validate() {
+    // test rules referring to unknown profiles
+    let file = ConfigFile::parse(
+        "[[rules]]\nname = \"x\"\nprofile = \"missing\"",
+        ConfigFormat::Toml,
+    )
+    .unwrap();
+
+    assert!(file.validate().is_err());
+
+    // test fault probabilities out of range
+    let file =
+        ConfigFile::parse("[profiles.x.fault]\nprobability = 1.5", ConfigFormat::Toml).unwrap();
+
+    assert!(file.validate().is_err());
+}
+
+#[test]
+fn test_normalized() {
+    let mut args = CliArgs::default();
+    args.min_sleep_ms = 500;
+    args.max_sleep_ms = 100;
+    args.sleep_ms = 50;
+    args.time_scale = -1.0;
+
+    // test that inconsistent sleep settings fail validation, but are reported as the server would use them
+    assert!(args.validate().is_err());
+
+    let file = ConfigFile::from(&args).normalized(&args);
+
+    assert_eq!(Some(100), file.min_sleep_ms);
+    assert_eq!(Some(500), file.max_sleep_ms);
+    assert_eq!(Some(100), file.sleep_ms);
+    assert_eq!(Some(args.time_scale()), file.time_scale);
+}
+
+#[test]
+fn test_format_from_path() {
+    assert_eq!(
+        ConfigFormat::Toml,
+        ConfigFormat::from_path(Path::new("slumberd.toml"))
+    );
+    assert_eq!(
+        ConfigFormat::Yaml,
+        ConfigFormat::from_path(Path::new("slumberd.yaml"))
+    );
+    assert_eq!(
+        ConfigFormat::Yaml,
+        ConfigFormat::from_path(Path::new("slumberd.yml"))
+    );
+}
+
+#[test]
+fn test_load_precedence() {
+    let path = write_config(
+        "precedence.toml",
+        "sleep_ms = 100\nmin_sleep_ms = 50\nmax_sleep_ms = 900\ntime_scale = 0.5\nrandom = true\nverbosity = 1\n",
+    );
+
+    // test that the file overrides defaults
+    let args = CliArgs::load_from(["slumberd", "-c", path.to_str().unwrap()], Vec::new()).unwrap();
+
+    assert_eq!(100, args.sleep_ms);
+    assert_eq!(50, args.min_sleep_ms);
+    assert_eq!(900, args.max_sleep_ms);
+    assert!((args.time_scale - 0.5).abs() < f64::EPSILON);
+    assert!(args.random);
+    assert_eq!(1, args.verbosity);
+    assert_eq!(8080, args.port);
+
+    // test that explicit flags override the file
+    let args = CliArgs::load_from(
+        [
+            "slumberd",
+            "-c",
+            path.to_str().unwrap(),
+            "-s",
+            "200",
+            "--min-sleep",
+            "10",
+            "--max-sleep",
+            "800",
+            "--time-scale",
+            "2",
+            "-vv",
+            "-P",
+            "9090",
+        ],
+        Vec::new(),
+    )
+    .unwrap();
+
+    assert_eq!(200, args.sleep_ms);
+    assert_eq!(10, args.min_sleep_ms);
+    assert_eq!(800, args.max_sleep_ms);
+    assert!((args.time_scale - 2.0).abs() < f64::EPSILON);
+    assert_eq!(2, args.verbosity);
+    assert_eq!(9090, args.port);
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_render_round_trip() {
+    let mut args = CliArgs::default();
+    args.sleep_ms = 1234;
+
+    for format in &[ConfigFormat::Toml, ConfigFormat::Yaml] {
+        let rendered = ConfigFile::from(&args).render(*format).unwrap();
+        let parsed = ConfigFile::parse(&rendered, *format).unwrap();
+
+        assert_eq!(ConfigFile::from(&args), parsed);
+    }
+}
+
+fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+    pairs
+        .iter()
+        .map(|(k, v)| (k.to_string(), v.to_string()))
+        .collect()
+}
+
+#[test]
+fn test_load_env_precedence() {
+    let path = write_config("env.toml", "sleep_ms = 100\nport = 7000\nrandom = false\n");
+
+    // test that the environment overrides the file, and that the file can be named in the environment
+    let args = CliArgs::load_from(
+        ["slumberd"],
+        vars(&[
+            ("SLUMBERD_CONFIG", path.to_str().unwrap()),
+            ("SLUMBERD_SLEEP_MS", "200"),
+            ("SLUMBERD_RANDOM", "true"),
+            ("SLUMBERD_TIME_SCALE", "0.25"),
+            ("SLUMBERD_UNRELATED", "ignored"),
+            ("OTHER_SLEEP_MS", "999"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(Some(path.clone()), args.config);
+    assert_eq!(200, args.sleep_ms);
+    assert_eq!(ConfigSource::Env, args.source("sleep_ms"));
+    assert_eq!(7000, args.port);
+    assert_eq!(ConfigSource::File, args.source("port"));
+    assert!(args.random);
+    assert!((args.time_scale - 0.25).abs() < f64::EPSILON);
+    assert_eq!(ConfigSource::Default, args.source("host"));
+
+    // test that flags override the environment
+    let args = CliArgs::load_from(
+        ["slumberd", "-s", "300"],
+        vars(&[("SLUMBERD_SLEEP_MS", "200")]),
+    )
+    .unwrap();
+
+    assert_eq!(300, args.sleep_ms);
+    assert_eq!(ConfigSource::Flag, args.source("sleep_ms"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_load_redact_headers() {
+    let args = CliArgs::load_from(
+        ["slumberd"],
+        vars(&[("SLUMBERD_REDACT_HEADERS", "X-Api-Key, X-Secret,")]),
+    )
+    .unwrap();
+
+    assert_eq!(vec!["X-Api-Key", "X-Secret"], args.redact_headers);
+    assert_eq!(ConfigSource::Env, args.source("redact_headers"));
+
+    // test that the flag can be repeated and replaces the environment
+    let args = CliArgs::load_from(
+        [
+            "slumberd",
+            "--redact-header",
+            "X-One",
+            "--redact-header",
+            "X-Two",
+        ],
+        vars(&[("SLUMBERD_REDACT_HEADERS", "X-Api-Key")]),
+    )
+    .unwrap();
+
+    assert_eq!(vec!["X-One", "X-Two"], args.redact_headers);
+    assert_eq!(ConfigSource::Flag, args.source("redact_headers"));
+}
+
+#[test]
+fn test_load_callbacks() {
+    let args = CliArgs::load_from(["slumberd"], vars(&[])).unwrap();
+
+    assert!(args.callback_allow.is_empty());
+    assert_eq!(3, args.callback_retries);
+
+    let args = CliArgs::load_from(
+        ["slumberd", "--callback-allow", "127.0.0.1:9000"],
+        vars(&[
+            ("SLUMBERD_CALLBACK_ALLOW", "localhost, example.com"),
+            ("SLUMBERD_CALLBACK_RETRIES", "0"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(vec!["127.0.0.1:9000"], args.callback_allow);
+    assert_eq!(ConfigSource::Flag, args.source("callback_allow"));
+    assert_eq!(0, args.callback_retries);
+    assert_eq!(ConfigSource::Env, args.source("callback_retries"));
+}
+
+#[test]
+fn test_byte_size() {
+    assert_eq!(ByteSize(512), "512".parse().unwrap());
+    assert_eq!(ByteSize(512 * 1024), "512K".parse().unwrap());
+    assert_eq!(ByteSize(10 * 1024 * 1024), "10MB".parse().unwrap());
+    assert_eq!(ByteSize(1 << 30), "1g".parse().unwrap());
+
+    assert!("".parse::<ByteSize>().is_err());
+    assert!("0".parse::<ByteSize>().is_err());
+    assert!("ten megabytes".parse::<ByteSize>().is_err());
+
+    assert_eq!("10M", ByteSize(10 * 1024 * 1024).to_string());
+    assert_eq!("1536K", ByteSize(1536 * 1024).to_string());
+    assert_eq!("1000", ByteSize(1000).to_string());
+}
+
+#[test]
+fn test_load_log_file() {
+    let path = write_config(
+        "log-file.toml",
+        r#"
+        log_file = "slumberd.log"
+        log_file_format = "json"
+        log_max_size = "10M"
+        log_rotation = "daily"
+        "#,
+    );
+
+    let args = CliArgs::load_from(
+        [
+            "slumberd",
+            "--config",
+            path.to_str().unwrap(),
+            "--log-keep",
+            "3",
+        ],
+        vars(&[("SLUMBERD_LOG_COMPRESS", "true")]),
+    )
+    .unwrap();
+
+    assert_eq!(Some(PathBuf::from("slumberd.log")), args.log_file);
+    assert_eq!(Some(LogFormat::Json), args.log_file_format);
+    assert_eq!(Some(ByteSize(10 * 1024 * 1024)), args.log_max_size);
+    assert_eq!(LogRotation::Daily, args.log_rotation);
+    assert_eq!(3, args.log_keep);
+    assert!(args.log_compress);
+    assert_eq!(ConfigSource::File, args.source("log_max_size"));
+    assert_eq!(ConfigSource::Env, args.source("log_compress"));
+    assert_eq!(ConfigSource::Flag, args.source("log_keep"));
+
+    fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_load_statsd() {
+    let args = CliArgs::load_from(
+        [
+            "slumberd",
+            "--statsd-tag",
+            "env:test",
+            "--statsd-tag",
+            "region:eu",
+        ],
+        vars(&[
+            ("SLUMBERD_STATSD", "127.0.0.1:8125"),
+            ("SLUMBERD_STATSD_FLAVOR", "StatsD"),
+            ("SLUMBERD_STATSD_TAGS", "team:sleep"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(Some("127.0.0.1:8125".to_string()), args.statsd);
+    assert_eq!("slumberd", args.statsd_prefix);
+    assert_eq!(StatsdFlavor::Statsd, args.statsd_flavor);
+    assert_eq!(vec!["env:test", "region:eu"], args.statsd_tags);
+    assert_eq!(ConfigSource::Env, args.source("statsd"));
+    assert_eq!(ConfigSource::Flag, args.source("statsd_tags"));
+}
+
+#[test]
+fn test_load_otlp() {
+    let args = CliArgs::load_from(
+        ["slumberd", "--otlp-sample-ratio", "0.25"],
+        vars(&[
+            ("SLUMBERD_OTLP_ENDPOINT", "http://collector:4318"),
+            ("SLUMBERD_OTLP_PROTOCOL", "HTTP"),
+        ]),
+    )
+    .unwrap();
+
+    assert_eq!(Some("http://collector:4318"), args.otlp_endpoint.as_deref());
+    assert_eq!(OtlpProtocol::Http, args.otlp_protocol);
+    assert_eq!("slumberd", args.otlp_service_name);
+    assert!((args.otlp_sample_ratio - 0.25).abs() < f64::EPSILON);
+    assert_eq!(ConfigSource::Env, args.source("otlp_protocol"));
+    assert_eq!(ConfigSource::Flag, args.source("otlp_sample_ratio"));
+}
+
+#[test]
+fn test_load_env_invalid() {
+    assert!(CliArgs::load_from(["slumberd"], vars(&[("SLUMBERD_PORT", "eighty")])).is_err());
+    assert!(CliArgs::load_from(
+        ["slumberd"],
+        vars(&[("SLUMBERD_REQUEST_ID_HEADER", "X Request Id")])
+    )
+    .is_err());
+    assert!(CliArgs::load_from(["slumberd"], vars(&[("SLUMBERD_JSON", "maybe")])).is_err());
+    assert!(
+        CliArgs::load_from(["slumberd"], vars(&[("SLUMBERD_OTLP_PROTOCOL", "thrift")])).is_err()
+    );
+    assert!(CliArgs::load_from(["slumberd", "--otlp-sample-ratio", "1.5"], vars(&[])).is_err());
+    assert!(CliArgs::load_from(
+        ["slumberd"],
+        vars(&[("SLUMBERD_ACCESS_LOG_FORMAT", "{method} {nope}")])
+    )
+    .is_err());
+
+    // test that kubernetes service links are ignored
+    let args = CliArgs::load_from(
+        ["slumberd"],
+        vars(&[("SLUMBERD_PORT", "tcp://10.0.0.1:8080")]),
+    )
+    .unwrap();
+
+    assert_eq!(8080, args.port);
+    assert_eq!(ConfigSource::Default, args.source("port"));
+}
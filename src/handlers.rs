@@ -1,49 +1,123 @@
 #[cfg(test)]
 mod tests;
 
+pub mod admin;
+pub mod gate;
+pub mod history;
+pub mod inflight;
+pub mod template;
+
+mod callback;
+mod format;
 mod response;
+mod sample;
+mod trace;
 
+use actix_web::error::{ErrorInternalServerError, ErrorPayloadTooLarge, InternalError};
 use actix_web::web::Path;
+use actix_web::web::{BytesMut, Payload};
 use actix_web::web::{Data, Query};
 use actix_web::{Error, HttpRequest, HttpResponse};
 
-use crate::config::CliArgs;
+use crate::config::{CliArgs, FaultSettings, Profile, Rule, SharedConfig, TimeScale, REDACTED};
+use crate::metrics::Metrics;
+
+use chrono::{DateTime, Utc};
 
-use futures::{future, Future};
+use futures::{future, Future, Stream};
 
 use rand::{thread_rng, Rng};
 
-use self::response::SlumberResponse;
+use self::admin::ErrorBody;
+use self::callback::Callback;
+use self::format::ResponseFormat;
+use self::gate::Gates;
+use self::history::{History, RequestRecord};
+use self::inflight::{InFlightRequest, InFlightRequests, Sleep, Wake};
+use self::response::{
+    BodyDigest, DurationTrace, KindTrace, ProfileTrace, SlumberEcho, SlumberExplain,
+    SlumberResponse,
+};
+use self::sample::{SampleQueryParams, SampleResponse};
+use self::template::TemplateRequest;
+use self::trace::TraceContext;
 
 use serde::Deserialize;
 use serde::Serialize;
 
 use std::cmp::Ord;
-use std::time::Duration;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
-use tokio::prelude::FutureExt;
+use tracing::Span;
 
+use actix_web::http::header::{ACCEPT, VARY};
 use actix_web::http::{HeaderMap, StatusCode};
 use uuid::Uuid;
 
-static MINIMUM_SLEEP_TIME_HEADER: &'static str = "X-Slumber-Min-Time";
+static MINIMUM_SLEEP_TIME_HEADER: &str = "X-Slumber-Min-Time";
+
+static MINIMUM_SLEEP_TIME_MS_HEADER: &str = "X-Slumber-Min-Time-Millis";
+
+static MAXIMUM_SLEEP_TIME_HEADER: &str = "X-Slumber-Max-Time";
+
+static MAXIMUM_SLEEP_TIME_MS_HEADER: &str = "X-Slumber-Max-Time-Millis";
+
+static CALLBACK_HEADER: &str = "X-Slumber-Callback";
+
+static CALLBACK_ASYNC_HEADER: &str = "X-Slumber-Callback-Async";
+
+static CALLBACK_DELAY_MS_HEADER: &str = "X-Slumber-Callback-Delay-Millis";
+
+static DRY_RUN_HEADER: &str = "X-Slumber-Dry-Run";
+
+static ECHO_HEADER: &str = "X-Slumber-Echo";
+
+static EXPLAIN_HEADER: &str = "X-Slumber-Explain";
+
+static FAULT_HEADER: &str = "X-Slumber-Fault";
+
+static GATE_HEADER: &str = "X-Slumber-Gate";
+
+static GATE_OPENED_HEADER: &str = "X-Slumber-Gate-Opened";
+
+static GATE_TIMEOUT_MS_HEADER: &str = "X-Slumber-Gate-Timeout-Millis";
+
+static PROFILE_HEADER: &str = "X-Slumber-Profile";
+
+static REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+static RULE_HEADER: &str = "X-Slumber-Rule";
+
+static TRACEPARENT_HEADER: &str = "traceparent";
+
+static TRACESTATE_HEADER: &str = "tracestate";
+
+static SCALED_SLEEP_TIME_HEADER: &str = "X-Slumber-Scaled-Time";
 
-static MINIMUM_SLEEP_TIME_MS_HEADER: &'static str = "X-Slumber-Min-Time-Millis";
+static SCALED_SLEEP_TIME_MS_HEADER: &str = "X-Slumber-Scaled-Time-Millis";
 
-static MAXIMUM_SLEEP_TIME_HEADER: &'static str = "X-Slumber-Max-Time";
+static SLEEP_TIME_HEADER: &str = "X-Slumber-Time";
 
-static MAXIMUM_SLEEP_TIME_MS_HEADER: &'static str = "X-Slumber-Max-Time-Millis";
+static SLEEP_TIME_MS_HEADER: &str = "X-Slumber-Time-Millis";
 
-static REQUEST_ID_HEADER: &'static str = "X-Request-Id";
+static SLEEP_KIND_HEADER: &str = "X-Slumber-Type";
 
-static SLEEP_TIME_HEADER: &'static str = "X-Slumber-Time";
+static TIME_SCALE_HEADER: &str = "X-Slumber-Time-Scale";
 
-static SLEEP_TIME_MS_HEADER: &'static str = "X-Slumber-Time-Millis";
+/// Headers whose values are always redacted when echoing a request.
+static REDACTED_HEADERS: [&str; 4] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+];
 
-static SLEEP_KIND_HEADER: &'static str = "X-Slumber-Type";
+/// The longest incoming request id which is reused rather than replaced.
+const MAX_REQUEST_ID_LEN: usize = 256;
 
-static USAGE_TEXT: &'static str =
-    include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", "USAGE.md"));
+static USAGE_TEXT: &str = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/", "USAGE.md"));
 
 #[derive(Copy, Clone, Debug, Deserialize, PartialEq, Serialize)]
 #[serde(rename_all = "snake_case")]
@@ -52,6 +126,45 @@ pub enum SlumberKind {
     Random,
 }
 
+impl SlumberKind {
+    fn as_str(self) -> &'static str {
+        match self {
+            SlumberKind::Fixed => "fixed",
+            SlumberKind::Random => "random",
+        }
+    }
+}
+
+/// The place a request parameter was resolved from, in order of priority.
+#[derive(Copy, Clone, Debug, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ParamSource {
+    Path,
+    Query,
+    Header,
+    Rule,
+    Profile,
+    Cli,
+}
+
+/// A resolved request parameter along with the source that supplied it and its raw value.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Sourced<T> {
+    pub value: T,
+    pub source: ParamSource,
+    pub raw: String,
+}
+
+impl<T> Sourced<T> {
+    fn new<S: Into<String>>(value: T, source: ParamSource, raw: S) -> Self {
+        Self {
+            value,
+            source,
+            raw: raw.into(),
+        }
+    }
+}
+
 #[derive(Default, Deserialize)]
 pub struct SleepQueryParams {
     #[serde(rename = "type")]
@@ -60,27 +173,377 @@ pub struct SleepQueryParams {
     pub max: Option<u64>,
     #[serde(rename = "time")]
     pub duration: Option<u64>,
+    pub explain: Option<String>,
+    pub dry_run: Option<String>,
+    pub echo: Option<String>,
+    pub profile: Option<String>,
+    pub format: Option<String>,
+    pub gate: Option<String>,
+    pub gate_timeout: Option<u64>,
+    pub callback: Option<String>,
+    pub callback_delay: Option<u64>,
+    pub callback_async: Option<String>,
+}
+
+/// The sleep parameters of a request, each tagged with where it was resolved from.
+struct SlumberParams {
+    kind: Sourced<SlumberKind>,
+    time: Sourced<Duration>,
+    min: Sourced<Duration>,
+    max: Sourced<Duration>,
+    profile: Option<Sourced<String>>,
+    fault: Option<FaultSettings>,
+    rule: Option<RuleOutcome>,
+    gate: Option<GateParams>,
+    callback: Option<Callback>,
+    format: ResponseFormat,
+    explain: bool,
+    dry_run: bool,
+    echo: bool,
+    request_id: String,
+    trace: Option<TraceContext>,
+    trace_state: Option<String>,
+}
+
+/// The rule which matched a request, which configures its response.
+#[derive(Clone)]
+struct RuleOutcome {
+    name: String,
+    rule: Rule,
+    /// The request, if the rule's response is a template.
+    request: Option<TemplateRequest>,
+}
+
+/// The gate which a request waits at instead of sleeping.
+#[derive(Clone, Debug, PartialEq)]
+struct GateParams {
+    name: String,
+    /// The longest the request waits for the gate to open.
+    timeout: Duration,
+}
+
+/// The body returned when a request will call back in the background.
+#[derive(Serialize)]
+struct AcceptedBody {
+    request_id: String,
+    /// The URL which is called back once the request has slept.
+    callback: String,
+}
+
+/// The data available to response templates.
+#[derive(Serialize)]
+struct TemplateData<'a> {
+    #[serde(flatten)]
+    response: &'a SlumberResponse,
+    request: &'a TemplateRequest,
+}
+
+impl SlumberParams {
+    /// Extract all parameters from the query string, the headers, the selected profile, or the configuration
+    /// defaults.
+    fn extract(
+        headers: &HeaderMap,
+        query: &SleepQueryParams,
+        config: &CliArgs,
+    ) -> Result<Self, Error> {
+        Self::extract_with_profile(headers, query, config, extract_profile(headers, query))
+    }
+
+    /// Extract all parameters, using the given profile rather than the one selected by the request.
+    fn extract_with_profile(
+        headers: &HeaderMap,
+        query: &SleepQueryParams,
+        config: &CliArgs,
+        profile: Option<Sourced<String>>,
+    ) -> Result<Self, Error> {
+        let trace = headers
+            .get(TRACEPARENT_HEADER)
+            .and_then(|h| h.to_str().ok())
+            .and_then(TraceContext::parse);
+
+        let mut params = Self {
+            request_id: extract_request_id(headers, trace.as_ref(), config),
+            trace: trace.map(|trace| trace.child()),
+            trace_state: headers
+                .get(TRACESTATE_HEADER)
+                .and_then(|h| h.to_str().ok())
+                .map(str::to_string),
+            kind: extract_sleep_kind(headers, query, config),
+            time: extract_sleep_time(headers, query, config),
+            min: extract_sleep_min_time(headers, query, config),
+            max: extract_sleep_max_time(headers, query, config),
+            profile: None,
+            fault: None,
+            rule: None,
+            gate: extract_gate(headers, query, config),
+            callback: extract_callback(headers, query, config)?,
+            format: extract_format(headers, query)?,
+            explain: extract_flag(headers, EXPLAIN_HEADER, query.explain.as_deref()),
+            dry_run: extract_flag(headers, DRY_RUN_HEADER, query.dry_run.as_deref()),
+            echo: extract_flag(headers, ECHO_HEADER, query.echo.as_deref()),
+        };
+
+        if let Some(name) = profile {
+            match config.profiles.get(&name.value) {
+                Some(profile) => params.apply_profile(name, profile),
+                None => {
+                    let response = HttpResponse::NotFound().json(ErrorBody {
+                        error: format!("Unknown profile '{}'.", name.value),
+                    });
+
+                    return Err(InternalError::from_response("unknown profile", response).into());
+                }
+            }
+        }
+
+        Ok(params)
+    }
+
+    /// Fill in the parameters which weren't given explicitly from a profile.
+    ///
+    /// Profiles take priority over the configuration defaults only, so explicit request parameters still win.
+    fn apply_profile(&mut self, name: Sourced<String>, profile: &Profile) {
+        if self.kind.source == ParamSource::Cli {
+            if let Some(kind) = profile.kind {
+                self.kind = Sourced::new(kind, ParamSource::Profile, kind.as_str());
+            }
+        }
+
+        let durations = [
+            (&mut self.time, profile.time_ms),
+            (&mut self.min, profile.min_ms),
+            (&mut self.max, profile.max_ms),
+        ];
+
+        for (param, millis) in durations {
+            if let (ParamSource::Cli, Some(millis)) = (param.source, millis) {
+                *param = Sourced::new(
+                    Duration::from_millis(millis),
+                    ParamSource::Profile,
+                    millis.to_string(),
+                );
+            }
+        }
+
+        self.profile = Some(name);
+        self.fault = profile.fault.clone();
+    }
+
+    /// Resolve the parameters into a slumber, enforcing the configured bounds.
+    fn resolve(&self, config: &CliArgs) -> SlumberConfig {
+        let mut slumber = match self.kind.value {
+            SlumberKind::Random => SlumberConfig::random(&self.min.value, &self.max.value, config),
+            SlumberKind::Fixed => SlumberConfig::fixed(&self.time.value, config),
+        };
+
+        if self.explain {
+            slumber.explain = Some(self.trace(&slumber));
+        }
+
+        slumber.id = self.request_id.clone();
+        slumber.id_header = config.request_id_header.clone();
+        slumber.trace = self.trace.clone();
+        slumber.trace_state = self.trace_state.clone();
+        slumber.profile = self.profile.as_ref().map(|name| name.value.clone());
+        slumber.rule = self.rule.clone();
+        slumber.format = self.format;
+        slumber.dry_run = self.dry_run;
+
+        // roll the dice on whether this request fails
+        slumber.fault = self
+            .fault
+            .as_ref()
+            .filter(|fault| thread_rng().gen_bool(fault.probability))
+            .cloned();
+
+        slumber
+    }
+
+    /// Resolve the parameters of a request which is being served, rather than sampled, recording any clamping.
+    fn resolve_served(
+        &self,
+        config: &CliArgs,
+        scale: f64,
+        route: &'static str,
+        metrics: Option<Data<Metrics>>,
+        span: Span,
+    ) -> SlumberConfig {
+        let mut slumber = self.resolve(config).scaled(scale);
+
+        // a gated request waits for its gate rather than sleeping, up to its timeout
+        if let Some(gate) = &self.gate {
+            slumber.duration = gate.timeout;
+            slumber.scale = 1.0;
+            slumber.scaled = gate.timeout;
+            slumber.gate = Some(gate.name.clone());
+        }
+
+        // the delay before calling back is dilated along with the sleep
+        slumber.callback = self.callback.clone().map(|mut callback| {
+            callback.delay = TimeScale::scale(scale, callback.delay);
+            callback
+        });
+
+        span.record("duration", tracing::field::debug(&slumber.duration));
+        span.record("min", tracing::field::debug(&slumber.min));
+        span.record("max", tracing::field::debug(&slumber.max));
+        span.record("scaled", tracing::field::debug(&slumber.scaled));
+
+        span.in_scope(|| {
+            tracing::debug!(
+                profile = slumber.profile.as_deref(),
+                rule = slumber.rule.as_ref().map(|outcome| outcome.name.as_str()),
+                "Resolved sleep."
+            )
+        });
+
+        // a gated request isn't clamped, as it waits for its gate rather than sleeping for the resolved duration
+        if let Some(metrics) = metrics.as_ref().filter(|_| self.gate.is_none()) {
+            let clamped = match slumber.kind {
+                SlumberKind::Fixed => vec![("time", self.time.value != slumber.duration)],
+                SlumberKind::Random => vec![
+                    ("min_time", self.min.value != slumber.min),
+                    ("max_time", self.max.value != slumber.max),
+                ],
+            };
+
+            for (param, _) in clamped.into_iter().filter(|(_, clamped)| *clamped) {
+                metrics.clamped(param);
+            }
+        }
+
+        slumber.route = route;
+        slumber.metrics = metrics;
+        slumber.span = span;
+        slumber
+    }
+
+    /// Build the resolution trace for a resolved slumber.
+    fn trace(&self, slumber: &SlumberConfig) -> SlumberExplain {
+        let kind = KindTrace {
+            source: self.kind.source,
+            raw: self.kind.raw.clone(),
+        };
+
+        let profile = self.profile.as_ref().map(|name| ProfileTrace {
+            source: name.source,
+            raw: name.raw.clone(),
+        });
+
+        // the request's bounds are only used in random mode, while fixed mode is bounded by the configuration
+        let (time, min, max) = match slumber.kind {
+            SlumberKind::Fixed => (
+                DurationTrace::new(
+                    self.time.source,
+                    &self.time.raw,
+                    &self.time.value,
+                    &slumber.duration,
+                ),
+                DurationTrace::unused(self.min.source, &self.min.raw),
+                DurationTrace::unused(self.max.source, &self.max.raw),
+            ),
+            SlumberKind::Random => (
+                DurationTrace::unused(self.time.source, &self.time.raw),
+                DurationTrace::new(
+                    self.min.source,
+                    &self.min.raw,
+                    &self.min.value,
+                    &slumber.min,
+                ),
+                DurationTrace::new(
+                    self.max.source,
+                    &self.max.raw,
+                    &self.max.value,
+                    &slumber.max,
+                ),
+            ),
+        };
+
+        SlumberExplain {
+            kind,
+            profile,
+            time,
+            min,
+            max,
+        }
+    }
 }
 
 struct SlumberConfig {
-    id: Uuid,
+    id: String,
+    id_header: String,
+    trace: Option<TraceContext>,
+    trace_state: Option<String>,
     kind: SlumberKind,
     min: Duration,
     max: Duration,
     duration: Duration,
+    scale: f64,
+    scaled: Duration,
+    explain: Option<SlumberExplain>,
+    profile: Option<String>,
+    rule: Option<RuleOutcome>,
+    format: ResponseFormat,
+    dry_run: bool,
+    fault: Option<FaultSettings>,
+    echo: Option<SlumberEcho>,
+    /// The route which served the request, for metrics.
+    route: &'static str,
+    metrics: Option<Data<Metrics>>,
+    /// The request as received, if it's being served rather than sampled.
+    received: Option<Received>,
+    /// The history which the request is recorded in once served, if any.
+    history: Option<Data<History>>,
+    /// The registry which the request is listed in while it sleeps, if any.
+    inflight: Option<InFlightRequests>,
+    /// The gate which the request waits at instead of sleeping, if any.
+    gate: Option<String>,
+    /// Whether the gate opened, rather than the request timing out.
+    gate_opened: bool,
+    gates: Option<Gates>,
+    /// The URL which is called back with the response once the request has slept, if any.
+    callback: Option<Callback>,
+    /// The time actually spent sleeping.
+    actual: Duration,
+    /// The span which the request's events are recorded in.
+    span: Span,
 }
 
 impl SlumberConfig {
     /// Generate a fixed-time slumber.
     fn fixed(req: &Duration, config: &CliArgs) -> Self {
         let (min, max) = (config.min_sleep(), config.max_sleep());
+        let duration = SleepBounds::duration(req, &min, &max);
 
         Self {
-            id: Uuid::new_v4(),
+            id: String::new(),
+            id_header: String::new(),
+            trace: None,
+            trace_state: None,
             kind: SlumberKind::Fixed,
             min,
             max,
-            duration: SleepBounds::duration(req, &min, &max),
+            duration,
+            scale: 1.0,
+            scaled: duration,
+            explain: None,
+            profile: None,
+            rule: None,
+            format: ResponseFormat::default(),
+            dry_run: false,
+            fault: None,
+            echo: None,
+            route: "",
+            metrics: None,
+            received: None,
+            history: None,
+            inflight: None,
+            gate: None,
+            gate_opened: false,
+            gates: None,
+            callback: None,
+            actual: Duration::from_secs(0),
+            span: Span::none(),
         }
     }
 
@@ -91,18 +554,64 @@ impl SlumberConfig {
 
         // pre-calculate these
         let (min, max) = (
-            SleepBounds::min(&req_min, &req_max, &cfg_min, &cfg_max),
-            SleepBounds::max(&req_min, &req_max, &cfg_min, &cfg_max),
+            SleepBounds::min(req_min, req_max, &cfg_min, &cfg_max),
+            SleepBounds::max(req_min, req_max, &cfg_min, &cfg_max),
         );
 
+        // an empty range can't be sampled from, so use the only value it contains
+        let duration = if min < max {
+            thread_rng().gen_range(min, max)
+        } else {
+            min
+        };
+
         Self {
-            id: Uuid::new_v4(),
+            id: String::new(),
+            id_header: String::new(),
+            trace: None,
+            trace_state: None,
             kind: SlumberKind::Random,
             min,
             max,
-            duration: thread_rng().gen_range(min, max),
+            duration,
+            scale: 1.0,
+            scaled: duration,
+            explain: None,
+            profile: None,
+            rule: None,
+            format: ResponseFormat::default(),
+            dry_run: false,
+            fault: None,
+            echo: None,
+            route: "",
+            metrics: None,
+            received: None,
+            history: None,
+            inflight: None,
+            gate: None,
+            gate_opened: false,
+            gates: None,
+            callback: None,
+            actual: Duration::from_secs(0),
+            span: Span::none(),
         }
     }
+
+    /// Scale the sleep duration by a time-dilation factor, keeping the nominal duration for reporting.
+    fn scaled(mut self, factor: f64) -> Self {
+        self.scale = factor;
+        // a duration too long to represent saturates, and is later capped by the sleep itself
+        self.scaled = TimeScale::scale(factor, self.duration);
+
+        self
+    }
+}
+
+/// The details of a request captured when it was received, for describing it while and after it's served.
+pub struct Received {
+    pub time: DateTime<Utc>,
+    pub method: String,
+    pub path: String,
 }
 
 struct SleepBounds;
@@ -110,7 +619,7 @@ struct SleepBounds;
 impl SleepBounds {
     fn duration(req: &Duration, min: &Duration, max: &Duration) -> Duration {
         // enforce the duration being >= the minimum and <= the maximum
-        req.max(min).min(max).clone()
+        *req.max(min).min(max)
     }
 
     fn max(
@@ -125,7 +634,7 @@ impl SleepBounds {
         let upper = req_max.min(config_max);
 
         // choose the largest bound between the lower and upper bounds
-        lower.max(upper).clone()
+        *lower.max(upper)
     }
 
     fn min(
@@ -140,7 +649,7 @@ impl SleepBounds {
         let upper = req_max.min(config_max);
 
         // choose the smallest bound between the lower and upper bounds
-        lower.min(upper).clone()
+        *lower.min(upper)
     }
 }
 
@@ -150,27 +659,226 @@ type SlumberFuture = Box<dyn Future<Item = HttpResponse, Error = Error>>;
 /// The default handler for non-specific path-based requests.
 pub fn default(
     req: HttpRequest,
-    data: Data<CliArgs>,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
     query: Query<SleepQueryParams>,
+    payload: Payload,
 ) -> SlumberFuture {
-    let kind = extract_sleep_kind(req.headers(), &query, &data);
+    let data = config.current();
+    let pairs = Query::<Vec<(String, String)>>::from_query(req.query_string())
+        .map(Query::into_inner)
+        .unwrap_or_default();
+    let rule = match_rule(&req, &pairs, &data.rules);
+
+    // a profile selected by the request takes priority over the rule's profile
+    let profile = extract_profile(req.headers(), &query).or_else(|| {
+        rule.and_then(|(_, r)| r.profile.as_ref())
+            .map(|name| Sourced::new(name.clone(), ParamSource::Rule, name.as_str()))
+    });
+
+    let mut params =
+        match SlumberParams::extract_with_profile(req.headers(), &query, &data, profile) {
+            Ok(params) => params,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+    if let Some((index, rule)) = rule {
+        params.rule = Some(RuleOutcome {
+            name: rule.label(index),
+            rule: rule.clone(),
+            request: if rule.template {
+                Some(template_request(&req, rule, pairs))
+            } else {
+                None
+            },
+        });
+    }
+
+    serve(&req, payload, params, data, scale.get(), "default")
+}
+
+/// Describe a request for use in response templates, without its body.
+fn template_request(
+    req: &HttpRequest,
+    rule: &Rule,
+    pairs: Vec<(String, String)>,
+) -> TemplateRequest {
+    let mut headers = BTreeMap::<String, String>::new();
+
+    for (name, value) in req.headers().iter() {
+        let value = String::from_utf8_lossy(value.as_bytes());
+
+        headers
+            .entry(name.as_str().to_string())
+            .and_modify(|v| *v = format!("{}, {}", v, value))
+            .or_insert_with(|| value.into_owned());
+    }
+
+    TemplateRequest {
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        params: rule.captures(req.path()),
+        query: pairs.into_iter().collect(),
+        headers,
+        ..Default::default()
+    }
+}
+
+/// Describe a request for echoing back in the response, without its body.
+fn echo_request(req: &HttpRequest, config: &CliArgs) -> SlumberEcho {
+    let mut headers = BTreeMap::<String, String>::new();
+
+    for (name, value) in req.headers().iter() {
+        let value = if is_redacted(name.as_str(), config) {
+            REDACTED.into()
+        } else {
+            String::from_utf8_lossy(value.as_bytes())
+        };
+
+        headers
+            .entry(name.as_str().to_string())
+            .and_modify(|v| *v = format!("{}, {}", v, value))
+            .or_insert_with(|| value.into_owned());
+    }
 
-    let (min, max) = (
-        extract_sleep_min_time(req.headers(), &query, &data),
-        extract_sleep_max_time(req.headers(), &query, &data),
+    SlumberEcho {
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+        query: Query::<Vec<(String, String)>>::from_query(req.query_string())
+            .map(|pairs| pairs.into_inner().into_iter().collect())
+            .unwrap_or_default(),
+        headers,
+        client_ip: req.peer_addr().map(|addr| addr.ip().to_string()),
+        http_version: format!("{:?}", req.version()),
+        body: BodyDigest::default(),
+    }
+}
+
+/// Check whether the value of a header should be hidden when echoing a request.
+fn is_redacted(name: &str, config: &CliArgs) -> bool {
+    REDACTED_HEADERS
+        .iter()
+        .copied()
+        .chain(config.redact_headers.iter().map(String::as_str))
+        .any(|redacted| redacted.eq_ignore_ascii_case(name))
+}
+
+/// Serve a request with resolved parameters, reading its body first if the response needs it.
+fn serve(
+    req: &HttpRequest,
+    payload: Payload,
+    mut params: SlumberParams,
+    config: Arc<CliArgs>,
+    scale: f64,
+    route: &'static str,
+) -> SlumberFuture {
+    let metrics = req.get_app_data::<Metrics>();
+    let span = tracing::info_span!(
+        "slumber",
+        request_id = params.request_id.as_str(),
+        kind = params.kind.value.as_str(),
+        route,
+        client = req.peer_addr().map(tracing::field::display),
+        duration = tracing::field::Empty,
+        min = tracing::field::Empty,
+        max = tracing::field::Empty,
+        scaled = tracing::field::Empty,
+        status = tracing::field::Empty,
     );
 
-    slumber(match kind {
-        SlumberKind::Random => SlumberConfig::random(&min, &max, &data),
-        SlumberKind::Fixed => {
-            SlumberConfig::fixed(&extract_sleep_time(req.headers(), &query, &data), &data)
+    // continue the caller's trace, reporting the exported span as the parent of anything downstream
+    #[cfg(feature = "otlp")]
+    {
+        if let Some(span_id) = crate::telemetry::link(&span, req.headers()) {
+            if let Some(trace) = &mut params.trace {
+                trace.parent_id = span_id;
+            }
         }
-    })
+    }
+
+    let echo = if params.echo {
+        Some(echo_request(req, &config))
+    } else {
+        None
+    };
+
+    let history = req.get_app_data::<History>();
+    let inflight = req
+        .get_app_data::<InFlightRequests>()
+        .map(|inflight| inflight.get_ref().clone());
+    let gates = req
+        .get_app_data::<Gates>()
+        .map(|gates| gates.get_ref().clone());
+    let received = Received {
+        time: Utc::now(),
+        method: req.method().to_string(),
+        path: req.path().to_string(),
+    };
+
+    let templated = params
+        .rule
+        .as_ref()
+        .is_some_and(|outcome| outcome.request.is_some());
+
+    if echo.is_none() && !templated {
+        let mut resolved = params.resolve_served(&config, scale, route, metrics, span);
+        resolved.received = Some(received);
+        resolved.history = history;
+        resolved.inflight = inflight;
+        resolved.gates = gates;
+
+        return slumber(resolved);
+    }
+
+    Box::new(read_body(payload).and_then(move |body| {
+        if let Some(outcome) = &mut params.rule {
+            outcome.request = outcome.request.take().map(|r| r.with_body(&body));
+        }
+
+        let mut resolved = params.resolve_served(&config, scale, route, metrics, span);
+        resolved.received = Some(received);
+        resolved.history = history;
+        resolved.inflight = inflight;
+        resolved.gates = gates;
+        resolved.echo = echo.map(|echo| echo.with_body(&body));
+
+        slumber(resolved)
+    }))
+}
+
+/// Read a request body, rejecting bodies which are too large to template or echo.
+fn read_body(payload: Payload) -> impl Future<Item = BytesMut, Error = Error> {
+    payload
+        .map_err(Error::from)
+        .fold(BytesMut::new(), |mut body, chunk| {
+            if body.len() + chunk.len() > template::MAX_BODY_SIZE {
+                return Err(ErrorPayloadTooLarge(format!(
+                    "Request bodies larger than {} bytes can't be templated or echoed.",
+                    template::MAX_BODY_SIZE
+                )));
+            }
+
+            body.extend_from_slice(&chunk);
+
+            Ok(body)
+        })
+}
+
+/// Find the first rule matching a request, along with its index.
+fn match_rule<'a>(
+    req: &HttpRequest,
+    query: &[(String, String)],
+    rules: &'a [Rule],
+) -> Option<(usize, &'a Rule)> {
+    rules
+        .iter()
+        .enumerate()
+        .find(|(_, rule)| rule.matches(req.method().as_str(), req.path(), req.headers(), query))
 }
 
 /// Handler for returning usage information at runtime.
-pub fn help(data: Data<CliArgs>) -> HttpResponse {
-    if data.disable_help {
+pub fn help(config: Data<SharedConfig>) -> HttpResponse {
+    if config.current().disable_help {
         // return a 403 if help is disabled
         HttpResponse::build(StatusCode::from_u16(403).unwrap()).finish()
     } else {
@@ -180,19 +888,217 @@ pub fn help(data: Data<CliArgs>) -> HttpResponse {
     }
 }
 
+/// Handler for drawing sample sleep durations from the resolved distribution without sleeping.
+pub fn sample(
+    req: HttpRequest,
+    config: Data<SharedConfig>,
+    query: Query<SleepQueryParams>,
+    sample: Query<SampleQueryParams>,
+) -> Result<HttpResponse, Error> {
+    let data = config.current();
+    let params = SlumberParams::extract(req.headers(), &query, &data)?;
+
+    let samples = (0..sample.count())
+        .map(|_| params.resolve(&data).duration)
+        .collect();
+
+    let payload = SampleResponse::new(params.kind.value, samples, sample.buckets());
+    let body = params
+        .format
+        .render(&payload)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(HttpResponse::Ok()
+        .content_type(params.format.content_type())
+        .header(VARY, "Accept")
+        .body(body))
+}
+
 /// Extract a duration using a query string value, header value, or the default value in that priority.
-fn extract_duration(headers: &HeaderMap, name: &str, qs: Option<u64>, default: u64) -> Duration {
-    Duration::from_millis(
-        qs.unwrap_or(
+fn extract_duration(
+    headers: &HeaderMap,
+    name: &str,
+    qs: Option<u64>,
+    default: u64,
+) -> Sourced<Duration> {
+    if let Some(millis) = qs {
+        return Sourced::new(
+            Duration::from_millis(millis),
+            ParamSource::Query,
+            millis.to_string(),
+        );
+    }
+
+    headers
+        .get(name)
+        .map(|h| h.to_str())
+        .and_then(|r| r.ok())
+        .and_then(|s| s.parse::<u64>().ok().map(|millis| (s, millis)))
+        .map(|(s, millis)| Sourced::new(Duration::from_millis(millis), ParamSource::Header, s))
+        .unwrap_or_else(|| {
+            Sourced::new(
+                Duration::from_millis(default),
+                ParamSource::Cli,
+                default.to_string(),
+            )
+        })
+}
+
+/// Extract a boolean flag from the query string or a header in that priority, defaulting to false.
+fn extract_flag(headers: &HeaderMap, name: &str, qs: Option<&str>) -> bool {
+    qs.and_then(parse_flag)
+        .or_else(|| {
             headers
                 .get(name)
                 .map(|h| h.to_str())
                 .and_then(|r| r.ok())
-                .map(|s| s.parse::<u64>())
-                .and_then(|r| r.ok())
-                .unwrap_or(default),
+                .and_then(parse_flag)
+        })
+        .unwrap_or(false)
+}
+
+/// Parse a boolean flag value, accepting the usual spellings of true and false.
+fn parse_flag(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "1" | "true" | "yes" | "on" => Some(true),
+        "0" | "false" | "no" | "off" => Some(false),
+        _ => None,
+    }
+}
+
+/// Extract the request id from the configured header or the incoming trace context in that priority, generating a
+/// new one if neither is present.
+fn extract_request_id(
+    headers: &HeaderMap,
+    trace: Option<&TraceContext>,
+    config: &CliArgs,
+) -> String {
+    headers
+        .get(config.request_id_header.as_str())
+        .and_then(|h| h.to_str().ok())
+        .map(str::trim)
+        .filter(|id| !id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN)
+        .map(str::to_string)
+        .or_else(|| trace.map(|trace| trace.trace_id.clone()))
+        .unwrap_or_else(|| Uuid::new_v4().to_string())
+}
+
+/// Extract the response format from the query string or the `Accept` header in that priority.
+fn extract_format(headers: &HeaderMap, query: &SleepQueryParams) -> Result<ResponseFormat, Error> {
+    if let Some(name) = &query.format {
+        return ResponseFormat::from_name(name).ok_or_else(|| {
+            let response = HttpResponse::BadRequest().json(ErrorBody {
+                error: format!(
+                    "Unknown format '{}', expected json, pretty, yaml, text, msgpack, or cbor.",
+                    name
+                ),
+            });
+
+            InternalError::from_response("unknown format", response).into()
+        });
+    }
+
+    Ok(headers
+        .get(ACCEPT)
+        .and_then(|h| h.to_str().ok())
+        .and_then(ResponseFormat::from_accept)
+        .unwrap_or_default())
+}
+
+/// Extract the name of the selected profile from the query string or the headers in that priority.
+fn extract_profile(headers: &HeaderMap, query: &SleepQueryParams) -> Option<Sourced<String>> {
+    if let Some(name) = &query.profile {
+        return Some(Sourced::new(
+            name.clone(),
+            ParamSource::Query,
+            name.as_str(),
+        ));
+    }
+
+    headers
+        .get(PROFILE_HEADER)
+        .map(|h| h.to_str())
+        .and_then(|r| r.ok())
+        .map(|s| s.trim())
+        .filter(|s| !s.is_empty())
+        .map(|s| Sourced::new(s.to_string(), ParamSource::Header, s))
+}
+
+/// Extract the gate to wait at from the query string or the headers, along with its timeout, which is bounded by the
+/// maximum sleep time.
+fn extract_gate(
+    headers: &HeaderMap,
+    query: &SleepQueryParams,
+    config: &CliArgs,
+) -> Option<GateParams> {
+    let name = query.gate.clone().or_else(|| {
+        headers
+            .get(GATE_HEADER)
+            .map(|h| h.to_str())
+            .and_then(|r| r.ok())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    })?;
+
+    let timeout = extract_duration(
+        headers,
+        GATE_TIMEOUT_MS_HEADER,
+        query.gate_timeout,
+        config.max_sleep_ms,
+    );
+
+    Some(GateParams {
+        name,
+        timeout: timeout.value.min(config.max_sleep()),
+    })
+}
+
+/// Extract the URL to call back from the query string or the headers, rejecting URLs whose host isn't allowed, along
+/// with the delay before calling back, which is bounded by the maximum sleep time.
+fn extract_callback(
+    headers: &HeaderMap,
+    query: &SleepQueryParams,
+    config: &CliArgs,
+) -> Result<Option<Callback>, Error> {
+    let url = query.callback.clone().or_else(|| {
+        headers
+            .get(CALLBACK_HEADER)
+            .map(|h| h.to_str())
+            .and_then(|r| r.ok())
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(str::to_string)
+    });
+
+    let url = match url {
+        Some(url) => url,
+        None => return Ok(None),
+    };
+
+    if !callback::allowed(&url, &config.callback_allow) {
+        let response = HttpResponse::Forbidden().json(ErrorBody {
+            error: format!(
+                "Callbacks to '{}' aren't allowed, only http URLs to a host passed to --callback-allow are.",
+                url
+            ),
+        });
+
+        return Err(InternalError::from_response("callback not allowed", response).into());
+    }
+
+    let delay = extract_duration(headers, CALLBACK_DELAY_MS_HEADER, query.callback_delay, 0);
+
+    Ok(Some(Callback {
+        url,
+        delay: delay.value.min(config.max_sleep()),
+        background: extract_flag(
+            headers,
+            CALLBACK_ASYNC_HEADER,
+            query.callback_async.as_deref(),
         ),
-    )
+        retries: config.callback_retries,
+    }))
 }
 
 /// Extract the sleep kind from the query string, the headers, or the configuration default in that priority.
@@ -200,25 +1106,27 @@ fn extract_sleep_kind(
     headers: &HeaderMap,
     query: &SleepQueryParams,
     config: &CliArgs,
-) -> SlumberKind {
+) -> Sourced<SlumberKind> {
+    if let Some(kind) = query.kind {
+        return Sourced::new(kind, ParamSource::Query, kind.as_str());
+    }
+
     let default = if config.random {
         SlumberKind::Random
     } else {
         SlumberKind::Fixed
     };
 
-    query.kind.unwrap_or(
-        headers
-            .get(SLEEP_KIND_HEADER)
-            .map(|h| h.to_str())
-            .and_then(|r| r.ok())
-            .map(|s| match s {
-                "fixed" => SlumberKind::Fixed,
-                "random" => SlumberKind::Random,
-                _ => default,
-            })
-            .unwrap_or(default),
-    )
+    headers
+        .get(SLEEP_KIND_HEADER)
+        .map(|h| h.to_str())
+        .and_then(|r| r.ok())
+        .and_then(|s| match s {
+            "fixed" => Some(Sourced::new(SlumberKind::Fixed, ParamSource::Header, s)),
+            "random" => Some(Sourced::new(SlumberKind::Random, ParamSource::Header, s)),
+            _ => None,
+        })
+        .unwrap_or_else(|| Sourced::new(default, ParamSource::Cli, default.as_str()))
 }
 
 /// Extract the minimum sleep time, respecting defined bounds.
@@ -226,7 +1134,7 @@ fn extract_sleep_min_time(
     headers: &HeaderMap,
     query: &SleepQueryParams,
     config: &CliArgs,
-) -> Duration {
+) -> Sourced<Duration> {
     extract_duration(
         headers,
         MINIMUM_SLEEP_TIME_MS_HEADER,
@@ -240,7 +1148,7 @@ fn extract_sleep_max_time(
     headers: &HeaderMap,
     query: &SleepQueryParams,
     config: &CliArgs,
-) -> Duration {
+) -> Sourced<Duration> {
     extract_duration(
         headers,
         MAXIMUM_SLEEP_TIME_MS_HEADER,
@@ -250,7 +1158,11 @@ fn extract_sleep_max_time(
 }
 
 /// Extract the requested sleep duration, respecting defined bounds.
-fn extract_sleep_time(headers: &HeaderMap, query: &SleepQueryParams, config: &CliArgs) -> Duration {
+fn extract_sleep_time(
+    headers: &HeaderMap,
+    query: &SleepQueryParams,
+    config: &CliArgs,
+) -> Sourced<Duration> {
     extract_duration(
         headers,
         SLEEP_TIME_MS_HEADER,
@@ -262,102 +1174,443 @@ fn extract_sleep_time(headers: &HeaderMap, query: &SleepQueryParams, config: &Cl
 pub mod path {
     use super::*;
 
+    /// Parse the query string of a route whose parameters are in its path, ignoring it if it's malformed.
+    ///
+    /// These routes didn't originally read the query string at all, so a malformed one mustn't start failing them.
+    fn lenient_query(req: &HttpRequest) -> SleepQueryParams {
+        Query::<SleepQueryParams>::from_query(req.query_string())
+            .map(Query::into_inner)
+            .unwrap_or_default()
+    }
+
     /// Sleep for a specific, path-specified amount of milliseconds.
     ///
     /// The maximum value will be gated to respect the CLI-specified maximum delay value to prevent DoS-like attacks.
-    pub fn specific(data: Data<CliArgs>, millis: Path<u64>) -> SlumberFuture {
-        slumber(SlumberConfig::fixed(&Duration::from_millis(*millis), &data))
+    pub fn specific(
+        req: HttpRequest,
+        config: Data<SharedConfig>,
+        scale: Data<TimeScale>,
+        payload: Payload,
+        millis: Path<u64>,
+    ) -> SlumberFuture {
+        let data = config.current();
+        let query = lenient_query(&req);
+        let mut params = match SlumberParams::extract(req.headers(), &query, &data) {
+            Ok(params) => params,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        params.kind = Sourced::new(SlumberKind::Fixed, ParamSource::Path, "fixed");
+        params.time = Sourced::new(
+            Duration::from_millis(*millis),
+            ParamSource::Path,
+            millis.to_string(),
+        );
+
+        serve(&req, payload, params, data, scale.get(), "/sleep/{millis}")
     }
 
     /// Sleep for a random amount of milliseconds within the CLI-specified minimum and maximum ranges.
     pub fn random(
         req: HttpRequest,
-        data: Data<CliArgs>,
+        config: Data<SharedConfig>,
+        scale: Data<TimeScale>,
         query: Query<SleepQueryParams>,
+        payload: Payload,
     ) -> SlumberFuture {
-        let (req_min, req_max) = (
-            extract_sleep_min_time(req.headers(), &query, &data),
-            extract_sleep_max_time(req.headers(), &query, &data),
-        );
+        let data = config.current();
+        let mut params = match SlumberParams::extract(req.headers(), &query, &data) {
+            Ok(params) => params,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        params.kind = Sourced::new(SlumberKind::Random, ParamSource::Path, "random");
 
-        slumber(SlumberConfig::random(&req_min, &req_max, &data))
+        serve(&req, payload, params, data, scale.get(), "/random")
     }
 
     /// Sleep for a random amount of milliseconds within the specified range.
     ///
     /// The maximum sleep time will be gated to the CLI-specified maximum delay value to prevent DoS-like attacks.
-    pub fn random_range(data: Data<CliArgs>, range: Path<(u64, u64)>) -> SlumberFuture {
-        slumber(SlumberConfig::random(
-            &Duration::from_millis(range.0),
-            &Duration::from_millis(range.1),
-            &data,
-        ))
+    pub fn random_range(
+        req: HttpRequest,
+        config: Data<SharedConfig>,
+        scale: Data<TimeScale>,
+        payload: Payload,
+        range: Path<(u64, u64)>,
+    ) -> SlumberFuture {
+        let data = config.current();
+        let query = lenient_query(&req);
+        let mut params = match SlumberParams::extract(req.headers(), &query, &data) {
+            Ok(params) => params,
+            Err(e) => return Box::new(future::err(e)),
+        };
+
+        params.kind = Sourced::new(SlumberKind::Random, ParamSource::Path, "random");
+        params.min = Sourced::new(
+            Duration::from_millis(range.0),
+            ParamSource::Path,
+            range.0.to_string(),
+        );
+        params.max = Sourced::new(
+            Duration::from_millis(range.1),
+            ParamSource::Path,
+            range.1.to_string(),
+        );
+
+        serve(
+            &req,
+            payload,
+            params,
+            data,
+            scale.get(),
+            "/random/{min}/{max}",
+        )
+    }
+
+    /// Sleep using the parameters of a path-specified profile.
+    pub fn profile(
+        req: HttpRequest,
+        config: Data<SharedConfig>,
+        scale: Data<TimeScale>,
+        payload: Payload,
+        name: Path<String>,
+    ) -> SlumberFuture {
+        let data = config.current();
+        let query = lenient_query(&req);
+        let profile = Sourced::new(name.clone(), ParamSource::Path, name.as_str());
+
+        match SlumberParams::extract_with_profile(req.headers(), &query, &data, Some(profile)) {
+            Ok(params) => serve(&req, payload, params, data, scale.get(), "/profile/{name}"),
+            Err(e) => Box::new(future::err(e)),
+        }
     }
 }
 
 /// Serve a sleepy request.
-fn slumber(config: SlumberConfig) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
-    log::debug!(
-        "{{request_id = {}, kind = {:?}}} Sleeping for {:?}.",
-        config.id,
-        config.kind,
-        config.duration,
-    );
+fn slumber(mut config: SlumberConfig) -> Box<dyn Future<Item = HttpResponse, Error = Error>> {
+    let span = config.span.clone();
+    let _entered = span.enter();
+
+    // a background callback is accepted straight away, leaving the request to sleep and call back on its own
+    if let Some(callback) = config
+        .callback
+        .as_mut()
+        .filter(|callback| callback.background)
+    {
+        callback.background = false;
+
+        let mut response = HttpResponse::Accepted();
+
+        response.header(REQUEST_ID_HEADER, config.id.as_str());
+
+        if !config.id_header.eq_ignore_ascii_case(REQUEST_ID_HEADER) {
+            response.header(config.id_header.as_str(), config.id.as_str());
+        }
+
+        let response = response.json(AcceptedBody {
+            request_id: config.id.clone(),
+            callback: callback.url.clone(),
+        });
+
+        tracing::debug!(callback = callback.url.as_str(), "Callback accepted.");
+        actix_rt::spawn(slumber(config).map(|_| ()).map_err(|_| ()));
+
+        return Box::new(future::ok(response));
+    }
+
+    if config.dry_run {
+        tracing::debug!(scaled = ?config.scaled, "Dry run, skipping sleep.");
+
+        return Box::new(future::result(respond(config)));
+    }
+
+    tracing::debug!(scaled = ?config.scaled, "Sleep started.");
+
+    // the guard is dropped along with the future if the client goes away
+    let sleeping = config.metrics.as_ref().map(|metrics| metrics.sleeping());
+    let started = Instant::now();
+
+    // as is the sleep, which lists the request as in flight until it wakes
+    let sleep = match (&config.inflight, &config.received) {
+        (Some(inflight), Some(received)) => inflight.sleep(
+            InFlightRequest {
+                request_id: config.id.clone(),
+                timestamp: received.time,
+                method: received.method.clone(),
+                path: received.path.clone(),
+                route: config.route.to_string(),
+                kind: config.kind,
+                time_millis: 0,
+                elapsed_millis: 0,
+                remaining_millis: 0,
+            },
+            config.scaled,
+        ),
+        _ => Sleep::new(config.scaled),
+    };
+
+    // a gated request wakes when its gate opens, unless it's timed out, released, or aborted first
+    let wake: Box<dyn Future<Item = Wake, Error = ()>> = match (&config.gates, &config.gate) {
+        (Some(gates), Some(gate)) => Box::new(
+            sleep
+                .select(gates.wait(gate).map(|()| Wake::Opened))
+                .map(|(wake, _)| wake)
+                .map_err(|_| ()),
+        ),
+        _ => Box::new(sleep),
+    };
+
+    Box::new(wake.then(move |wake| {
+        drop(sleeping);
 
-    Box::new(
-        future::empty::<(), ()>()
-            .timeout(config.duration.clone())
-            .then(move |_r| {
-                log::debug!(
-                    "{{request_id = {}, kind = {:?}}} Sending response.",
-                    config.id,
-                    config.kind,
+        let mut config = config;
+        let actual = started.elapsed();
+
+        match wake {
+            Ok(Wake::Released) => config
+                .span
+                .in_scope(|| tracing::debug!(actual = ?actual, "Sleep released early.")),
+            Ok(Wake::Aborted(fault)) => {
+                config.span.in_scope(
+                    || tracing::debug!(actual = ?actual, status = fault.status, "Sleep aborted."),
                 );
+                config.fault = Some(fault);
+            }
+            Ok(Wake::Opened) => {
+                config
+                    .span
+                    .in_scope(|| tracing::debug!(actual = ?actual, "Gate opened."));
+                config.gate_opened = true;
+            }
+            _ => config
+                .span
+                .in_scope(|| tracing::debug!(actual = ?actual, "Sleep finished.")),
+        }
 
-                // generate json response
-                let payload = match config.kind {
-                    SlumberKind::Fixed => {
-                        SlumberResponse::builder(&config.id, config.kind, &config.duration).build()
-                    }
-                    SlumberKind::Random => {
-                        SlumberResponse::builder(&config.id, config.kind, &config.duration)
-                            .min(&config.min)
-                            .max(&config.max)
-                            .build()
-                    }
-                };
+        if let Some(metrics) = &config.metrics {
+            metrics.slept(config.kind.as_str(), &actual);
+        }
 
-                let mut response = HttpResponse::Ok();
+        config.actual = actual;
 
-                response
-                    .content_type("application/json")
-                    .header(REQUEST_ID_HEADER, config.id.to_string())
-                    .header(SLEEP_TIME_HEADER, payload.duration.duration_pretty.as_str())
-                    .header(
-                        SLEEP_TIME_MS_HEADER,
-                        format!("{}", payload.duration.duration_millis),
-                    );
+        respond(config)
+    }))
+}
+
+/// Build the response for a slumber which has finished sleeping.
+fn respond(config: SlumberConfig) -> Result<HttpResponse, Error> {
+    let span = config.span.clone();
+    let _entered = span.enter();
+
+    // generate json response
+    let mut builder = SlumberResponse::builder(&config.id, config.kind, &config.duration);
+
+    if let SlumberKind::Random = config.kind {
+        builder = builder.min(&config.min).max(&config.max);
+    }
+
+    if (config.scale - 1.0).abs() > f64::EPSILON {
+        builder = builder.scaled(config.scale, &config.scaled);
+    }
+
+    if let Some(profile) = &config.profile {
+        builder = builder.profile(profile);
+    }
+
+    if let Some(rule) = &config.rule {
+        builder = builder.rule(&rule.name);
+    }
+
+    if let Some(gate) = &config.gate {
+        builder = builder.gate(gate, config.gate_opened);
+    }
+
+    if config.dry_run {
+        builder = builder.dry_run();
+    }
+
+    if config.fault.is_some() {
+        builder = builder.fault();
+    }
+
+    if let Some(explain) = config.explain {
+        builder = builder.explain(explain);
+    }
+
+    if let Some(echo) = config.echo {
+        builder = builder.echo(echo);
+    }
+
+    let payload = builder.build();
+
+    // a fault takes priority over the status configured by a rule
+    let rule = &config.rule;
+    let status = config
+        .fault
+        .as_ref()
+        .map(|fault| fault.status)
+        .or_else(|| rule.as_ref().and_then(|outcome| outcome.rule.status))
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::OK);
+
+    span.record("status", status.as_u16());
+    tracing::debug!("Sending response.");
+
+    if let Some(callback) = config.callback.clone() {
+        actix_rt::spawn(callback.send(&payload, status.as_u16(), span.clone()));
+    }
+
+    if let Some(metrics) = &config.metrics {
+        // a gated request's duration is its gate timeout, which wasn't requested as a sleep
+        let requested = match config.gate {
+            Some(_) => None,
+            None => Some(&config.duration),
+        };
+
+        metrics.request(
+            config.kind.as_str(),
+            config.route,
+            status.as_u16(),
+            requested,
+        );
+
+        if config.fault.is_some() {
+            metrics.fault(config.profile.as_deref().unwrap_or(""), status.as_u16());
+        }
+    }
+
+    if let (Some(history), Some(received)) = (&config.history, &config.received) {
+        history.record(RequestRecord::new(
+            received,
+            config.route,
+            &payload,
+            &config.actual,
+            status.as_u16(),
+        ));
+    }
+
+    let mut response = HttpResponse::build(status);
+
+    response
+        .header(VARY, "Accept")
+        .header(REQUEST_ID_HEADER, config.id.as_str())
+        .header(SLEEP_TIME_HEADER, payload.duration.duration_pretty.as_str())
+        .header(
+            SLEEP_TIME_MS_HEADER,
+            format!("{}", payload.duration.duration_millis),
+        );
+
+    match &payload.duration.kind {
+        SlumberKind::Random => {
+            response.header(SLEEP_KIND_HEADER, "random");
+            response.header(MINIMUM_SLEEP_TIME_HEADER, format!("{:?}", config.min));
+            response.header(
+                MINIMUM_SLEEP_TIME_MS_HEADER,
+                format!("{}", config.min.as_millis()),
+            );
+            response.header(MAXIMUM_SLEEP_TIME_HEADER, format!("{:?}", config.max));
+            response.header(
+                MAXIMUM_SLEEP_TIME_MS_HEADER,
+                format!("{}", config.max.as_millis()),
+            );
+        }
+        SlumberKind::Fixed => {
+            response.header(SLEEP_KIND_HEADER, "fixed");
+        }
+    };
+
+    if let Some(scale) = payload.duration.scale {
+        response.header(TIME_SCALE_HEADER, format!("{}", scale));
+        response.header(SCALED_SLEEP_TIME_HEADER, format!("{:?}", config.scaled));
+        response.header(
+            SCALED_SLEEP_TIME_MS_HEADER,
+            format!("{}", config.scaled.as_millis()),
+        );
+    }
+
+    // also return the id in the header it was read from, if that's been changed
+    if !config.id_header.eq_ignore_ascii_case(REQUEST_ID_HEADER) {
+        response.header(config.id_header.as_str(), config.id.as_str());
+    }
+
+    if let Some(trace) = &config.trace {
+        response.header(TRACEPARENT_HEADER, trace.to_string());
+
+        if let Some(state) = &config.trace_state {
+            response.header(TRACESTATE_HEADER, state.as_str());
+        }
+    }
+
+    if let Some(profile) = &config.profile {
+        response.header(PROFILE_HEADER, profile.as_str());
+    }
+
+    if let Some(gate) = &config.gate {
+        response.header(GATE_HEADER, gate.as_str());
+        response.header(GATE_OPENED_HEADER, config.gate_opened.to_string());
+    }
 
-                match &payload.duration.kind {
-                    SlumberKind::Random => {
-                        response.header(SLEEP_KIND_HEADER, "random");
-                        response.header(MINIMUM_SLEEP_TIME_HEADER, format!("{:?}", config.min));
-                        response.header(
-                            MINIMUM_SLEEP_TIME_MS_HEADER,
-                            format!("{}", config.min.as_millis()),
-                        );
-                        response.header(MAXIMUM_SLEEP_TIME_HEADER, format!("{:?}", config.max));
-                        response.header(
-                            MAXIMUM_SLEEP_TIME_MS_HEADER,
-                            format!("{}", config.max.as_millis()),
-                        );
-                    }
-                    SlumberKind::Fixed => {
-                        response.header(SLEEP_KIND_HEADER, "fixed");
-                    }
+    if let Some(outcome) = &config.rule {
+        response.header(RULE_HEADER, outcome.name.as_str());
+
+        for (name, value) in &outcome.rule.response_headers {
+            response.header(name.as_str(), value.as_str());
+        }
+    }
+
+    if config.dry_run {
+        response.header(DRY_RUN_HEADER, "true");
+    }
+
+    if let Some(fault) = &config.fault {
+        response.header(FAULT_HEADER, "true");
+
+        // a custom fault body replaces the json response entirely
+        if let Some(body) = &fault.body {
+            return Ok(response.content_type("text/plain").body(body.clone()));
+        }
+    } else if let Some(outcome) = &config.rule {
+        // as does a fixture or body configured by a rule, leaving the slumber metadata in the headers only
+        let content_type = outcome.rule.content_type();
+
+        let body = match (&outcome.request, &outcome.rule.template_name) {
+            (Some(request), Some(name)) => {
+                let data = TemplateData {
+                    response: &payload,
+                    request,
                 };
 
-                Ok(response.body(serde_json::to_string_pretty(&payload)?))
-            }),
-    )
+                let rendered = template::render(name, &data).map_err(|e| {
+                    tracing::error!(
+                        rule = outcome.name.as_str(),
+                        error = e.as_str(),
+                        "Unable to render template."
+                    );
+
+                    ErrorInternalServerError(e)
+                })?;
+
+                Some(rendered.into_bytes())
+            }
+            _ => match (&outcome.rule.fixture_contents, &outcome.rule.body) {
+                (Some(contents), _) => Some(contents.to_vec()),
+                (None, Some(body)) => Some(body.clone().into_bytes()),
+                (None, None) => None,
+            },
+        };
+
+        if let Some(body) = body {
+            return Ok(response.content_type(content_type).body(body));
+        }
+    }
+
+    let body = config
+        .format
+        .render(&payload)
+        .map_err(ErrorInternalServerError)?;
+
+    Ok(response
+        .content_type(config.format.content_type())
+        .body(body))
 }
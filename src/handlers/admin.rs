@@ -0,0 +1,643 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::dev::Payload;
+use actix_web::error::InternalError;
+use actix_web::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use actix_web::http::HeaderMap;
+use actix_web::web::{self, Data, Json, Path, ServiceConfig};
+use actix_web::{Error, FromRequest, HttpRequest, HttpResponse};
+
+use crate::config::{
+    CliArgs, ConfigFile, ConfigSource, FaultSettings, Profile, SharedConfig, TimeScale,
+};
+use crate::logging::LogLevel;
+use crate::reload::Reloader;
+
+use super::gate::{GateState, Gates};
+use super::inflight::{InFlightRequest, InFlightRequests};
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+/// Register the admin routes, either on the main listener or on a separate admin listener.
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.service(
+        web::scope("/_admin")
+            .service(
+                web::resource("/settings")
+                    .route(web::get().to(settings))
+                    .route(web::put().to(update_settings))
+                    .route(web::patch().to(update_settings)),
+            )
+            .service(web::resource("/profiles").route(web::get().to(profiles)))
+            .service(
+                web::resource("/profiles/{name}")
+                    .route(web::get().to(profile))
+                    .route(web::put().to(set_profile))
+                    .route(web::delete().to(delete_profile)),
+            )
+            .service(
+                web::resource("/time-scale")
+                    .route(web::get().to(time_scale))
+                    .route(web::put().to(set_time_scale)),
+            )
+            .service(
+                web::resource("/log-level")
+                    .route(web::get().to(log_level))
+                    .route(web::put().to(set_log_level)),
+            )
+            .service(web::resource("/reload").route(web::post().to(reload))),
+    )
+    .route("/_config", web::get().to(config))
+    .route("/_config/", web::get().to(config))
+    .route("/_inflight", web::get().to(inflight))
+    .route("/_inflight/", web::get().to(inflight))
+    .route("/_inflight/{id}/release", web::post().to(release))
+    .route("/_inflight/{id}/extend", web::post().to(extend))
+    .route("/_inflight/{id}/abort", web::post().to(abort))
+    .route("/_gates", web::get().to(gates))
+    .route("/_gates/", web::get().to(gates))
+    .service(
+        web::resource("/_gates/{name}")
+            .route(web::get().to(gate))
+            .route(web::put().to(set_gate))
+            .route(web::delete().to(delete_gate)),
+    )
+    .route("/_gates/{name}/open", web::post().to(open_gate))
+    .route("/_gates/{name}/close", web::post().to(close_gate));
+}
+
+/// An extractor which rejects requests that don't carry the configured admin token.
+///
+/// If no admin token is configured, every request is refused, unless unauthenticated access was explicitly allowed.
+pub struct AdminAuth;
+
+impl FromRequest for AdminAuth {
+    type Config = ();
+    type Error = Error;
+    type Future = Result<Self, Error>;
+
+    fn from_request(req: &HttpRequest, _: &mut Payload) -> Self::Future {
+        let (token, insecure) = match req.get_app_data::<SharedConfig>() {
+            Some(config) => {
+                let current = config.current();
+
+                (current.admin_token.clone(), current.admin_insecure)
+            }
+            None => (None, false),
+        };
+
+        if token.is_none() && !insecure {
+            let response = HttpResponse::Forbidden().json(ErrorBody {
+                error: "The admin API is disabled, as no admin token is configured. Set --admin-token, or pass \
+                        --admin-insecure to allow unauthenticated access."
+                    .to_string(),
+            });
+
+            Err(InternalError::from_response("forbidden", response).into())
+        } else if authorized(req.headers(), token.as_deref()) {
+            Ok(AdminAuth)
+        } else {
+            let response = HttpResponse::Unauthorized()
+                .header(WWW_AUTHENTICATE, "Bearer")
+                .json(ErrorBody {
+                    error: "A valid admin token is required.".to_string(),
+                });
+
+            Err(InternalError::from_response("unauthorized", response).into())
+        }
+    }
+}
+
+/// Check the bearer token in the request headers against the configured admin token.
+fn authorized(headers: &HeaderMap, token: Option<&str>) -> bool {
+    let token = match token {
+        Some(token) => token,
+        None => return true,
+    };
+
+    headers
+        .get(AUTHORIZATION)
+        .and_then(|h| h.to_str().ok())
+        .and_then(|h| h.strip_prefix("Bearer "))
+        .map(|provided| constant_time_eq(provided.trim().as_bytes(), token.as_bytes()))
+        .unwrap_or(false)
+}
+
+/// Compare two byte strings without short-circuiting on the first difference.
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |acc, (x, y)| acc | (x ^ y)) == 0
+}
+
+/// The body used to report and update the time-dilation factor.
+#[derive(Deserialize, Serialize)]
+pub struct TimeScaleBody {
+    pub time_scale: f64,
+}
+
+/// The body used to report and update the log level, as comma-separated filter directives.
+#[derive(Deserialize, Serialize)]
+pub struct LogLevelBody {
+    pub log_level: String,
+}
+
+/// The body returned when listing or changing in-flight requests.
+#[derive(Serialize)]
+pub struct InFlightBody {
+    pub requests: Vec<InFlightRequest>,
+}
+
+/// The body used to extend the sleep of in-flight requests.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ExtendBody {
+    pub millis: u64,
+}
+
+/// The body used to abort in-flight requests with a fault.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AbortBody {
+    #[serde(default = "AbortBody::default_status")]
+    pub status: u16,
+    pub body: Option<String>,
+}
+
+impl AbortBody {
+    fn default_status() -> u16 {
+        503
+    }
+}
+
+/// The body used to change the barrier of a gate.
+#[derive(Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GateBody {
+    pub barrier: Option<usize>,
+}
+
+/// The body returned when opening a gate.
+#[derive(Serialize)]
+pub struct OpenedBody {
+    #[serde(flatten)]
+    pub gate: GateState,
+    /// The number of requests which were waiting at the gate.
+    pub released: usize,
+}
+
+/// The body returned when a request is rejected.
+#[derive(Serialize)]
+pub struct ErrorBody {
+    pub error: String,
+}
+
+/// The body used to report and update the settings which can be changed at runtime.
+#[derive(Default, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsBody {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sleep_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_sleep_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_sleep_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub random: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disable_help: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub time_scale: Option<f64>,
+}
+
+impl SettingsBody {
+    fn new(config: &CliArgs, scale: &TimeScale) -> Self {
+        Self {
+            sleep_ms: Some(config.sleep_ms),
+            min_sleep_ms: Some(config.min_sleep_ms),
+            max_sleep_ms: Some(config.max_sleep_ms),
+            random: Some(config.random),
+            disable_help: Some(config.disable_help),
+            time_scale: Some(scale.get()),
+        }
+    }
+
+    /// Apply the settings which are present to a configuration, validating the settings which changed.
+    ///
+    /// Settings which aren't present aren't validated, as they may have been normalized at startup instead.
+    fn apply(&self, config: &mut CliArgs) -> Result<(), String> {
+        macro_rules! overlay {
+            ($($field:ident),*) => {$(
+                if let Some(value) = self.$field {
+                    config.$field = value;
+                    config.sources.insert(stringify!($field), ConfigSource::Admin);
+                }
+            )*};
+        }
+
+        overlay!(
+            sleep_ms,
+            min_sleep_ms,
+            max_sleep_ms,
+            random,
+            disable_help,
+            time_scale
+        );
+
+        if self.sleep_ms.is_some() || self.min_sleep_ms.is_some() || self.max_sleep_ms.is_some() {
+            config.validate_sleep()?;
+        }
+
+        if self.time_scale.is_some() {
+            config.validate_time_scale()?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The body returned when dumping the effective configuration.
+#[derive(Serialize)]
+pub struct ConfigBody {
+    pub config: ConfigFile,
+    pub sources: BTreeMap<String, ConfigSource>,
+    pub time_scale: f64,
+    pub log_level: String,
+}
+
+/// Report the current time-dilation factor.
+pub fn time_scale(_auth: AdminAuth, scale: Data<TimeScale>) -> HttpResponse {
+    HttpResponse::Ok().json(TimeScaleBody {
+        time_scale: scale.get(),
+    })
+}
+
+/// Change the time-dilation factor applied to new requests.
+pub fn set_time_scale(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
+    body: Json<TimeScaleBody>,
+) -> HttpResponse {
+    match scale.set(body.time_scale) {
+        Ok(()) => {
+            // keep the configuration in step, so that it's reported in /_config and compared against on reload
+            let _ = config.update(|next| -> Result<(), ()> {
+                next.time_scale = body.time_scale;
+                next.sources.insert("time_scale", ConfigSource::Admin);
+
+                Ok(())
+            });
+
+            tracing::info!("Time scale changed to {}.", body.time_scale);
+
+            HttpResponse::Ok().json(TimeScaleBody {
+                time_scale: scale.get(),
+            })
+        }
+        Err(error) => HttpResponse::BadRequest().json(ErrorBody { error }),
+    }
+}
+
+/// Report the current log level.
+pub fn log_level(_auth: AdminAuth, level: Data<LogLevel>) -> HttpResponse {
+    HttpResponse::Ok().json(LogLevelBody {
+        log_level: level.to_string(),
+    })
+}
+
+/// Change the log level of every log destination.
+pub fn set_log_level(
+    _auth: AdminAuth,
+    level: Data<LogLevel>,
+    body: Json<LogLevelBody>,
+) -> HttpResponse {
+    match level.set(&body.log_level) {
+        Ok(()) => {
+            tracing::info!("Log level changed to {}.", level.get_ref());
+
+            HttpResponse::Ok().json(LogLevelBody {
+                log_level: level.to_string(),
+            })
+        }
+        Err(error) => HttpResponse::BadRequest().json(ErrorBody { error }),
+    }
+}
+
+/// Report the settings which can be changed at runtime.
+pub fn settings(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(SettingsBody::new(&config.current(), &scale))
+}
+
+/// Change settings for new requests, leaving settings which aren't present in the body untouched.
+pub fn update_settings(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
+    body: Json<SettingsBody>,
+) -> HttpResponse {
+    match config.update(|next| body.apply(next)) {
+        Ok(next) => {
+            if body.time_scale.is_some() {
+                let _ = scale.set(next.time_scale());
+            }
+
+            tracing::info!(
+                "Settings changed (min sleep time: {:?}, default sleep time: {:?}, max sleep time: {:?}, random: {}, time scale: {}).",
+                next.min_sleep(),
+                next.sleep(),
+                next.max_sleep(),
+                next.random,
+                scale.get(),
+            );
+
+            HttpResponse::Ok().json(SettingsBody::new(&next, &scale))
+        }
+        Err(error) => HttpResponse::BadRequest().json(ErrorBody { error }),
+    }
+}
+
+/// List the defined latency profiles.
+pub fn profiles(_auth: AdminAuth, config: Data<SharedConfig>) -> HttpResponse {
+    HttpResponse::Ok().json(&config.current().profiles)
+}
+
+/// Report a single latency profile.
+pub fn profile(_auth: AdminAuth, config: Data<SharedConfig>, name: Path<String>) -> HttpResponse {
+    match config.current().profiles.get(name.as_str()) {
+        Some(profile) => HttpResponse::Ok().json(profile),
+        None => HttpResponse::NotFound().json(ErrorBody {
+            error: format!("Unknown profile '{}'.", name),
+        }),
+    }
+}
+
+/// Create or replace a latency profile.
+pub fn set_profile(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    name: Path<String>,
+    body: Json<Profile>,
+) -> HttpResponse {
+    let profile = body.into_inner();
+
+    if let Some(fault) = &profile.fault {
+        if let Err(error) = fault.validate() {
+            return HttpResponse::BadRequest().json(ErrorBody { error });
+        }
+    }
+
+    let _ = config.update(|next| -> Result<(), ()> {
+        next.profiles.insert(name.to_string(), profile.clone());
+        next.sources.insert("profiles", ConfigSource::Admin);
+
+        Ok(())
+    });
+
+    tracing::info!("Profile '{}' changed.", name);
+
+    HttpResponse::Ok().json(profile)
+}
+
+/// Delete a latency profile, unless a rule still refers to it.
+pub fn delete_profile(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    name: Path<String>,
+) -> HttpResponse {
+    let result = config.update(|next| {
+        if next.profiles.remove(name.as_str()).is_none() {
+            return Err(HttpResponse::NotFound().json(ErrorBody {
+                error: format!("Unknown profile '{}'.", name),
+            }));
+        }
+
+        if next
+            .rules
+            .iter()
+            .any(|r| r.profile.as_ref() == Some(&*name))
+        {
+            return Err(HttpResponse::Conflict().json(ErrorBody {
+                error: format!("Profile '{}' is used by a rule.", name),
+            }));
+        }
+
+        next.sources.insert("profiles", ConfigSource::Admin);
+
+        Ok(())
+    });
+
+    match result {
+        Ok(_) => {
+            tracing::info!("Profile '{}' deleted.", name);
+
+            HttpResponse::NoContent().finish()
+        }
+        Err(response) => response,
+    }
+}
+
+/// Dump the effective configuration along with where each option came from.
+pub fn config(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
+    level: Data<LogLevel>,
+) -> HttpResponse {
+    let current = config.current();
+
+    HttpResponse::Ok().json(ConfigBody {
+        config: ConfigFile::from(&*current).redacted(),
+        sources: current
+            .describe()
+            .into_iter()
+            .map(|(name, _, source)| (name, source))
+            .collect(),
+        time_scale: scale.get(),
+        log_level: level.to_string(),
+    })
+}
+
+/// List the requests which are currently sleeping.
+pub fn inflight(_auth: AdminAuth, inflight: Data<InFlightRequests>) -> HttpResponse {
+    HttpResponse::Ok().json(InFlightBody {
+        requests: inflight.list(),
+    })
+}
+
+/// Respond to in-flight requests now, rather than once they finish sleeping.
+pub fn release(
+    _auth: AdminAuth,
+    inflight: Data<InFlightRequests>,
+    id: Path<String>,
+) -> HttpResponse {
+    let requests = inflight.release(&id);
+
+    if !requests.is_empty() {
+        tracing::info!("Released request '{}' early.", id);
+    }
+
+    changed(&id, requests)
+}
+
+/// Extend the sleep of in-flight requests.
+pub fn extend(
+    _auth: AdminAuth,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
+    inflight: Data<InFlightRequests>,
+    id: Path<String>,
+    body: Json<ExtendBody>,
+) -> HttpResponse {
+    let max_sleep = config.current().max_sleep();
+
+    if Duration::from_millis(body.millis) > max_sleep {
+        return HttpResponse::BadRequest().json(ErrorBody {
+            error: format!(
+                "An extension can't be longer than the maximum sleep time ({}ms), got {}ms.",
+                max_sleep.as_millis(),
+                body.millis
+            ),
+        });
+    }
+
+    // requests sleep for at most the maximum sleep time, scaled as their sleeps were
+    let limit = TimeScale::scale(scale.get(), max_sleep);
+    let requests = inflight.extend(&id, Duration::from_millis(body.millis), limit);
+
+    if !requests.is_empty() {
+        tracing::info!(
+            "Extended the sleep of request '{}' by {}ms.",
+            id,
+            body.millis
+        );
+    }
+
+    changed(&id, requests)
+}
+
+/// Respond to in-flight requests now with a fault.
+pub fn abort(
+    _auth: AdminAuth,
+    inflight: Data<InFlightRequests>,
+    id: Path<String>,
+    body: Json<AbortBody>,
+) -> HttpResponse {
+    let body = body.into_inner();
+    let fault = FaultSettings {
+        probability: 1.0,
+        status: body.status,
+        body: body.body,
+    };
+
+    if let Err(error) = fault.validate() {
+        return HttpResponse::BadRequest().json(ErrorBody { error });
+    }
+
+    let requests = inflight.abort(&id, fault);
+
+    if !requests.is_empty() {
+        tracing::info!("Aborted request '{}' with status {}.", id, body.status);
+    }
+
+    changed(&id, requests)
+}
+
+/// Report the in-flight requests which were changed, or that there weren't any.
+fn changed(id: &str, requests: Vec<InFlightRequest>) -> HttpResponse {
+    if requests.is_empty() {
+        HttpResponse::NotFound().json(ErrorBody {
+            error: format!("No request with id '{}' is in flight.", id),
+        })
+    } else {
+        HttpResponse::Ok().json(InFlightBody { requests })
+    }
+}
+
+/// List the gates which are open, have a barrier, or have requests waiting.
+pub fn gates(_auth: AdminAuth, gates: Data<Gates>) -> HttpResponse {
+    HttpResponse::Ok().json(gates.list())
+}
+
+/// Report a single gate.
+pub fn gate(_auth: AdminAuth, gates: Data<Gates>, name: Path<String>) -> HttpResponse {
+    HttpResponse::Ok().json(gates.get(&name))
+}
+
+/// Change the barrier of a gate, releasing the waiting requests if there are already enough of them.
+pub fn set_gate(
+    _auth: AdminAuth,
+    gates: Data<Gates>,
+    name: Path<String>,
+    body: Json<GateBody>,
+) -> HttpResponse {
+    if body.barrier == Some(0) {
+        return HttpResponse::BadRequest().json(ErrorBody {
+            error: "A gate's barrier must be at least 1.".to_string(),
+        });
+    }
+
+    let released = gates.set_barrier(&name, body.barrier);
+
+    tracing::info!(
+        "Barrier of gate '{}' changed to {:?}, releasing {} requests.",
+        name,
+        body.barrier,
+        released
+    );
+
+    HttpResponse::Ok().json(gates.get(&name))
+}
+
+/// Open a gate, releasing the waiting requests and letting later requests through.
+pub fn open_gate(_auth: AdminAuth, gates: Data<Gates>, name: Path<String>) -> HttpResponse {
+    let released = gates.open(&name);
+
+    tracing::info!("Gate '{}' opened, releasing {} requests.", name, released);
+
+    HttpResponse::Ok().json(OpenedBody {
+        gate: gates.get(&name),
+        released,
+    })
+}
+
+/// Close a gate, so that later requests wait at it.
+pub fn close_gate(_auth: AdminAuth, gates: Data<Gates>, name: Path<String>) -> HttpResponse {
+    gates.close(&name);
+
+    tracing::info!("Gate '{}' closed.", name);
+
+    HttpResponse::Ok().json(gates.get(&name))
+}
+
+/// Forget a gate, releasing the waiting requests.
+pub fn delete_gate(_auth: AdminAuth, gates: Data<Gates>, name: Path<String>) -> HttpResponse {
+    match gates.remove(&name) {
+        Some(released) => {
+            tracing::info!("Gate '{}' deleted, releasing {} requests.", name, released);
+
+            HttpResponse::NoContent().finish()
+        }
+        None => HttpResponse::NotFound().json(ErrorBody {
+            error: format!("Unknown gate '{}'.", name),
+        }),
+    }
+}
+
+/// Reload configuration from the command-line, the environment, and the configuration file.
+pub fn reload(_auth: AdminAuth, reloader: Data<Reloader>) -> HttpResponse {
+    tracing::info!("Reloading configuration on admin request.");
+
+    match reloader.reload() {
+        Ok(()) => HttpResponse::NoContent().finish(),
+        Err(e) => HttpResponse::BadRequest().json(ErrorBody {
+            error: e.to_string(),
+        }),
+    }
+}
@@ -0,0 +1,181 @@
+use super::authorized;
+use super::configure;
+use super::constant_time_eq;
+use super::SettingsBody;
+
+use crate::config::{CliArgs, ConfigSource, SharedConfig, TimeScale};
+
+use actix_web::http::header::AUTHORIZATION;
+use actix_web::http::{HeaderMap, HeaderValue};
+use actix_web::test::{self, TestRequest};
+use actix_web::web::Data;
+use actix_web::App;
+
+#[test]
+fn test_authorized() {
+    let mut headers = HeaderMap::new();
+
+    // test that everything is allowed without a token
+    assert!(authorized(&headers, None));
+
+    // test that a token is required when configured
+    assert!(!authorized(&headers, Some("secret")));
+
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer wrong"));
+    assert!(!authorized(&headers, Some("secret")));
+
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic secret"));
+    assert!(!authorized(&headers, Some("secret")));
+
+    headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret"));
+    assert!(authorized(&headers, Some("secret")));
+}
+
+#[test]
+fn test_constant_time_eq() {
+    assert!(constant_time_eq(b"secret", b"secret"));
+    assert!(!constant_time_eq(b"secret", b"secreT"));
+    assert!(!constant_time_eq(b"secret", b"secrets"));
+    assert!(!constant_time_eq(b"", b"secret"));
+}
+
+#[test]
+fn test_update_settings() {
+    let config = SharedConfig::new(CliArgs::default());
+
+    // test a valid update
+    let body = SettingsBody {
+        sleep_ms: Some(100),
+        random: Some(true),
+        ..Default::default()
+    };
+
+    let next = config.update(|next| body.apply(next)).unwrap();
+
+    assert_eq!(100, next.sleep_ms);
+    assert!(next.random);
+    assert_eq!(ConfigSource::Admin, next.source("sleep_ms"));
+    assert_eq!(ConfigSource::Default, next.source("max_sleep_ms"));
+
+    // test that inconsistent bounds are rejected and nothing changes
+    let body = SettingsBody {
+        sleep_ms: Some(500),
+        min_sleep_ms: Some(2000),
+        max_sleep_ms: Some(1000),
+        ..Default::default()
+    };
+
+    assert!(config.update(|next| body.apply(next)).is_err());
+    assert_eq!(100, config.current().sleep_ms);
+    assert_eq!(15, config.current().min_sleep_ms);
+
+    // test that a sleep time outside of the bounds is rejected
+    let body = SettingsBody {
+        sleep_ms: Some(50_000),
+        ..Default::default()
+    };
+
+    assert!(config.update(|next| body.apply(next)).is_err());
+    assert_eq!(100, config.current().sleep_ms);
+}
+
+#[test]
+fn test_admin_auth() {
+    fn status(args: CliArgs, token: Option<&str>) -> u16 {
+        let mut app = test::init_service(
+            App::new()
+                .register_data(Data::new(SharedConfig::new(args)))
+                .register_data(Data::new(TimeScale::new(1.0)))
+                .configure(configure),
+        );
+
+        let mut req = TestRequest::with_uri("/_admin/time-scale");
+
+        if let Some(token) = token {
+            req = req.header(AUTHORIZATION, format!("Bearer {}", token));
+        }
+
+        test::call_service(&mut app, req.to_request())
+            .status()
+            .as_u16()
+    }
+
+    // test that the admin API is refused without a token, unless unauthenticated access is allowed
+    assert_eq!(403, status(CliArgs::default(), None));
+
+    let insecure = CliArgs {
+        admin_insecure: true,
+        ..CliArgs::default()
+    };
+
+    assert_eq!(200, status(insecure, None));
+
+    // test that a configured token is required either way
+    let token = CliArgs {
+        admin_token: Some("secret".to_string()),
+        admin_insecure: true,
+        ..CliArgs::default()
+    };
+
+    assert_eq!(401, status(token.clone(), None));
+    assert_eq!(200, status(token, Some("secret")));
+}
+
+#[test]
+fn test_update_settings_time_scale() {
+    // a startup time scale of 0 is normalized to 1.0, leaving the raw value in the configuration
+    let args = CliArgs {
+        admin_insecure: true,
+        time_scale: 0.0,
+        ..CliArgs::default()
+    };
+
+    let config = Data::new(SharedConfig::new(args.clone()));
+    let scale = Data::new(TimeScale::new(args.time_scale()));
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(config.clone())
+            .register_data(scale.clone())
+            .configure(configure),
+    );
+
+    // test that settings can be changed without tripping over the unchanged startup time scale
+    let req = TestRequest::patch()
+        .uri("/_admin/settings")
+        .set_json(&serde_json::json!({ "sleep_ms": 100 }))
+        .to_request();
+
+    assert!(test::call_service(&mut app, req).status().is_success());
+    assert_eq!(100, config.current().sleep_ms);
+
+    // test that an accepted time scale is recorded in the configuration as well as applied
+    let req = TestRequest::patch()
+        .uri("/_admin/settings")
+        .set_json(&serde_json::json!({ "sleep_ms": 100, "time_scale": 0.5 }))
+        .to_request();
+
+    assert!(test::call_service(&mut app, req).status().is_success());
+    assert!((scale.get() - 0.5).abs() < f64::EPSILON);
+    assert!((config.current().time_scale - 0.5).abs() < f64::EPSILON);
+    assert_eq!(ConfigSource::Admin, config.current().source("time_scale"));
+
+    // test that an invalid time scale is rejected and nothing changes
+    let req = TestRequest::patch()
+        .uri("/_admin/settings")
+        .set_json(&serde_json::json!({ "sleep_ms": 200, "time_scale": 0 }))
+        .to_request();
+
+    assert_eq!(400, test::call_service(&mut app, req).status().as_u16());
+    assert_eq!(100, config.current().sleep_ms);
+    assert!((scale.get() - 0.5).abs() < f64::EPSILON);
+
+    // test that the time-scale endpoint keeps the configuration in step too
+    let req = TestRequest::put()
+        .uri("/_admin/time-scale")
+        .set_json(&serde_json::json!({ "time_scale": 2.0 }))
+        .to_request();
+
+    assert!(test::call_service(&mut app, req).status().is_success());
+    assert!((config.current().time_scale - 2.0).abs() < f64::EPSILON);
+}
@@ -0,0 +1,192 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::client::Client;
+use actix_web::http::Uri;
+use actix_web::web::Bytes;
+
+use futures::future::{self, Loop};
+use futures::Future;
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use tokio::timer::Delay;
+
+use tracing::Span;
+
+use super::response::SlumberResponse;
+
+static ATTEMPT_HEADER: &str = "X-Slumber-Callback-Attempt";
+
+static STATUS_HEADER: &str = "X-Slumber-Status";
+
+/// The delay before the first retry of a failed callback, which doubles with each retry after it.
+const RETRY_DELAY: Duration = Duration::from_millis(100);
+
+/// How long each attempt waits for the receiver to respond.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The most callbacks which can be waiting to be sent or retried at once, beyond which new callbacks are dropped.
+const MAX_IN_FLIGHT: usize = 1024;
+
+/// The number of callbacks which are waiting to be sent or retried.
+static IN_FLIGHT: AtomicUsize = AtomicUsize::new(0);
+
+thread_local! {
+    /// The client which every callback on a worker thread is sent with, as clients can't be shared between threads.
+    static CLIENT: Client = Client::default();
+}
+
+/// A URL which is called back with the response of a request once it has slept.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Callback {
+    pub url: String,
+    /// How long to wait after the sleep before calling back.
+    pub delay: Duration,
+    /// Whether to respond with `202 Accepted` straight away, sleeping and calling back in the background.
+    pub background: bool,
+    /// The number of times a failed callback is retried.
+    pub retries: u32,
+}
+
+impl Callback {
+    /// POST the response to the callback URL after the delay, retrying until the receiver responds with a success
+    /// status or the retries run out.
+    pub fn send(
+        self,
+        payload: &SlumberResponse,
+        status: u16,
+        span: Span,
+    ) -> impl Future<Item = (), Error = ()> {
+        let permit = match Permit::acquire() {
+            Some(permit) => permit,
+            None => {
+                span.in_scope(|| {
+                    tracing::warn!(
+                        limit = MAX_IN_FLIGHT,
+                        "Too many callbacks in flight, dropping the callback."
+                    )
+                });
+
+                return future::Either::A(future::ok(()));
+            }
+        };
+
+        // serializing a response can't fail, as it's made of strings and numbers
+        let body = Bytes::from(serde_json::to_vec(payload).unwrap());
+        let request_id = payload.request_id.clone();
+        let client = CLIENT.with(Client::clone);
+
+        let Callback {
+            url,
+            delay,
+            retries,
+            ..
+        } = self;
+
+        let sent = Delay::new(Instant::now() + delay).then(move |_| {
+            future::loop_fn(0, move |attempt| {
+                let span = span.clone();
+
+                client
+                    .post(url.as_str())
+                    .timeout(TIMEOUT)
+                    .content_type("application/json")
+                    .header("X-Request-Id", request_id.as_str())
+                    .header(ATTEMPT_HEADER, (attempt + 1).to_string())
+                    .header(STATUS_HEADER, status.to_string())
+                    .send_body(body.clone())
+                    .then(move |result| {
+                        let error = match result {
+                            Ok(res) if res.status().is_success() => {
+                                span.in_scope(|| {
+                                    tracing::debug!(attempt = attempt + 1, "Callback sent.")
+                                });
+
+                                return future::Either::A(future::ok(Loop::Break(())));
+                            }
+                            Ok(res) => format!("the receiver responded with {}", res.status()),
+                            Err(e) => e.to_string(),
+                        };
+
+                        if attempt >= retries {
+                            span.in_scope(|| {
+                                tracing::warn!(
+                                    attempt = attempt + 1,
+                                    error = error.as_str(),
+                                    "Callback failed, giving up."
+                                )
+                            });
+
+                            return future::Either::A(future::ok(Loop::Break(())));
+                        }
+
+                        let backoff = RETRY_DELAY * 2u32.saturating_pow(attempt);
+
+                        span.in_scope(|| {
+                            tracing::debug!(
+                                attempt = attempt + 1,
+                                error = error.as_str(),
+                                backoff = ?backoff,
+                                "Callback failed, retrying."
+                            )
+                        });
+
+                        future::Either::B(
+                            Delay::new(Instant::now() + backoff)
+                                .then(move |_| Ok(Loop::Continue(attempt + 1))),
+                        )
+                    })
+            })
+        });
+
+        // the callback no longer counts as in flight once it's been sent or given up on
+        future::Either::B(sent.then(move |result| {
+            drop(permit);
+            result
+        }))
+    }
+}
+
+/// A callback's place among those in flight, which is given up when dropped.
+struct Permit;
+
+impl Permit {
+    /// Take a place among the callbacks in flight, unless they're all taken.
+    fn acquire() -> Option<Self> {
+        if IN_FLIGHT.fetch_add(1, Ordering::SeqCst) < MAX_IN_FLIGHT {
+            Some(Permit)
+        } else {
+            IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+            None
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        IN_FLIGHT.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Check whether a callback URL is plain HTTP, and its host is allowed either on any port or on its own port.
+pub fn allowed(url: &str, allow: &[String]) -> bool {
+    let uri = match url.parse::<Uri>() {
+        Ok(uri) => uri,
+        Err(_) => return false,
+    };
+
+    let host = match (uri.scheme_str(), uri.host()) {
+        (Some("http"), Some(host)) => host.to_lowercase(),
+        _ => return false,
+    };
+
+    let authority = format!("{}:{}", host, uri.port_u16().unwrap_or(80));
+
+    allow.iter().any(|allowed| {
+        let allowed = allowed.trim().to_lowercase();
+
+        allowed == host || allowed == authority
+    })
+}
@@ -0,0 +1,263 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use super::allowed;
+
+use crate::config::{CliArgs, SharedConfig, TimeScale};
+use crate::handlers::path;
+
+use actix_web::test::{self, TestRequest};
+use actix_web::web::{self, Data};
+use actix_web::App;
+
+use std::io::{Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc::{self, Receiver};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use tokio::timer::Delay;
+
+/// Receive HTTP requests on a local port, responding to each with the next status, and repeating the last.
+fn receiver(statuses: Vec<u16>) -> (u16, Receiver<String>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let port = listener.local_addr().unwrap().port();
+    let (sender, received) = mpsc::channel();
+
+    thread::spawn(move || {
+        for (index, stream) in listener.incoming().enumerate() {
+            let mut stream = stream.unwrap();
+            let mut request = Vec::new();
+            let mut buffer = [0; 4096];
+
+            // read until the whole body has arrived, as given by its content length
+            loop {
+                let read = stream.read(&mut buffer).unwrap();
+                request.extend_from_slice(&buffer[..read]);
+
+                let text = String::from_utf8_lossy(&request).to_lowercase();
+
+                if let Some(end) = text.find("\r\n\r\n") {
+                    let length = text
+                        .lines()
+                        .find_map(|line| line.strip_prefix("content-length:"))
+                        .map(|length| length.trim().parse::<usize>().unwrap())
+                        .unwrap_or(0);
+
+                    if read == 0 || request.len() >= end + 4 + length {
+                        break;
+                    }
+                }
+            }
+
+            let status = statuses[index.min(statuses.len() - 1)];
+
+            write!(
+                stream,
+                "HTTP/1.1 {} Whatever\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+                status
+            )
+            .unwrap();
+
+            if sender
+                .send(String::from_utf8_lossy(&request).into_owned())
+                .is_err()
+            {
+                break;
+            }
+        }
+    });
+
+    (port, received)
+}
+
+/// Run the spawned callbacks until the receiver has received a request.
+fn wait(received: &Receiver<String>) -> String {
+    let started = Instant::now();
+
+    while started.elapsed() < Duration::from_secs(5) {
+        if let Ok(request) = received.try_recv() {
+            return request;
+        }
+
+        test::block_on(Delay::new(Instant::now() + Duration::from_millis(10))).unwrap();
+    }
+
+    panic!("No callback was received.");
+}
+
+fn header<'a>(request: &'a str, name: &str) -> Option<&'a str> {
+    request.lines().find_map(|line| {
+        let (header, value) = line.split_once(':')?;
+
+        if header.eq_ignore_ascii_case(name) {
+            Some(value.trim())
+        } else {
+            None
+        }
+    })
+}
+
+fn body(request: &str) -> serde_json::Value {
+    serde_json::from_str(&request[request.find("\r\n\r\n").unwrap() + 4..]).unwrap()
+}
+
+fn config(allow: &[&str]) -> Data<SharedConfig> {
+    let mut args = CliArgs::default();
+    args.callback_allow = allow.iter().map(|host| host.to_string()).collect();
+
+    Data::new(SharedConfig::new(args))
+}
+
+#[test]
+fn test_allowed() {
+    let allow = vec!["localhost".to_string(), "127.0.0.1:9000".to_string()];
+
+    assert!(allowed("http://localhost/hook", &allow));
+    assert!(allowed("http://LOCALHOST:1234/hook", &allow));
+    assert!(allowed("http://127.0.0.1:9000/hook?a=b", &allow));
+    assert!(!allowed("http://127.0.0.1/hook", &allow));
+    assert!(!allowed("http://127.0.0.1:9001/hook", &allow));
+    assert!(!allowed("http://localhost.example.com/hook", &allow));
+
+    // test that only absolute, plain http URLs are allowed
+    assert!(!allowed("https://localhost/hook", &allow));
+    assert!(!allowed("/hook", &allow));
+    assert!(!allowed("not a url", &allow));
+    assert!(!allowed("http://localhost/hook", &[]));
+
+    // test that the default port is implied
+    assert!(allowed(
+        "http://example.com/",
+        &["example.com:80".to_string()]
+    ));
+}
+
+#[test]
+fn test_callback() {
+    let (port, received) = receiver(vec![500, 200]);
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(config(&["127.0.0.1"]))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    let req = TestRequest::with_header("X-Request-Id", "paid")
+        .uri(&format!(
+            "/sleep/20?callback=http://127.0.0.1:{}/hook",
+            port
+        ))
+        .to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(200, res.status().as_u16());
+
+    // test that the response is posted to the callback URL once the request has slept
+    let request = wait(&received);
+
+    assert!(request.starts_with("POST /hook HTTP/1.1\r\n"));
+    assert_eq!(Some("application/json"), header(&request, "Content-Type"));
+    assert_eq!(Some("paid"), header(&request, "X-Request-Id"));
+    assert_eq!(Some("200"), header(&request, "X-Slumber-Status"));
+    assert_eq!(Some("1"), header(&request, "X-Slumber-Callback-Attempt"));
+    assert_eq!("paid", body(&request)["request_id"]);
+    assert_eq!(20, body(&request)["slumber"]["time_millis"]);
+
+    // test that a failed callback is retried
+    let request = wait(&received);
+
+    assert_eq!(Some("2"), header(&request, "X-Slumber-Callback-Attempt"));
+    assert_eq!("paid", body(&request)["request_id"]);
+}
+
+#[test]
+fn test_background() {
+    let (port, received) = receiver(vec![200]);
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(config(&["127.0.0.1"]))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    // test that a background callback is accepted before the request sleeps
+    let url = format!("http://127.0.0.1:{}/hook", port);
+    let req = TestRequest::with_header("X-Slumber-Callback", url.as_str())
+        .header("X-Slumber-Callback-Async", "true")
+        .header("X-Slumber-Callback-Delay-Millis", "20")
+        .header("X-Request-Id", "later")
+        .uri("/sleep/200")
+        .to_request();
+
+    let started = Instant::now();
+    let res = test::call_service(&mut app, req);
+
+    assert!(started.elapsed() < Duration::from_millis(200));
+    assert_eq!(202, res.status().as_u16());
+    assert_eq!("later", res.headers().get("X-Request-Id").unwrap());
+
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(res)).unwrap();
+
+    assert_eq!("later", json["request_id"]);
+    assert_eq!(url, json["callback"]);
+
+    // test that the callback follows once the request has slept
+    let request = wait(&received);
+
+    assert!(started.elapsed() >= Duration::from_millis(220));
+    assert_eq!("later", body(&request)["request_id"]);
+    assert_eq!(200, body(&request)["slumber"]["time_millis"]);
+}
+
+#[test]
+fn test_scaled_delay() {
+    let (port, received) = receiver(vec![200]);
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(config(&["127.0.0.1"]))
+            .register_data(Data::new(TimeScale::new(0.1)))
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    let req = TestRequest::with_uri(&format!(
+        "/sleep/20?callback=http://127.0.0.1:{}/hook&callback_delay=3000",
+        port
+    ))
+    .to_request();
+
+    let started = Instant::now();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(200, res.status().as_u16());
+
+    // test that the delay before calling back is scaled along with the sleep
+    wait(&received);
+
+    assert!(started.elapsed() >= Duration::from_millis(300));
+    assert!(started.elapsed() < Duration::from_millis(3000));
+}
+
+#[test]
+fn test_not_allowed() {
+    let mut app = test::init_service(
+        App::new()
+            .register_data(config(&[]))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    let req = TestRequest::with_uri("/sleep/10?callback=http://127.0.0.1:9000/hook").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(403, res.status().as_u16());
+
+    let json: serde_json::Value = serde_json::from_slice(&test::read_body(res)).unwrap();
+
+    assert!(json["error"]
+        .as_str()
+        .unwrap()
+        .starts_with("Callbacks to 'http://127.0.0.1:9000/hook' aren't allowed"));
+}
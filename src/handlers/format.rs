@@ -0,0 +1,141 @@
+#[cfg(test)]
+mod tests;
+
+use serde::Serialize;
+use serde_json::Value;
+
+use std::fmt::Write;
+
+/// The format a response body is serialized in.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub enum ResponseFormat {
+    Json,
+    #[default]
+    PrettyJson,
+    Yaml,
+    Text,
+    MessagePack,
+    Cbor,
+}
+
+impl ResponseFormat {
+    /// Parse the name of a format as given in the query string.
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.trim().to_lowercase().as_str() {
+            "json" => Some(ResponseFormat::Json),
+            "pretty" => Some(ResponseFormat::PrettyJson),
+            "yaml" | "yml" => Some(ResponseFormat::Yaml),
+            "text" | "txt" => Some(ResponseFormat::Text),
+            "msgpack" | "messagepack" => Some(ResponseFormat::MessagePack),
+            "cbor" => Some(ResponseFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Parse a media type, treating wildcards as the default format.
+    fn from_media_type(media_type: &str) -> Option<Self> {
+        match media_type {
+            "*/*" | "application/*" => Some(ResponseFormat::default()),
+            "application/json" => Some(ResponseFormat::Json),
+            "application/yaml" | "application/x-yaml" | "text/yaml" | "text/x-yaml" => {
+                Some(ResponseFormat::Yaml)
+            }
+            "text/plain" | "text/*" => Some(ResponseFormat::Text),
+            "application/msgpack" | "application/x-msgpack" | "application/vnd.msgpack" => {
+                Some(ResponseFormat::MessagePack)
+            }
+            "application/cbor" => Some(ResponseFormat::Cbor),
+            _ => None,
+        }
+    }
+
+    /// Choose the most preferred supported format from an `Accept` header.
+    pub fn from_accept(accept: &str) -> Option<Self> {
+        let mut ranges: Vec<(f32, Self)> = accept
+            .split(',')
+            .filter_map(|range| {
+                let mut parts = range.split(';');
+                let media_type = parts.next()?.trim().to_lowercase();
+
+                let quality = parts
+                    .filter_map(|p| p.trim().strip_prefix("q="))
+                    .filter_map(|q| q.trim().parse::<f32>().ok())
+                    .next()
+                    .unwrap_or(1.0);
+
+                Self::from_media_type(&media_type)
+                    .filter(|_| quality > 0.0)
+                    .map(|format| (quality, format))
+            })
+            .collect();
+
+        // the sort is stable, so ties are broken by the order in the header
+        ranges.sort_by(|a, b| b.0.partial_cmp(&a.0).unwrap_or(std::cmp::Ordering::Equal));
+
+        ranges.first().map(|(_, format)| *format)
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            ResponseFormat::Json | ResponseFormat::PrettyJson => "application/json",
+            ResponseFormat::Yaml => "application/yaml",
+            ResponseFormat::Text => "text/plain; charset=utf-8",
+            ResponseFormat::MessagePack => "application/msgpack",
+            ResponseFormat::Cbor => "application/cbor",
+        }
+    }
+
+    /// Serialize a value in this format.
+    pub fn render<T: Serialize>(self, value: &T) -> Result<Vec<u8>, String> {
+        match self {
+            ResponseFormat::Json => serde_json::to_vec(value).map_err(|e| e.to_string()),
+            ResponseFormat::PrettyJson => {
+                serde_json::to_vec_pretty(value).map_err(|e| e.to_string())
+            }
+            ResponseFormat::Yaml => serde_yaml::to_vec(value).map_err(|e| e.to_string()),
+            ResponseFormat::Text => {
+                let value = serde_json::to_value(value).map_err(|e| e.to_string())?;
+                let mut text = String::new();
+
+                flatten(&mut text, "", &value);
+
+                Ok(text.into_bytes())
+            }
+            ResponseFormat::MessagePack => {
+                rmp_serde::to_vec_named(value).map_err(|e| e.to_string())
+            }
+            ResponseFormat::Cbor => serde_cbor::to_vec(value).map_err(|e| e.to_string()),
+        }
+    }
+}
+
+/// Write a value as `key=value` lines, joining nested keys and array indices with dots.
+fn flatten(text: &mut String, prefix: &str, value: &Value) {
+    let key = |name: &str| {
+        if prefix.is_empty() {
+            name.to_string()
+        } else {
+            format!("{}.{}", prefix, name)
+        }
+    };
+
+    match value {
+        Value::Null => {}
+        Value::Object(map) => {
+            for (name, value) in map {
+                flatten(text, &key(name), value);
+            }
+        }
+        Value::Array(values) => {
+            for (index, value) in values.iter().enumerate() {
+                flatten(text, &key(&index.to_string()), value);
+            }
+        }
+        Value::String(s) => {
+            let _ = writeln!(text, "{}={}", prefix, s);
+        }
+        value => {
+            let _ = writeln!(text, "{}={}", prefix, value);
+        }
+    }
+}
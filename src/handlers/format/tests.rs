@@ -0,0 +1,138 @@
+use super::ResponseFormat;
+
+use crate::handlers::response::SlumberResponse;
+use crate::handlers::SlumberKind;
+
+use serde::Serialize;
+
+use std::time::Duration;
+
+#[derive(Serialize)]
+struct Nested {
+    time_millis: u64,
+    time: String,
+    min_time: Option<String>,
+}
+
+#[derive(Serialize)]
+struct Payload {
+    slumber: Nested,
+    request_id: String,
+    tags: Vec<&'static str>,
+}
+
+fn payload() -> Payload {
+    Payload {
+        slumber: Nested {
+            time_millis: 1500,
+            time: "1.5s".to_string(),
+            min_time: None,
+        },
+        request_id: "abc".to_string(),
+        tags: vec!["a", "b"],
+    }
+}
+
+#[test]
+fn test_from_name() {
+    assert_eq!(
+        Some(ResponseFormat::Json),
+        ResponseFormat::from_name("json")
+    );
+    assert_eq!(
+        Some(ResponseFormat::PrettyJson),
+        ResponseFormat::from_name("pretty")
+    );
+    assert_eq!(
+        Some(ResponseFormat::Yaml),
+        ResponseFormat::from_name("YAML")
+    );
+    assert_eq!(
+        Some(ResponseFormat::MessagePack),
+        ResponseFormat::from_name("msgpack")
+    );
+    assert_eq!(None, ResponseFormat::from_name("xml"));
+}
+
+#[test]
+fn test_from_accept() {
+    assert_eq!(
+        Some(ResponseFormat::Json),
+        ResponseFormat::from_accept("application/json")
+    );
+
+    // test that wildcards get the default format
+    assert_eq!(
+        Some(ResponseFormat::PrettyJson),
+        ResponseFormat::from_accept("text/html,application/xhtml+xml,*/*;q=0.8")
+    );
+
+    // test quality ordering
+    assert_eq!(
+        Some(ResponseFormat::Cbor),
+        ResponseFormat::from_accept("application/json;q=0.5, application/cbor")
+    );
+    assert_eq!(
+        Some(ResponseFormat::Text),
+        ResponseFormat::from_accept("application/yaml;q=0, text/plain;q=0.1")
+    );
+
+    // test that unsupported types are ignored
+    assert_eq!(None, ResponseFormat::from_accept("image/png"));
+}
+
+#[test]
+fn test_render() {
+    let payload = payload();
+
+    assert_eq!(
+        r#"{"slumber":{"time_millis":1500,"time":"1.5s","min_time":null},"request_id":"abc","tags":["a","b"]}"#,
+        String::from_utf8(ResponseFormat::Json.render(&payload).unwrap()).unwrap()
+    );
+
+    assert_eq!(
+        "request_id=abc\nslumber.time=1.5s\nslumber.time_millis=1500\ntags.0=a\ntags.1=b\n",
+        String::from_utf8(ResponseFormat::Text.render(&payload).unwrap()).unwrap()
+    );
+
+    let yaml = String::from_utf8(ResponseFormat::Yaml.render(&payload).unwrap()).unwrap();
+    assert!(yaml.contains("time_millis: 1500"));
+
+    // test that the binary formats round trip
+    let value: serde_json::Value =
+        rmp_serde::from_slice(&ResponseFormat::MessagePack.render(&payload).unwrap()).unwrap();
+    assert_eq!("abc", value["request_id"]);
+
+    let value: serde_json::Value =
+        serde_cbor::from_slice(&ResponseFormat::Cbor.render(&payload).unwrap()).unwrap();
+    assert_eq!(1500, value["slumber"]["time_millis"]);
+}
+
+#[test]
+fn test_render_response() {
+    let response =
+        SlumberResponse::builder("abc", SlumberKind::Random, &Duration::from_millis(1500))
+            .min(&Duration::from_millis(1000))
+            .max(&Duration::from_millis(2000))
+            .scaled(0.5, &Duration::from_millis(750))
+            .profile("db-slow")
+            .build();
+
+    // test that the durations of a real response survive the binary formats as integers
+    for format in [ResponseFormat::MessagePack, ResponseFormat::Cbor] {
+        let rendered = format.render(&response).unwrap();
+        let value: serde_json::Value = match format {
+            ResponseFormat::MessagePack => rmp_serde::from_slice(&rendered).unwrap(),
+            _ => serde_cbor::from_slice(&rendered).unwrap(),
+        };
+
+        assert_eq!("abc", value["request_id"]);
+        assert_eq!("db-slow", value["profile"]);
+        assert_eq!("random", value["slumber"]["type"]);
+        assert_eq!(1500, value["slumber"]["time_millis"]);
+        assert_eq!("1.5s", value["slumber"]["time"]);
+        assert_eq!(1000, value["slumber"]["min_time_millis"]);
+        assert_eq!(2000, value["slumber"]["max_time_millis"]);
+        assert_eq!(750, value["slumber"]["scaled_time_millis"]);
+    }
+}
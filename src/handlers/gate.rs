@@ -0,0 +1,222 @@
+#[cfg(test)]
+mod tests;
+
+use futures::sync::oneshot::{self, Receiver, Sender};
+use futures::{Async, Future, Poll};
+
+use parking_lot::Mutex;
+
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+/// Named gates which hold requests until they're opened, for deterministic concurrency tests.
+///
+/// A gate is closed until it's opened, at which point every waiting request is released and later requests pass
+/// straight through until it's closed again. A gate with a barrier also releases its waiting requests, without
+/// opening, as soon as the given number of requests are waiting.
+#[derive(Clone, Default)]
+pub struct Gates(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    /// The number of requests which have waited, used to key each request.
+    count: AtomicU64,
+    gates: Mutex<BTreeMap<String, Gate>>,
+}
+
+#[derive(Default)]
+struct Gate {
+    open: bool,
+    barrier: Option<usize>,
+    waiting: BTreeMap<u64, Sender<()>>,
+}
+
+impl Gate {
+    /// Release every waiting request, returning how many there were.
+    fn release(&mut self) -> usize {
+        let waiting = std::mem::take(&mut self.waiting);
+        let released = waiting.len();
+
+        for (_, sender) in waiting {
+            let _ = sender.send(());
+        }
+
+        released
+    }
+
+    /// Release the waiting requests if there are enough of them to pass the barrier.
+    fn check_barrier(&mut self) -> usize {
+        match self.barrier {
+            Some(barrier) if self.waiting.len() >= barrier => self.release(),
+            _ => 0,
+        }
+    }
+
+    /// Whether the gate is in the state of a gate which was never used, so needn't be kept.
+    fn is_idle(&self) -> bool {
+        !self.open && self.barrier.is_none() && self.waiting.is_empty()
+    }
+
+    fn describe(&self, name: &str) -> GateState {
+        GateState {
+            name: name.to_string(),
+            open: self.open,
+            barrier: self.barrier,
+            waiting: self.waiting.len(),
+        }
+    }
+}
+
+impl Gates {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Wait at a gate until it's opened or its barrier is reached, creating the gate if necessary.
+    pub fn wait(&self, name: &str) -> GateWait {
+        let mut gates = self.0.gates.lock();
+        let gate = gates.entry(name.to_string()).or_default();
+
+        if gate.open {
+            return GateWait {
+                receiver: None,
+                registration: None,
+            };
+        }
+
+        let key = self.0.count.fetch_add(1, Ordering::SeqCst);
+        let (sender, receiver) = oneshot::channel();
+
+        gate.waiting.insert(key, sender);
+        gate.check_barrier();
+
+        GateWait {
+            receiver: Some(receiver),
+            registration: Some((self.clone(), name.to_string(), key)),
+        }
+    }
+
+    /// The gates which are open, have a barrier, or have requests waiting, by name.
+    pub fn list(&self) -> Vec<GateState> {
+        self.0
+            .gates
+            .lock()
+            .iter()
+            .map(|(name, gate)| gate.describe(name))
+            .collect()
+    }
+
+    /// Report a gate, which is closed if it's never been used.
+    pub fn get(&self, name: &str) -> GateState {
+        self.0
+            .gates
+            .lock()
+            .get(name)
+            .map(|gate| gate.describe(name))
+            .unwrap_or_else(|| Gate::default().describe(name))
+    }
+
+    /// Open a gate, releasing every waiting request, and return how many were released.
+    pub fn open(&self, name: &str) -> usize {
+        let mut gates = self.0.gates.lock();
+        let gate = gates.entry(name.to_string()).or_default();
+
+        gate.open = true;
+        gate.release()
+    }
+
+    /// Close a gate, so that later requests wait at it.
+    pub fn close(&self, name: &str) {
+        let mut gates = self.0.gates.lock();
+
+        if let Some(gate) = gates.get_mut(name) {
+            gate.open = false;
+
+            if gate.is_idle() {
+                gates.remove(name);
+            }
+        }
+    }
+
+    /// Change the barrier of a gate, returning how many waiting requests it released.
+    pub fn set_barrier(&self, name: &str, barrier: Option<usize>) -> usize {
+        let mut gates = self.0.gates.lock();
+        let gate = gates.entry(name.to_string()).or_default();
+
+        gate.barrier = barrier;
+
+        let released = gate.check_barrier();
+
+        if gate.is_idle() {
+            gates.remove(name);
+        }
+
+        released
+    }
+
+    /// Forget a gate, releasing every waiting request, and return how many were released.
+    pub fn remove(&self, name: &str) -> Option<usize> {
+        self.0
+            .gates
+            .lock()
+            .remove(name)
+            .map(|mut gate| gate.release())
+    }
+
+    fn leave(&self, name: &str, key: u64) {
+        let mut gates = self.0.gates.lock();
+
+        if let Some(gate) = gates.get_mut(name) {
+            gate.waiting.remove(&key);
+
+            if gate.is_idle() {
+                gates.remove(name);
+            }
+        }
+    }
+}
+
+/// The state of a gate.
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct GateState {
+    pub name: String,
+    pub open: bool,
+    pub barrier: Option<usize>,
+    /// The number of requests waiting at the gate.
+    pub waiting: usize,
+}
+
+/// A request waiting at a gate, which is no longer counted as waiting once it's released or dropped.
+pub struct GateWait {
+    receiver: Option<Receiver<()>>,
+    registration: Option<(Gates, String, u64)>,
+}
+
+impl Future for GateWait {
+    type Item = ();
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        if let Some(receiver) = &mut self.receiver {
+            // the sender is only dropped without sending if the gate was forgotten, which releases it anyway
+            if let Ok(Async::NotReady) = receiver.poll() {
+                return Ok(Async::NotReady);
+            }
+        }
+
+        self.registration = None;
+
+        Ok(Async::Ready(()))
+    }
+}
+
+impl Drop for GateWait {
+    fn drop(&mut self) {
+        if let Some((gates, name, key)) = self.registration.take() {
+            gates.leave(&name, key);
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use super::{GateState, Gates};
+
+use crate::config::{CliArgs, SharedConfig, TimeScale};
+use crate::handlers::{admin, path};
+
+use actix_web::dev::Service;
+use actix_web::test::{self, TestRequest};
+use actix_web::web::{self, Data};
+use actix_web::App;
+
+use futures::{Async, Future};
+
+use std::time::{Duration, Instant};
+
+use tokio::timer::Delay;
+
+fn state(name: &str, open: bool, barrier: Option<usize>, waiting: usize) -> GateState {
+    GateState {
+        name: name.to_string(),
+        open,
+        barrier,
+        waiting,
+    }
+}
+
+#[test]
+fn test_open() {
+    let gates = Gates::new();
+
+    let first = gates.wait("a");
+    let second = gates.wait("a");
+
+    assert_eq!(vec![state("a", false, None, 2)], gates.list());
+    assert_eq!(2, gates.open("a"));
+    assert_eq!(state("a", true, None, 0), gates.get("a"));
+
+    test::block_on(first.join(second)).unwrap();
+
+    // test that requests pass straight through an open gate, until it's closed again
+    test::block_on(gates.wait("a")).unwrap();
+
+    gates.close("a");
+
+    assert!(gates.list().is_empty());
+    assert_eq!(state("a", false, None, 0), gates.get("a"));
+
+    let mut waiting = gates.wait("a");
+
+    assert_eq!(
+        Async::NotReady,
+        test::block_fn(|| Ok::<_, ()>(waiting.poll().unwrap())).unwrap()
+    );
+    assert_eq!(1, gates.get("a").waiting);
+}
+
+#[test]
+fn test_barrier() {
+    let gates = Gates::new();
+
+    let first = gates.wait("a");
+
+    // test that a barrier releases the requests already waiting once it's reached
+    assert_eq!(0, gates.set_barrier("a", Some(2)));
+
+    let second = gates.wait("a");
+
+    test::block_on(first.join(second)).unwrap();
+
+    // test that the barrier stays closed, releasing every group of requests in turn
+    assert_eq!(state("a", false, Some(2), 0), gates.get("a"));
+
+    let third = gates.wait("a");
+
+    assert_eq!(1, gates.get("a").waiting);
+    assert_eq!(1, gates.set_barrier("a", Some(1)));
+
+    test::block_on(third).unwrap();
+
+    assert_eq!(0, gates.set_barrier("a", None));
+    assert!(gates.list().is_empty());
+}
+
+#[test]
+fn test_leave() {
+    let gates = Gates::new();
+
+    let first = gates.wait("a");
+    let second = gates.wait("b");
+
+    // test that a request which goes away no longer waits, and forgets the gate once nothing waits at it
+    drop(first);
+
+    assert_eq!(vec![state("b", false, None, 1)], gates.list());
+    assert_eq!(Some(1), gates.remove("b"));
+    assert_eq!(None, gates.remove("b"));
+
+    test::block_on(second).unwrap();
+}
+
+#[test]
+fn test_gate() {
+    let gates = Gates::new();
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(Data::new(SharedConfig::new(CliArgs {
+                admin_insecure: true,
+                ..CliArgs::default()
+            })))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .register_data(Data::new(gates.clone()))
+            .configure(admin::configure)
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    // test that a gated request waits for its gate rather than sleeping
+    let req = TestRequest::with_uri("/sleep/10?gate=start").to_request();
+
+    let open = Delay::new(Instant::now() + Duration::from_millis(50))
+        .map_err(|_| ())
+        .map(|_| {
+            let waiting = gates.get("start").waiting;
+            let released = gates.open("start");
+
+            (waiting, released)
+        });
+
+    let started = Instant::now();
+    let (res, (waiting, released)) = test::block_fn(|| app.call(req).map_err(|_| ()).join(open))
+        .ok()
+        .unwrap();
+
+    assert!(started.elapsed() >= Duration::from_millis(50));
+    assert_eq!(1, waiting);
+    assert_eq!(1, released);
+    assert_eq!(200, res.status().as_u16());
+    assert_eq!("start", res.headers().get("X-Slumber-Gate").unwrap());
+    assert_eq!("true", res.headers().get("X-Slumber-Gate-Opened").unwrap());
+
+    // test that a gated request gives up after its timeout
+    let req = TestRequest::with_header("X-Slumber-Gate", "other")
+        .header("X-Slumber-Gate-Timeout-Millis", "20")
+        .uri("/sleep/10")
+        .to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!(20, json["slumber"]["time_millis"]);
+    assert_eq!("other", json["gate"]["name"]);
+    assert_eq!(false, json["gate"]["opened"]);
+    assert!(gates.list().iter().all(|gate| gate.name != "other"));
+
+    // test that gates can be managed through the admin API
+    let req = TestRequest::put()
+        .uri("/_gates/start")
+        .set_json(&serde_json::json!({"barrier": 3}))
+        .to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!(true, json["open"]);
+    assert_eq!(3, json["barrier"]);
+
+    let req = TestRequest::post().uri("/_gates/start/close").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!(false, json["open"]);
+
+    let req = TestRequest::with_uri("/_gates").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!("start", json[0]["name"]);
+
+    let req = TestRequest::post().uri("/_gates/start/open").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!(true, json["open"]);
+    assert_eq!(0, json["released"]);
+
+    let req = TestRequest::delete().uri("/_gates/start").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(204, res.status().as_u16());
+
+    // test that unknown gates and empty barriers are rejected
+    let req = TestRequest::delete().uri("/_gates/start").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(404, res.status().as_u16());
+
+    let req = TestRequest::put()
+        .uri("/_gates/start")
+        .set_json(&serde_json::json!({"barrier": 0}))
+        .to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(400, res.status().as_u16());
+}
@@ -0,0 +1,240 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::error::ErrorInternalServerError;
+use actix_web::web::{self, Bytes, Data, Query, ServiceConfig};
+use actix_web::HttpResponse;
+
+use chrono::{DateTime, Utc};
+
+use futures::sync::mpsc::{self, UnboundedSender};
+use futures::Stream;
+
+use parking_lot::Mutex;
+
+use serde::{Deserialize, Serialize};
+
+use std::collections::VecDeque;
+use std::time::Duration;
+
+use super::admin::AdminAuth;
+use super::response::{SlumberDuration, SlumberResponse};
+use super::Received;
+
+/// Register the request history routes on the main listener, behind the admin token.
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.route("/_requests", web::get().to(requests))
+        .route("/_requests/", web::get().to(requests))
+        .route("/_requests/stream", web::get().to(stream))
+        .route("/_requests/stream/", web::get().to(stream));
+}
+
+/// A bounded history of the most recent sleepy requests, which also feeds live subscribers as requests complete.
+pub struct History {
+    capacity: usize,
+    records: Mutex<VecDeque<RequestRecord>>,
+    subscribers: Mutex<Vec<Subscriber>>,
+}
+
+/// A client following the live feed, along with the requests it's interested in.
+struct Subscriber {
+    filter: HistoryQuery,
+    sender: UnboundedSender<Bytes>,
+}
+
+impl History {
+    /// Keep up to the given number of requests, dropping the oldest beyond that.
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            records: Mutex::new(VecDeque::with_capacity(capacity)),
+            subscribers: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Record a completed request and push it to every interested subscriber.
+    pub fn record(&self, record: RequestRecord) {
+        let mut subscribers = self.subscribers.lock();
+
+        if !subscribers.is_empty() {
+            let event = Bytes::from(event(&record));
+
+            // subscribers which have gone away are dropped when the next request completes, whether or not they'd have
+            // been sent it
+            subscribers.retain(|subscriber| {
+                if subscriber.filter.matches(&record) {
+                    subscriber.sender.unbounded_send(event.clone()).is_ok()
+                } else {
+                    !subscriber.sender.is_closed()
+                }
+            });
+        }
+
+        drop(subscribers);
+
+        if self.capacity == 0 {
+            return;
+        }
+
+        let mut records = self.records.lock();
+
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+
+        records.push_back(record);
+    }
+
+    /// The recorded requests matching a query, from the oldest.
+    pub fn find(&self, query: &HistoryQuery) -> Vec<RequestRecord> {
+        let records = self.records.lock();
+        let mut found: Vec<RequestRecord> = records
+            .iter()
+            .filter(|record| query.matches(record))
+            .cloned()
+            .collect();
+
+        // a limit keeps the most recent requests
+        if let Some(limit) = query.limit {
+            found.drain(..found.len().saturating_sub(limit));
+        }
+
+        found
+    }
+
+    /// Follow requests matching a query as they complete, as server-sent events.
+    pub fn subscribe(&self, filter: HistoryQuery) -> impl Stream<Item = Bytes, Error = ()> {
+        let (sender, receiver) = mpsc::unbounded();
+        let mut subscribers = self.subscribers.lock();
+
+        subscribers.retain(|subscriber| !subscriber.sender.is_closed());
+        subscribers.push(Subscriber { filter, sender });
+
+        receiver
+    }
+}
+
+/// A request which has been served, as kept in the history.
+#[derive(Clone, Debug, Serialize)]
+pub struct RequestRecord {
+    pub request_id: String,
+    /// When the request was received.
+    pub timestamp: DateTime<Utc>,
+    pub method: String,
+    pub path: String,
+    /// The route pattern which served the request.
+    pub route: String,
+    /// The resolved sleep, as returned in the response.
+    pub slumber: SlumberDuration,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    /// The time the request actually spent sleeping.
+    pub actual_time: String,
+    pub actual_time_millis: u64,
+    pub status: u16,
+    pub fault: bool,
+}
+
+impl RequestRecord {
+    /// Describe a request from its response.
+    pub fn new(
+        received: &Received,
+        route: &str,
+        response: &SlumberResponse,
+        actual: &Duration,
+        status: u16,
+    ) -> Self {
+        Self {
+            request_id: response.request_id.clone(),
+            timestamp: received.time,
+            method: received.method.clone(),
+            path: received.path.clone(),
+            route: route.to_string(),
+            slumber: response.duration.clone(),
+            profile: response.profile.clone(),
+            rule: response.rule.clone(),
+            dry_run: response.dry_run,
+            actual_time: format!("{:?}", actual),
+            actual_time_millis: actual.as_millis() as u64,
+            status,
+            fault: response.fault,
+        }
+    }
+}
+
+/// The requests to return from the history or follow in the live feed.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct HistoryQuery {
+    /// Only requests with this request id.
+    pub id: Option<String>,
+    /// Only requests for this path, or for paths starting with it if it ends with `*`.
+    pub path: Option<String>,
+    /// Only requests received at or after this time.
+    pub since: Option<DateTime<Utc>>,
+    /// Only requests received before this time.
+    pub until: Option<DateTime<Utc>>,
+    /// At most this many of the most recent matching requests.
+    pub limit: Option<usize>,
+}
+
+impl HistoryQuery {
+    /// Check whether a request matches every filter of the query.
+    pub fn matches(&self, record: &RequestRecord) -> bool {
+        let path = match self.path.as_deref() {
+            Some(path) => match path.strip_suffix('*') {
+                Some(prefix) => record.path.starts_with(prefix),
+                None => record.path == path,
+            },
+            None => true,
+        };
+
+        path && self.id.as_ref().is_none_or(|id| *id == record.request_id)
+            && self.since.is_none_or(|since| record.timestamp >= since)
+            && self.until.is_none_or(|until| record.timestamp < until)
+    }
+}
+
+#[derive(Serialize)]
+pub struct RequestsBody {
+    pub requests: Vec<RequestRecord>,
+}
+
+/// Format a request as a server-sent event.
+fn event(record: &RequestRecord) -> String {
+    // serializing a record can't fail, as it's made of strings and numbers
+    format!(
+        "event: request\ndata: {}\n\n",
+        serde_json::to_string(record).unwrap()
+    )
+}
+
+/// Handler for listing recent requests.
+pub fn requests(
+    _auth: AdminAuth,
+    history: Data<History>,
+    query: Query<HistoryQuery>,
+) -> HttpResponse {
+    HttpResponse::Ok().json(RequestsBody {
+        requests: history.find(&query),
+    })
+}
+
+/// Handler for following requests as they complete, as server-sent events.
+pub fn stream(
+    _auth: AdminAuth,
+    history: Data<History>,
+    query: Query<HistoryQuery>,
+) -> HttpResponse {
+    let events = history
+        .subscribe(query.into_inner())
+        .map_err(|_| ErrorInternalServerError("The request feed was closed."));
+
+    HttpResponse::Ok()
+        .content_type("text/event-stream")
+        .header("Cache-Control", "no-cache")
+        .streaming(events)
+}
@@ -0,0 +1,202 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use super::{History, HistoryQuery, RequestRecord};
+
+use crate::config::{CliArgs, SharedConfig, TimeScale};
+use crate::handlers::path;
+use crate::handlers::response::SlumberResponse;
+use crate::handlers::SlumberKind;
+
+use actix_web::test::{self, TestRequest};
+use actix_web::web::{self, Data};
+use actix_web::App;
+
+use chrono::{TimeZone, Utc};
+
+use futures::Stream;
+
+use std::time::Duration;
+
+fn record(id: &str, path: &str, second: u32) -> RequestRecord {
+    let response = SlumberResponse::builder(id, SlumberKind::Fixed, &Duration::from_millis(15))
+        .fault()
+        .build();
+
+    RequestRecord {
+        request_id: id.to_string(),
+        timestamp: Utc.ymd(2024, 1, 1).and_hms(0, 0, second),
+        method: "GET".to_string(),
+        path: path.to_string(),
+        route: "default".to_string(),
+        slumber: response.duration,
+        profile: None,
+        rule: None,
+        dry_run: false,
+        actual_time: "15ms".to_string(),
+        actual_time_millis: 15,
+        status: 503,
+        fault: response.fault,
+    }
+}
+
+fn ids(records: &[RequestRecord]) -> Vec<&str> {
+    records
+        .iter()
+        .map(|record| record.request_id.as_str())
+        .collect()
+}
+
+#[test]
+fn test_find() {
+    let history = History::new(3);
+
+    history.record(record("a", "/sleep/15", 1));
+    history.record(record("b", "/sleep/15", 2));
+    history.record(record("c", "/random", 3));
+    history.record(record("d", "/sleep/20", 4));
+
+    // test that the oldest request is dropped once the history is full
+    assert_eq!(
+        vec!["b", "c", "d"],
+        ids(&history.find(&HistoryQuery::default()))
+    );
+
+    let mut query = HistoryQuery::default();
+    query.id = Some("c".to_string());
+    assert_eq!(vec!["c"], ids(&history.find(&query)));
+
+    let mut query = HistoryQuery::default();
+    query.path = Some("/sleep/15".to_string());
+    assert_eq!(vec!["b"], ids(&history.find(&query)));
+
+    query.path = Some("/sleep/*".to_string());
+    assert_eq!(vec!["b", "d"], ids(&history.find(&query)));
+
+    // test that the time range includes its start but not its end
+    let mut query = HistoryQuery::default();
+    query.since = Some(Utc.ymd(2024, 1, 1).and_hms(0, 0, 2));
+    query.until = Some(Utc.ymd(2024, 1, 1).and_hms(0, 0, 4));
+    assert_eq!(vec!["b", "c"], ids(&history.find(&query)));
+
+    // test that a limit keeps the most recent requests
+    let mut query = HistoryQuery::default();
+    query.limit = Some(2);
+    assert_eq!(vec!["c", "d"], ids(&history.find(&query)));
+}
+
+#[test]
+fn test_disabled() {
+    let history = History::new(0);
+
+    history.record(record("a", "/sleep/15", 1));
+
+    assert!(history.find(&HistoryQuery::default()).is_empty());
+}
+
+#[test]
+fn test_requests() {
+    let mut app = test::init_service(
+        App::new()
+            .register_data(Data::new(SharedConfig::new(CliArgs {
+                admin_insecure: true,
+                ..CliArgs::default()
+            })))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .register_data(Data::new(History::new(10)))
+            .configure(super::configure)
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    for uri in &["/sleep/15?dry_run=1", "/sleep/25?dry_run=1"] {
+        let req = TestRequest::with_header("X-Request-Id", &uri[7..9])
+            .uri(uri)
+            .to_request();
+
+        test::call_service(&mut app, req);
+    }
+
+    let req = TestRequest::with_uri("/_requests?path=/sleep/25").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+    let requests = json["requests"].as_array().unwrap();
+
+    assert_eq!(1, requests.len());
+    assert_eq!("25", requests[0]["request_id"]);
+    assert_eq!("GET", requests[0]["method"]);
+    assert_eq!("/sleep/{millis}", requests[0]["route"]);
+    assert_eq!(25, requests[0]["slumber"]["time_millis"]);
+    assert_eq!(true, requests[0]["dry_run"]);
+    assert_eq!(0, requests[0]["actual_time_millis"]);
+    assert_eq!(200, requests[0]["status"]);
+    assert_eq!(false, requests[0]["fault"]);
+
+    // test that an invalid time range is rejected
+    let req = TestRequest::with_uri("/_requests?since=yesterday").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(400, res.status().as_u16());
+}
+
+#[test]
+fn test_stream() {
+    let history = Data::new(History::new(10));
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(Data::new(SharedConfig::new(CliArgs {
+                admin_token: Some("secret".to_string()),
+                ..CliArgs::default()
+            })))
+            .register_data(history.clone())
+            .configure(super::configure),
+    );
+
+    // test that the feed requires the admin token
+    let req = TestRequest::with_uri("/_requests/stream").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(401, res.status().as_u16());
+
+    let req = TestRequest::with_header("Authorization", "Bearer secret")
+        .uri("/_requests/stream?path=/sleep/*")
+        .to_request();
+    let mut res = test::call_service(&mut app, req);
+
+    assert_eq!(
+        "text/event-stream",
+        res.headers().get("Content-Type").unwrap()
+    );
+
+    // test that only requests matching the filter are pushed, as they complete
+    history.record(record("a", "/random", 1));
+    history.record(record("b", "/sleep/15", 2));
+
+    let (chunk, _) = test::block_on(res.take_body().into_future()).ok().unwrap();
+    let chunk = String::from_utf8(chunk.unwrap().to_vec()).unwrap();
+
+    assert!(chunk.starts_with("event: request\ndata: {"));
+    assert!(chunk.ends_with("}\n\n"));
+
+    let json: serde_json::Value = serde_json::from_str(&chunk[21..chunk.len() - 2]).unwrap();
+
+    assert_eq!("b", json["request_id"]);
+    assert_eq!(true, json["fault"]);
+}
+
+#[test]
+fn test_unsubscribe() {
+    let history = History::new(10);
+
+    let mut query = HistoryQuery::default();
+    query.path = Some("/sleep/*".to_string());
+
+    let feed = history.subscribe(query);
+
+    history.record(record("a", "/random", 1));
+    assert_eq!(1, history.subscribers.lock().len());
+
+    // test that a subscriber which has gone away is dropped, even if it wouldn't have been sent the request
+    drop(feed);
+    history.record(record("b", "/random", 2));
+
+    assert!(history.subscribers.lock().is_empty());
+}
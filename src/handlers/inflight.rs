@@ -0,0 +1,278 @@
+#[cfg(test)]
+mod tests;
+
+use chrono::{DateTime, Utc};
+
+use futures::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::{Async, Future, Poll, Stream};
+
+use parking_lot::Mutex;
+
+use serde::Serialize;
+
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use tokio::timer::Delay;
+
+use crate::config::FaultSettings;
+
+use super::SlumberKind;
+
+/// The longest a request can sleep for, however long it asks for, as the timer can't track deadlines much further out.
+const MAX_SLEEP: Duration = Duration::from_secs(365 * 24 * 60 * 60);
+
+/// The requests which are currently sleeping, which can be released early, extended, or aborted with a fault.
+///
+/// Requests are looked up by request id. As clients choose their own ids, every sleeping request with the id is
+/// affected.
+#[derive(Clone, Default)]
+pub struct InFlightRequests(Arc<Inner>);
+
+#[derive(Default)]
+struct Inner {
+    /// The number of requests which have slept, used to key each request.
+    count: AtomicU64,
+    requests: Mutex<BTreeMap<u64, Entry>>,
+}
+
+/// A sleeping request, along with the means to wake it.
+struct Entry {
+    request: InFlightRequest,
+    started: Instant,
+    deadline: Instant,
+    commands: UnboundedSender<Command>,
+}
+
+impl Entry {
+    /// Describe the request as it stands now.
+    fn describe(&self, now: Instant) -> InFlightRequest {
+        InFlightRequest {
+            time_millis: self.deadline.duration_since(self.started).as_millis() as u64,
+            elapsed_millis: now.duration_since(self.started).as_millis() as u64,
+            remaining_millis: self.deadline.saturating_duration_since(now).as_millis() as u64,
+            ..self.request.clone()
+        }
+    }
+}
+
+/// A change to a sleeping request.
+enum Command {
+    /// Sleep until a new deadline.
+    Reset(Instant),
+    /// Stop sleeping and respond.
+    Release,
+    /// Stop sleeping and respond with a fault.
+    Abort(FaultSettings),
+}
+
+impl InFlightRequests {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sleep for a duration, registering the request until it wakes or is dropped.
+    pub fn sleep(&self, request: InFlightRequest, duration: Duration) -> Sleep {
+        let key = self.0.count.fetch_add(1, Ordering::SeqCst);
+
+        let started = Instant::now();
+        let deadline = deadline(started, duration);
+        let (commands, receiver) = mpsc::unbounded();
+
+        self.0.requests.lock().insert(
+            key,
+            Entry {
+                request,
+                started,
+                deadline,
+                commands,
+            },
+        );
+
+        Sleep {
+            delay: Delay::new(deadline),
+            commands: Some(receiver),
+            registration: Some((self.clone(), key)),
+        }
+    }
+
+    /// The sleeping requests, from the longest sleeping.
+    pub fn list(&self) -> Vec<InFlightRequest> {
+        let now = Instant::now();
+
+        self.0
+            .requests
+            .lock()
+            .values()
+            .map(|entry| entry.describe(now))
+            .collect()
+    }
+
+    /// Wake the requests with an id now, returning them as they were.
+    pub fn release(&self, id: &str) -> Vec<InFlightRequest> {
+        self.wake(id, || Command::Release)
+    }
+
+    /// Wake the requests with an id now with a fault, returning them as they were.
+    pub fn abort(&self, id: &str, fault: FaultSettings) -> Vec<InFlightRequest> {
+        self.wake(id, || Command::Abort(fault.clone()))
+    }
+
+    /// Extend the sleep of the requests with an id, without letting them sleep for longer than the limit in total,
+    /// returning them as they now are.
+    pub fn extend(&self, id: &str, by: Duration, limit: Duration) -> Vec<InFlightRequest> {
+        let now = Instant::now();
+        let mut requests = self.0.requests.lock();
+
+        requests
+            .values_mut()
+            .filter(|entry| entry.request.request_id == id)
+            .map(|entry| {
+                // a request which already sleeps for longer than the limit isn't cut short
+                let cap = deadline(entry.started, limit);
+
+                entry.deadline = entry
+                    .deadline
+                    .checked_add(by)
+                    .map_or(cap, |extended| extended.min(cap))
+                    .max(entry.deadline);
+                let _ = entry
+                    .commands
+                    .unbounded_send(Command::Reset(entry.deadline));
+
+                entry.describe(now)
+            })
+            .collect()
+    }
+
+    /// Send a command which wakes the requests with an id, no longer listing them.
+    fn wake<F>(&self, id: &str, command: F) -> Vec<InFlightRequest>
+    where
+        F: Fn() -> Command,
+    {
+        let now = Instant::now();
+        let mut requests = self.0.requests.lock();
+
+        let keys: Vec<u64> = requests
+            .iter()
+            .filter(|(_, entry)| entry.request.request_id == id)
+            .map(|(key, _)| *key)
+            .collect();
+
+        keys.into_iter()
+            .filter_map(|key| requests.remove(&key))
+            .map(|entry| {
+                let _ = entry.commands.unbounded_send(command());
+
+                entry.describe(now)
+            })
+            .collect()
+    }
+
+    fn remove(&self, key: u64) {
+        self.0.requests.lock().remove(&key);
+    }
+}
+
+/// The end of a sleep starting at an instant, capped at the longest sleep rather than overflowing.
+fn deadline(started: Instant, duration: Duration) -> Instant {
+    started + duration.min(MAX_SLEEP)
+}
+
+/// A request which is sleeping.
+#[derive(Clone, Debug, Serialize)]
+pub struct InFlightRequest {
+    pub request_id: String,
+    /// When the request was received.
+    pub timestamp: DateTime<Utc>,
+    pub method: String,
+    pub path: String,
+    /// The route pattern which is serving the request.
+    pub route: String,
+    #[serde(rename = "type")]
+    pub kind: SlumberKind,
+    /// The time the request sleeps for in total, including any extensions.
+    pub time_millis: u64,
+    pub elapsed_millis: u64,
+    pub remaining_millis: u64,
+}
+
+/// How a sleep ended.
+#[derive(Debug, PartialEq)]
+pub enum Wake {
+    /// The sleep ran its course.
+    Elapsed,
+    /// The sleep was cut short.
+    Released,
+    /// The sleep was cut short, to respond with a fault.
+    Aborted(FaultSettings),
+    /// The gate the request was waiting at opened.
+    Opened,
+}
+
+/// A sleep which can be released, extended, or aborted while it's registered as in flight.
+///
+/// The request is no longer listed as in flight once the sleep wakes, or if it's dropped because the client went away.
+pub struct Sleep {
+    delay: Delay,
+    commands: Option<UnboundedReceiver<Command>>,
+    registration: Option<(InFlightRequests, u64)>,
+}
+
+impl Sleep {
+    /// Sleep for a duration without registering the request.
+    pub fn new(duration: Duration) -> Self {
+        Self {
+            delay: Delay::new(deadline(Instant::now(), duration)),
+            commands: None,
+            registration: None,
+        }
+    }
+
+    fn unregister(&mut self) {
+        if let Some((requests, key)) = self.registration.take() {
+            requests.remove(key);
+        }
+    }
+}
+
+impl Future for Sleep {
+    type Item = Wake;
+    type Error = ();
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        while let Some(commands) = &mut self.commands {
+            match commands.poll() {
+                Ok(Async::Ready(Some(Command::Reset(deadline)))) => self.delay.reset(deadline),
+                Ok(Async::Ready(Some(Command::Release))) => {
+                    self.unregister();
+                    return Ok(Async::Ready(Wake::Released));
+                }
+                Ok(Async::Ready(Some(Command::Abort(fault)))) => {
+                    self.unregister();
+                    return Ok(Async::Ready(Wake::Aborted(fault)));
+                }
+                Ok(Async::NotReady) => break,
+                // the request is no longer registered, so nothing else can change its sleep
+                Ok(Async::Ready(None)) | Err(()) => self.commands = None,
+            }
+        }
+
+        // a broken timer wakes the request rather than leaving it sleeping forever
+        match self.delay.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(())) | Err(_) => {
+                self.unregister();
+                Ok(Async::Ready(Wake::Elapsed))
+            }
+        }
+    }
+}
+
+impl Drop for Sleep {
+    fn drop(&mut self) {
+        self.unregister();
+    }
+}
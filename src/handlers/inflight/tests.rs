@@ -0,0 +1,235 @@
+use super::{InFlightRequest, InFlightRequests, Sleep, Wake};
+
+use crate::config::{CliArgs, FaultSettings, SharedConfig, TimeScale};
+use crate::handlers::{admin, path, SlumberKind};
+
+use actix_web::dev::Service;
+use actix_web::test::{self, TestRequest};
+use actix_web::web::{self, Data};
+use actix_web::App;
+
+use chrono::Utc;
+
+use futures::Future;
+
+use std::time::{Duration, Instant};
+
+use tokio::timer::Delay;
+
+fn request(id: &str) -> InFlightRequest {
+    InFlightRequest {
+        request_id: id.to_string(),
+        timestamp: Utc::now(),
+        method: "GET".to_string(),
+        path: "/sleep/10000".to_string(),
+        route: "/sleep/{millis}".to_string(),
+        kind: SlumberKind::Fixed,
+        time_millis: 0,
+        elapsed_millis: 0,
+        remaining_millis: 0,
+    }
+}
+
+fn fault(status: u16) -> FaultSettings {
+    FaultSettings {
+        probability: 1.0,
+        status,
+        body: None,
+    }
+}
+
+fn ids(requests: &[InFlightRequest]) -> Vec<&str> {
+    requests
+        .iter()
+        .map(|request| request.request_id.as_str())
+        .collect()
+}
+
+#[test]
+fn test_list() {
+    let inflight = InFlightRequests::new();
+
+    let first = inflight.sleep(request("a"), Duration::from_secs(10));
+    let second = inflight.sleep(request("b"), Duration::from_secs(5));
+
+    let requests = inflight.list();
+
+    assert_eq!(2, requests.len());
+    assert_eq!("a", requests[0].request_id);
+    assert_eq!(10000, requests[0].time_millis);
+    assert!(requests[0].remaining_millis <= 10000);
+    assert_eq!("b", requests[1].request_id);
+
+    // test that a sleep which is dropped, as happens when a client goes away, is no longer listed
+    drop(first);
+
+    assert_eq!(vec!["b"], ids(&inflight.list()));
+
+    drop(second);
+
+    assert!(inflight.list().is_empty());
+}
+
+#[test]
+fn test_release() {
+    let inflight = InFlightRequests::new();
+
+    let sleep = inflight.sleep(request("a"), Duration::from_secs(10));
+    let other = inflight.sleep(request("b"), Duration::from_secs(10));
+
+    assert_eq!(vec!["a"], ids(&inflight.release("a")));
+    assert_eq!(vec!["b"], ids(&inflight.list()));
+    assert!(inflight.release("missing").is_empty());
+
+    let started = Instant::now();
+
+    assert_eq!(Wake::Released, test::block_on(sleep).unwrap());
+    assert!(started.elapsed() < Duration::from_secs(5));
+
+    drop(other);
+}
+
+#[test]
+fn test_extend() {
+    let inflight = InFlightRequests::new();
+
+    let started = Instant::now();
+    let sleep = inflight.sleep(request("a"), Duration::from_millis(10));
+
+    let requests = inflight.extend("a", Duration::from_millis(90), Duration::from_secs(1));
+
+    assert_eq!(1, requests.len());
+    assert_eq!(100, requests[0].time_millis);
+
+    // test that the total sleep is capped at the limit, and that a longer sleep isn't cut short
+    let requests = inflight.extend("a", Duration::from_millis(90), Duration::from_millis(150));
+
+    assert_eq!(150, requests[0].time_millis);
+
+    let requests = inflight.extend("a", Duration::from_millis(90), Duration::from_millis(120));
+
+    assert_eq!(150, requests[0].time_millis);
+
+    assert_eq!(Wake::Elapsed, test::block_on(sleep).unwrap());
+    assert!(started.elapsed() >= Duration::from_millis(150));
+    assert!(inflight.list().is_empty());
+
+    // test that an extension too long to represent is capped rather than overflowing
+    let sleep = inflight.sleep(request("b"), Duration::from_millis(10));
+    let requests = inflight.extend("b", Duration::MAX, Duration::MAX);
+
+    assert!(requests[0].time_millis > 1000 * 60 * 60 * 24);
+
+    assert_eq!(1, inflight.release("b").len());
+    assert_eq!(Wake::Released, test::block_on(sleep).unwrap());
+}
+
+#[test]
+fn test_abort() {
+    let inflight = InFlightRequests::new();
+
+    let sleep = inflight.sleep(request("a"), Duration::from_secs(10));
+
+    assert_eq!(1, inflight.abort("a", fault(504)).len());
+    assert_eq!(Wake::Aborted(fault(504)), test::block_on(sleep).unwrap());
+}
+
+#[test]
+fn test_unregistered() {
+    let started = Instant::now();
+
+    assert_eq!(
+        Wake::Elapsed,
+        test::block_on(Sleep::new(Duration::from_millis(10))).unwrap()
+    );
+    assert!(started.elapsed() >= Duration::from_millis(10));
+}
+
+#[test]
+fn test_admin() {
+    let inflight = InFlightRequests::new();
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(Data::new(SharedConfig::new(CliArgs {
+                admin_insecure: true,
+                ..CliArgs::default()
+            })))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .register_data(Data::new(inflight.clone()))
+            .configure(admin::configure)
+            .route("/sleep/{millis}", web::to_async(path::specific)),
+    );
+
+    // test that a sleeping request is listed, and responds with the fault it was aborted with
+    let req = TestRequest::with_header("X-Request-Id", "slow")
+        .uri("/sleep/10000")
+        .to_request();
+
+    let abort = Delay::new(Instant::now() + Duration::from_millis(50))
+        .map_err(|_| ())
+        .map(|_| {
+            let listed = inflight.list();
+            let aborted = inflight.abort("slow", fault(504));
+
+            (listed, aborted)
+        });
+
+    let started = Instant::now();
+    let (res, (listed, aborted)) = test::block_fn(|| app.call(req).map_err(|_| ()).join(abort))
+        .ok()
+        .unwrap();
+
+    assert!(started.elapsed() < Duration::from_secs(5));
+    assert_eq!(vec!["slow"], ids(&listed));
+    assert_eq!("/sleep/10000", listed[0].path);
+    assert_eq!(1, aborted.len());
+    assert_eq!(504, res.status().as_u16());
+    assert_eq!("true", res.headers().get("X-Slumber-Fault").unwrap());
+
+    // test that requests can be listed and released through the admin API
+    let sleep = inflight.sleep(request("a"), Duration::from_secs(10));
+
+    let req = TestRequest::with_uri("/_inflight").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!("a", json["requests"][0]["request_id"]);
+    assert_eq!("fixed", json["requests"][0]["type"]);
+
+    let req = TestRequest::post().uri("/_inflight/a/release").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(200, res.status().as_u16());
+    assert_eq!(Wake::Released, test::block_on(sleep).unwrap());
+
+    // test that unknown requests and invalid faults are rejected
+    let req = TestRequest::post().uri("/_inflight/a/release").to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(404, res.status().as_u16());
+
+    let req = TestRequest::post()
+        .uri("/_inflight/a/abort")
+        .set_json(&serde_json::json!({"status": 42}))
+        .to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(400, res.status().as_u16());
+
+    let req = TestRequest::post()
+        .uri("/_inflight/a/extend")
+        .set_json(&serde_json::json!({"millis": 100}))
+        .to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(404, res.status().as_u16());
+
+    // test that an extension longer than the maximum sleep time is rejected
+    let req = TestRequest::post()
+        .uri("/_inflight/a/extend")
+        .set_json(&serde_json::json!({"millis": u64::MAX}))
+        .to_request();
+    let res = test::call_service(&mut app, req);
+
+    assert_eq!(400, res.status().as_u16());
+}
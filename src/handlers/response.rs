@@ -1,52 +1,134 @@
 use serde::Serialize;
 
-use std::time::Duration;
+use sha2::{Digest, Sha256};
 
-use super::SlumberKind;
+use std::collections::BTreeMap;
+use std::time::Duration;
 
-use uuid::Uuid;
+use super::{ParamSource, SlumberKind};
 
 #[derive(Serialize)]
 pub struct SlumberResponse {
     #[serde(rename = "slumber")]
     pub duration: SlumberDuration,
     #[serde(rename = "request_id")]
-    pub request_id: Uuid,
+    pub request_id: String,
+    #[serde(rename = "profile", skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    #[serde(rename = "rule", skip_serializing_if = "Option::is_none")]
+    pub rule: Option<String>,
+    #[serde(rename = "gate", skip_serializing_if = "Option::is_none")]
+    pub gate: Option<SlumberGate>,
+    #[serde(rename = "dry_run", skip_serializing_if = "std::ops::Not::not")]
+    pub dry_run: bool,
+    #[serde(rename = "fault", skip_serializing_if = "std::ops::Not::not")]
+    pub fault: bool,
+    #[serde(rename = "explain", skip_serializing_if = "Option::is_none")]
+    pub explain: Option<SlumberExplain>,
+    #[serde(rename = "echo", skip_serializing_if = "Option::is_none")]
+    pub echo: Option<SlumberEcho>,
 }
 
 impl SlumberResponse {
     pub fn builder(
-        request_id: &Uuid,
+        request_id: &str,
         kind: SlumberKind,
         duration: &Duration,
     ) -> SlumberResponseBuilder {
         SlumberResponseBuilder {
-            request_id: request_id.clone(),
+            request_id: request_id.to_string(),
             kind,
-            duration: duration.clone(),
+            duration: *duration,
             min: None,
             max: None,
+            scaled: None,
+            profile: None,
+            rule: None,
+            gate: None,
+            dry_run: false,
+            fault: false,
+            explain: None,
+            echo: None,
         }
     }
 }
 
 pub struct SlumberResponseBuilder {
     kind: SlumberKind,
-    request_id: Uuid,
+    request_id: String,
     duration: Duration,
     min: Option<Duration>,
     max: Option<Duration>,
+    scaled: Option<(f64, Duration)>,
+    profile: Option<String>,
+    rule: Option<String>,
+    gate: Option<SlumberGate>,
+    dry_run: bool,
+    fault: bool,
+    explain: Option<SlumberExplain>,
+    echo: Option<SlumberEcho>,
 }
 
 impl SlumberResponseBuilder {
     pub fn min(mut self, duration: &Duration) -> Self {
-        self.min = Some(duration.clone());
+        self.min = Some(*duration);
 
         self
     }
 
     pub fn max(mut self, duration: &Duration) -> Self {
-        self.max = Some(duration.clone());
+        self.max = Some(*duration);
+
+        self
+    }
+
+    pub fn scaled(mut self, scale: f64, duration: &Duration) -> Self {
+        self.scaled = Some((scale, *duration));
+
+        self
+    }
+
+    pub fn profile(mut self, name: &str) -> Self {
+        self.profile = Some(name.to_string());
+
+        self
+    }
+
+    pub fn rule(mut self, name: &str) -> Self {
+        self.rule = Some(name.to_string());
+
+        self
+    }
+
+    pub fn gate(mut self, name: &str, opened: bool) -> Self {
+        self.gate = Some(SlumberGate {
+            name: name.to_string(),
+            opened,
+        });
+
+        self
+    }
+
+    pub fn dry_run(mut self) -> Self {
+        self.dry_run = true;
+
+        self
+    }
+
+    pub fn fault(mut self) -> Self {
+        self.fault = true;
+
+        self
+    }
+
+    pub fn explain(mut self, explain: SlumberExplain) -> Self {
+        self.explain = Some(explain);
+
+        self
+    }
+
+    pub fn echo(mut self, echo: SlumberEcho) -> Self {
+        self.echo = Some(echo);
 
         self
     }
@@ -56,31 +138,180 @@ impl SlumberResponseBuilder {
             request_id: self.request_id,
             duration: SlumberDuration {
                 kind: self.kind,
-                duration_millis: self.duration.as_millis(),
+                duration_millis: self.duration.as_millis() as u64,
                 duration_pretty: format!("{:?}", self.duration),
                 max_pretty: self.max.as_ref().map(|d| format!("{:?}", d)),
-                max_millis: self.max.as_ref().map(|d| d.as_millis()),
+                max_millis: self.max.as_ref().map(|d| d.as_millis() as u64),
                 min_pretty: self.min.as_ref().map(|d| format!("{:?}", d)),
-                min_millis: self.min.as_ref().map(|d| d.as_millis()),
+                min_millis: self.min.as_ref().map(|d| d.as_millis() as u64),
+                scale: self.scaled.as_ref().map(|(s, _)| *s),
+                scaled_pretty: self.scaled.as_ref().map(|(_, d)| format!("{:?}", d)),
+                scaled_millis: self.scaled.as_ref().map(|(_, d)| d.as_millis() as u64),
             },
+            profile: self.profile,
+            rule: self.rule,
+            gate: self.gate,
+            dry_run: self.dry_run,
+            fault: self.fault,
+            explain: self.explain,
+            echo: self.echo,
         }
     }
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Debug, Serialize)]
 pub struct SlumberDuration {
     #[serde(rename = "type")]
     pub kind: SlumberKind,
     #[serde(rename = "time_millis")]
-    pub duration_millis: u128,
+    pub duration_millis: u64,
     #[serde(rename = "time")]
     pub duration_pretty: String,
     #[serde(rename = "max_time", skip_serializing_if = "Option::is_none")]
     pub max_pretty: Option<String>,
     #[serde(rename = "max_time_millis", skip_serializing_if = "Option::is_none")]
-    pub max_millis: Option<u128>,
+    pub max_millis: Option<u64>,
     #[serde(rename = "min_time_millis", skip_serializing_if = "Option::is_none")]
-    pub min_millis: Option<u128>,
+    pub min_millis: Option<u64>,
     #[serde(rename = "min_time", skip_serializing_if = "Option::is_none")]
     pub min_pretty: Option<String>,
+    #[serde(rename = "time_scale", skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    #[serde(rename = "scaled_time", skip_serializing_if = "Option::is_none")]
+    pub scaled_pretty: Option<String>,
+    #[serde(rename = "scaled_time_millis", skip_serializing_if = "Option::is_none")]
+    pub scaled_millis: Option<u64>,
+}
+
+/// The gate a request waited at, and whether it opened before the request timed out.
+#[derive(Serialize)]
+pub struct SlumberGate {
+    pub name: String,
+    pub opened: bool,
+}
+
+/// A trace of where each sleep parameter was resolved from and how it was bounded.
+#[derive(Serialize)]
+pub struct SlumberExplain {
+    #[serde(rename = "type")]
+    pub kind: KindTrace,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub profile: Option<ProfileTrace>,
+    pub time: DurationTrace,
+    #[serde(rename = "min_time")]
+    pub min: DurationTrace,
+    #[serde(rename = "max_time")]
+    pub max: DurationTrace,
+}
+
+#[derive(Serialize)]
+pub struct KindTrace {
+    pub source: ParamSource,
+    pub raw: String,
+}
+
+#[derive(Serialize)]
+pub struct ProfileTrace {
+    pub source: ParamSource,
+    pub raw: String,
+}
+
+#[derive(Serialize)]
+pub struct DurationTrace {
+    pub source: ParamSource,
+    pub raw: String,
+    pub used: bool,
+    pub clamped: bool,
+    #[serde(rename = "clamped_to", skip_serializing_if = "Option::is_none")]
+    pub clamped_pretty: Option<String>,
+    #[serde(rename = "clamped_to_millis", skip_serializing_if = "Option::is_none")]
+    pub clamped_millis: Option<u64>,
+}
+
+impl DurationTrace {
+    /// Trace a requested duration, noting whether the resolved duration differs from what was requested.
+    pub fn new(
+        source: ParamSource,
+        raw: &str,
+        requested: &Duration,
+        resolved: &Duration,
+    ) -> DurationTrace {
+        let clamped = requested != resolved;
+
+        DurationTrace {
+            source,
+            raw: raw.to_string(),
+            used: true,
+            clamped,
+            clamped_pretty: if clamped {
+                Some(format!("{:?}", resolved))
+            } else {
+                None
+            },
+            clamped_millis: if clamped {
+                Some(resolved.as_millis() as u64)
+            } else {
+                None
+            },
+        }
+    }
+    /// Trace a duration which was resolved but played no part in the sleep, such as the time in random mode.
+    pub fn unused(source: ParamSource, raw: &str) -> DurationTrace {
+        DurationTrace {
+            source,
+            raw: raw.to_string(),
+            used: false,
+            clamped: false,
+            clamped_pretty: None,
+            clamped_millis: None,
+        }
+    }
+}
+
+/// A description of the request which was received, with sensitive header values redacted.
+#[derive(Clone, Debug, Serialize)]
+pub struct SlumberEcho {
+    pub method: String,
+    pub path: String,
+    pub query: BTreeMap<String, String>,
+    pub headers: BTreeMap<String, String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub client_ip: Option<String>,
+    pub http_version: String,
+    pub body: BodyDigest,
+}
+
+impl SlumberEcho {
+    /// Record the body of the request, which is only known once it has been read.
+    pub fn with_body(mut self, body: &[u8]) -> Self {
+        self.body = BodyDigest::new(body);
+
+        self
+    }
+}
+
+/// The length and SHA-256 digest of a request body.
+#[derive(Clone, Debug, Default, PartialEq, Serialize)]
+pub struct BodyDigest {
+    pub length: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl BodyDigest {
+    pub fn new(body: &[u8]) -> BodyDigest {
+        if body.is_empty() {
+            return BodyDigest::default();
+        }
+
+        let sha256 = Sha256::digest(body)
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect();
+
+        BodyDigest {
+            length: body.len(),
+            sha256: Some(sha256),
+        }
+    }
 }
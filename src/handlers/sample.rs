@@ -0,0 +1,158 @@
+#[cfg(test)]
+mod tests;
+
+use serde::{Deserialize, Serialize};
+
+use std::time::Duration;
+
+use super::SlumberKind;
+
+/// The number of samples drawn when a count is not specified.
+const DEFAULT_SAMPLE_COUNT: usize = 100;
+
+/// The maximum number of samples which may be drawn in a single request.
+const MAXIMUM_SAMPLE_COUNT: usize = 10_000;
+
+/// The number of histogram buckets used when a bucket count is not specified.
+const DEFAULT_BUCKET_COUNT: usize = 10;
+
+/// The maximum number of histogram buckets which may be requested.
+const MAXIMUM_BUCKET_COUNT: usize = 100;
+
+#[derive(Default, Deserialize)]
+pub struct SampleQueryParams {
+    #[serde(rename = "n")]
+    pub count: Option<usize>,
+    pub buckets: Option<usize>,
+}
+
+impl SampleQueryParams {
+    /// The number of samples to draw, bounded to a sane maximum.
+    pub fn count(&self) -> usize {
+        self.count
+            .unwrap_or(DEFAULT_SAMPLE_COUNT)
+            .clamp(1, MAXIMUM_SAMPLE_COUNT)
+    }
+
+    /// The number of histogram buckets to use, bounded to a sane maximum.
+    pub fn buckets(&self) -> usize {
+        self.buckets
+            .unwrap_or(DEFAULT_BUCKET_COUNT)
+            .clamp(1, MAXIMUM_BUCKET_COUNT)
+    }
+}
+
+/// The response type returned by sample requests.
+#[derive(Serialize)]
+pub struct SampleResponse {
+    #[serde(rename = "type")]
+    pub kind: SlumberKind,
+    pub count: usize,
+    pub samples_millis: Vec<u64>,
+    pub percentiles: SamplePercentiles,
+    pub histogram: Vec<HistogramBucket>,
+}
+
+impl SampleResponse {
+    /// Summarize a set of sampled durations into percentiles and a histogram.
+    pub fn new(kind: SlumberKind, mut samples: Vec<Duration>, buckets: usize) -> Self {
+        samples.sort();
+
+        Self {
+            kind,
+            count: samples.len(),
+            samples_millis: samples.iter().map(|d| d.as_millis() as u64).collect(),
+            percentiles: SamplePercentiles::new(&samples),
+            histogram: HistogramBucket::histogram(&samples, buckets),
+        }
+    }
+}
+
+#[derive(Serialize)]
+pub struct SamplePercentiles {
+    pub min_millis: u64,
+    pub p50_millis: u64,
+    pub p90_millis: u64,
+    pub p95_millis: u64,
+    pub p99_millis: u64,
+    pub max_millis: u64,
+    pub mean_millis: u64,
+}
+
+impl SamplePercentiles {
+    /// Calculate percentiles from a sorted set of samples.
+    fn new(sorted: &[Duration]) -> Self {
+        let total: u128 = sorted.iter().map(|d| d.as_nanos()).sum();
+        let mean = total.checked_div(sorted.len() as u128).unwrap_or(0);
+
+        Self {
+            min_millis: percentile(sorted, 0.0),
+            p50_millis: percentile(sorted, 50.0),
+            p90_millis: percentile(sorted, 90.0),
+            p95_millis: percentile(sorted, 95.0),
+            p99_millis: percentile(sorted, 99.0),
+            max_millis: percentile(sorted, 100.0),
+            mean_millis: (mean / 1_000_000) as u64,
+        }
+    }
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+pub struct HistogramBucket {
+    pub lower_millis: u64,
+    pub upper_millis: u64,
+    pub count: usize,
+}
+
+impl HistogramBucket {
+    /// Divide a sorted set of samples into equal-width buckets spanning the observed range.
+    fn histogram(sorted: &[Duration], buckets: usize) -> Vec<HistogramBucket> {
+        let (lower, upper) = match (sorted.first(), sorted.last()) {
+            (Some(lower), Some(upper)) => (lower.as_nanos(), upper.as_nanos()),
+            _ => return Vec::new(),
+        };
+
+        // a single bucket suffices when every sample is identical
+        let buckets = if lower == upper { 1 } else { buckets.max(1) };
+        let width = ((upper - lower) / buckets as u128).max(1);
+
+        let mut counts = vec![0; buckets];
+
+        for sample in sorted {
+            let index = ((sample.as_nanos() - lower) / width) as usize;
+
+            // the maximum sample belongs in the last bucket
+            counts[index.min(buckets - 1)] += 1;
+        }
+
+        counts
+            .into_iter()
+            .enumerate()
+            .map(|(i, count)| {
+                let bucket_lower = lower + width * i as u128;
+                let bucket_upper = if i == buckets - 1 {
+                    upper
+                } else {
+                    bucket_lower + width
+                };
+
+                HistogramBucket {
+                    lower_millis: (bucket_lower / 1_000_000) as u64,
+                    upper_millis: (bucket_upper / 1_000_000) as u64,
+                    count,
+                }
+            })
+            .collect()
+    }
+}
+
+/// Select a nearest-rank percentile from a sorted set of samples, in milliseconds.
+fn percentile(sorted: &[Duration], pct: f64) -> u64 {
+    if sorted.is_empty() {
+        return 0;
+    }
+
+    let rank = ((pct / 100.0) * sorted.len() as f64).ceil() as usize;
+
+    sorted[rank.clamp(1, sorted.len()) - 1].as_millis() as u64
+}
@@ -0,0 +1,92 @@
+use super::percentile;
+use super::HistogramBucket;
+use super::SampleQueryParams;
+use super::SampleResponse;
+
+use crate::handlers::SlumberKind;
+
+use std::time::Duration;
+
+fn millis(values: &[u64]) -> Vec<Duration> {
+    values.iter().map(|v| Duration::from_millis(*v)).collect()
+}
+
+#[test]
+fn test_sample_query_bounds() {
+    let mut query: SampleQueryParams = Default::default();
+
+    // test defaults
+    assert_eq!(100, query.count());
+    assert_eq!(10, query.buckets());
+
+    // test lower bounds
+    query.count = Some(0);
+    query.buckets = Some(0);
+
+    assert_eq!(1, query.count());
+    assert_eq!(1, query.buckets());
+
+    // test upper bounds
+    query.count = Some(1_000_000);
+    query.buckets = Some(1_000_000);
+
+    assert_eq!(10_000, query.count());
+    assert_eq!(100, query.buckets());
+}
+
+#[test]
+fn test_percentile() {
+    let samples = millis(&[10, 20, 30, 40, 50, 60, 70, 80, 90, 100]);
+
+    assert_eq!(10, percentile(&samples, 0.0));
+    assert_eq!(50, percentile(&samples, 50.0));
+    assert_eq!(90, percentile(&samples, 90.0));
+    assert_eq!(100, percentile(&samples, 99.0));
+    assert_eq!(100, percentile(&samples, 100.0));
+
+    // test empty samples
+    assert_eq!(0, percentile(&[], 50.0));
+}
+
+#[test]
+fn test_histogram() {
+    let samples = millis(&[0, 10, 20, 50, 99, 100]);
+
+    assert_eq!(
+        vec![
+            HistogramBucket {
+                lower_millis: 0,
+                upper_millis: 50,
+                count: 3,
+            },
+            HistogramBucket {
+                lower_millis: 50,
+                upper_millis: 100,
+                count: 3,
+            },
+        ],
+        HistogramBucket::histogram(&samples, 2)
+    );
+
+    // test identical samples collapse to a single bucket
+    let samples = millis(&[500, 500, 500]);
+
+    assert_eq!(
+        vec![HistogramBucket {
+            lower_millis: 500,
+            upper_millis: 500,
+            count: 3,
+        }],
+        HistogramBucket::histogram(&samples, 10)
+    );
+}
+
+#[test]
+fn test_sample_response() {
+    let response = SampleResponse::new(SlumberKind::Random, millis(&[300, 100, 200]), 10);
+
+    assert_eq!(3, response.count);
+    assert_eq!(vec![100, 200, 300], response.samples_millis);
+    assert_eq!(200, response.percentiles.mean_millis);
+    assert_eq!(3, response.histogram.iter().map(|b| b.count).sum::<usize>());
+}
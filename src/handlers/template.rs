@@ -0,0 +1,164 @@
+#[cfg(test)]
+mod tests;
+
+use chrono::{DateTime, SecondsFormat, Utc};
+
+use handlebars::{
+    Context, Handlebars, Helper, HelperResult, Output, RenderContext, RenderError, Template,
+};
+
+use parking_lot::RwLock;
+
+use rand::distributions::Uniform;
+use rand::{thread_rng, Rng};
+
+use serde::Serialize;
+use serde_json::Value;
+
+use sha2::{Digest, Sha256};
+
+use std::collections::BTreeMap;
+use std::sync::OnceLock;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The largest request body which is read to make it available to templates.
+pub const MAX_BODY_SIZE: usize = 1024 * 1024;
+
+/// The request as it is made available to templates.
+#[derive(Clone, Debug, Default, Serialize)]
+pub struct TemplateRequest {
+    pub method: String,
+    pub path: String,
+    /// Named captures from the rule's path regex.
+    pub params: BTreeMap<String, String>,
+    pub query: BTreeMap<String, String>,
+    /// Header values by lowercase name, with repeated headers joined by commas.
+    pub headers: BTreeMap<String, String>,
+    pub body: String,
+    /// The request body, if it is valid JSON.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub json: Option<Value>,
+}
+
+impl TemplateRequest {
+    /// Attach the request body, parsing it as JSON if possible.
+    pub fn with_body(mut self, body: &[u8]) -> Self {
+        self.body = String::from_utf8_lossy(body).into_owned();
+        self.json = serde_json::from_slice(body).ok();
+
+        self
+    }
+}
+
+/// The shared template registry, with helpers registered and HTML escaping disabled.
+fn registry() -> &'static RwLock<Handlebars<'static>> {
+    static REGISTRY: OnceLock<RwLock<Handlebars<'static>>> = OnceLock::new();
+
+    REGISTRY.get_or_init(|| {
+        let mut registry = Handlebars::new();
+
+        // templates produce JSON as often as HTML, so values are inserted verbatim
+        registry.register_escape_fn(handlebars::no_escape);
+        registry.register_helper("now", Box::new(now));
+        registry.register_helper("random", Box::new(random));
+
+        RwLock::new(registry)
+    })
+}
+
+/// Check that a template compiles.
+pub fn validate(source: &str) -> Result<(), String> {
+    Template::compile(source)
+        .map(|_| ())
+        .map_err(|e| format!("invalid template: {}", e))
+}
+
+/// Compile a template once and register it, returning the name to render it by.
+///
+/// Templates are named after a digest of their source, so reloading an unchanged template doesn't compile it again, and
+/// requests still using the configuration from before a reload can render the templates it had.
+pub fn register(source: &str) -> Result<String, String> {
+    let name: String = Sha256::digest(source.as_bytes())
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect();
+
+    if !registry().read().has_template(&name) {
+        registry()
+            .write()
+            .register_template_string(&name, source)
+            .map_err(|e| format!("invalid template: {}", e))?;
+    }
+
+    Ok(name)
+}
+
+/// Render a registered template against the given data.
+pub fn render<T: Serialize>(name: &str, data: &T) -> Result<String, String> {
+    registry()
+        .read()
+        .render(name, data)
+        .map_err(|e| e.to_string())
+}
+
+/// `{{now}}` renders the current time in RFC 3339 format, `{{now "unix"}}` in seconds since the epoch, and
+/// `{{now "millis"}}` in milliseconds since the epoch.
+fn now(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let now = SystemTime::now();
+    let elapsed = now.duration_since(UNIX_EPOCH).unwrap_or_default();
+
+    let rendered = match h.param(0).and_then(|p| p.value().as_str()) {
+        Some("unix") => elapsed.as_secs().to_string(),
+        Some("millis") => elapsed.as_millis().to_string(),
+        Some(format) => {
+            return Err(RenderError::new(format!(
+                "unknown time format '{}' for now, expected unix or millis",
+                format
+            )))
+        }
+        None => rfc3339(now),
+    };
+
+    out.write(&rendered)?;
+
+    Ok(())
+}
+
+/// `{{random}}` renders a random number between 0 and 1, and `{{random min max}}` a random integer between `min` and
+/// `max` inclusive.
+fn random(
+    h: &Helper,
+    _: &Handlebars,
+    _: &Context,
+    _: &mut RenderContext,
+    out: &mut dyn Output,
+) -> HelperResult {
+    let bound = |index: usize| h.param(index).and_then(|p| p.value().as_i64());
+
+    let rendered = match (bound(0), bound(1)) {
+        (Some(min), Some(max)) if min <= max => thread_rng()
+            .sample(Uniform::new_inclusive(min, max))
+            .to_string(),
+        (None, None) if h.params().is_empty() => thread_rng().gen::<f64>().to_string(),
+        _ => {
+            return Err(RenderError::new(
+                "random expects no arguments or an integer minimum and maximum",
+            ))
+        }
+    };
+
+    out.write(&rendered)?;
+
+    Ok(())
+}
+
+/// Format a time as an RFC 3339 timestamp in UTC, to the millisecond.
+fn rfc3339(time: SystemTime) -> String {
+    DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Millis, true)
+}
@@ -0,0 +1,72 @@
+use super::register;
+use super::render;
+use super::rfc3339;
+use super::validate;
+use super::TemplateRequest;
+
+use serde_json::json;
+
+use std::time::{Duration, UNIX_EPOCH};
+
+#[test]
+fn test_rfc3339() {
+    let at = |millis| rfc3339(UNIX_EPOCH + Duration::from_millis(millis));
+
+    assert_eq!("1970-01-01T00:00:00.000Z", at(0));
+    assert_eq!("2000-02-29T23:59:59.999Z", at(951_868_799_999));
+    assert_eq!("2024-12-31T12:00:00.000Z", at(1_735_646_400_000));
+}
+
+#[test]
+fn test_render() {
+    let request = TemplateRequest {
+        method: "POST".to_string(),
+        path: "/users/42".to_string(),
+        ..Default::default()
+    }
+    .with_body(br#"{"name": "<b>ada</b>"}"#);
+
+    let data = json!({ "request": request, "request_id": "abc" });
+    let render = |source: &str| render(&register(source).unwrap(), &data);
+
+    // test that request values are available and not escaped
+    assert_eq!(
+        "POST /users/42 <b>ada</b> abc",
+        render("{{request.method}} {{request.path}} {{request.json.name}} {{request_id}}").unwrap()
+    );
+
+    // test helpers
+    let value: i64 = render("{{random 1 6}}").unwrap().parse().unwrap();
+    assert!((1..=6).contains(&value));
+
+    let value: f64 = render("{{random}}").unwrap().parse().unwrap();
+    assert!((0.0..1.0).contains(&value));
+
+    assert_eq!(24, render("{{now}}").unwrap().len());
+    assert!(render("{{now \"millis\"}}").unwrap().parse::<u64>().is_ok());
+    assert!(render("{{now \"fortnights\"}}").is_err());
+    assert!(render("{{random 6 1}}").is_err());
+
+    // test that the full range of integers can be drawn from without overflowing
+    let max = format!("{{{{random {} {}}}}}", i64::MAX, i64::MAX);
+    assert_eq!(i64::MAX.to_string(), render(&max).unwrap());
+
+    let min = format!("{{{{random {} {}}}}}", i64::MIN, i64::MIN + 1);
+    assert!(render(&min).unwrap().parse::<i64>().unwrap() <= i64::MIN + 1);
+}
+
+#[test]
+fn test_register() {
+    // test that a template is registered once, under a name derived from its source
+    let name = register("{{request.path}}").unwrap();
+
+    assert_eq!(name, register("{{request.path}}").unwrap());
+    assert_ne!(name, register("{{request.method}}").unwrap());
+    assert!(register("{{#if request.path}}").is_err());
+}
+
+#[test]
+fn test_validate() {
+    assert!(validate("{{request.path}}").is_ok());
+    assert!(validate("{{#if request.path}}").is_err());
+}
@@ -1,19 +1,44 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use super::default;
+use super::echo_request;
 use super::extract_duration;
+use super::extract_flag;
+use super::extract_profile;
+use super::extract_request_id;
 use super::extract_sleep_kind;
 use super::extract_sleep_max_time;
 use super::extract_sleep_min_time;
 use super::extract_sleep_time;
+use super::path;
+use super::ParamSource;
 use super::SleepBounds;
 use super::SleepQueryParams;
+use super::SlumberConfig;
 use super::SlumberKind;
+use super::SlumberParams;
+use super::Sourced;
+use super::TraceContext;
+use super::ECHO_HEADER;
+use super::EXPLAIN_HEADER;
 use super::MAXIMUM_SLEEP_TIME_MS_HEADER;
 use super::MINIMUM_SLEEP_TIME_MS_HEADER;
 use super::SLEEP_KIND_HEADER;
 use super::SLEEP_TIME_MS_HEADER;
 
-use crate::config::CliArgs;
+use crate::config::{
+    CliArgs, ConfigFile, ConfigFormat, FaultSettings, Profile, SharedConfig, TimeScale,
+};
+use crate::metrics::Metrics;
+
+use actix_web::test;
+use actix_web::web::{self, Data};
+use actix_web::App;
+
+use tracing::Span;
 
 use actix_web::http::{HeaderMap, HeaderName, HeaderValue};
+use actix_web::test::TestRequest;
 use std::time::Duration;
 
 #[test]
@@ -26,18 +51,18 @@ fn test_extract_sleep_min_time() {
 
     // test fallback to cli args
     assert_eq!(
-        Duration::from_millis(1000),
+        Sourced::new(Duration::from_millis(1000), ParamSource::Cli, "1000"),
         extract_sleep_min_time(&headers, &query, &args)
     );
 
     // test headers
     headers.insert(
-        HeaderName::from_bytes(&MINIMUM_SLEEP_TIME_MS_HEADER.to_lowercase().as_bytes()).unwrap(),
+        HeaderName::from_bytes(MINIMUM_SLEEP_TIME_MS_HEADER.to_lowercase().as_bytes()).unwrap(),
         HeaderValue::from_str("1500").unwrap(),
     );
 
     assert_eq!(
-        Duration::from_millis(1500),
+        Sourced::new(Duration::from_millis(1500), ParamSource::Header, "1500"),
         extract_sleep_min_time(&headers, &query, &args)
     );
 
@@ -45,7 +70,7 @@ fn test_extract_sleep_min_time() {
     query.min = Some(1750);
 
     assert_eq!(
-        Duration::from_millis(1750),
+        Sourced::new(Duration::from_millis(1750), ParamSource::Query, "1750"),
         extract_sleep_min_time(&headers, &query, &args)
     );
 }
@@ -60,18 +85,18 @@ fn test_extract_sleep_max_time() {
 
     // test fallback to cli args
     assert_eq!(
-        Duration::from_millis(4000),
+        Sourced::new(Duration::from_millis(4000), ParamSource::Cli, "4000"),
         extract_sleep_max_time(&headers, &query, &args)
     );
 
     // test headers
     headers.insert(
-        HeaderName::from_bytes(&MAXIMUM_SLEEP_TIME_MS_HEADER.to_lowercase().as_bytes()).unwrap(),
+        HeaderName::from_bytes(MAXIMUM_SLEEP_TIME_MS_HEADER.to_lowercase().as_bytes()).unwrap(),
         HeaderValue::from_static("3500"),
     );
 
     assert_eq!(
-        Duration::from_millis(3500),
+        Sourced::new(Duration::from_millis(3500), ParamSource::Header, "3500"),
         extract_sleep_max_time(&headers, &query, &args)
     );
 
@@ -79,7 +104,7 @@ fn test_extract_sleep_max_time() {
     query.max = Some(3000);
 
     assert_eq!(
-        Duration::from_millis(3000),
+        Sourced::new(Duration::from_millis(3000), ParamSource::Query, "3000"),
         extract_sleep_max_time(&headers, &query, &args)
     );
 }
@@ -94,18 +119,18 @@ fn test_extract_sleep_time() {
 
     // test fallback to cli args
     assert_eq!(
-        Duration::from_millis(3500),
+        Sourced::new(Duration::from_millis(3500), ParamSource::Cli, "3500"),
         extract_sleep_time(&headers, &query, &args)
     );
 
     // test headers
     headers.insert(
-        HeaderName::from_bytes(&SLEEP_TIME_MS_HEADER.to_lowercase().as_bytes()).unwrap(),
+        HeaderName::from_bytes(SLEEP_TIME_MS_HEADER.to_lowercase().as_bytes()).unwrap(),
         HeaderValue::from_static("3000"),
     );
 
     assert_eq!(
-        Duration::from_millis(3000),
+        Sourced::new(Duration::from_millis(3000), ParamSource::Header, "3000"),
         extract_sleep_time(&headers, &query, &args)
     );
 
@@ -113,7 +138,7 @@ fn test_extract_sleep_time() {
     query.duration = Some(2500);
 
     assert_eq!(
-        Duration::from_millis(2500),
+        Sourced::new(Duration::from_millis(2500), ParamSource::Query, "2500"),
         extract_sleep_time(&headers, &query, &args)
     );
 }
@@ -129,7 +154,7 @@ fn test_extract() {
 
     // test fallback to cli args
     assert_eq!(
-        Duration::from_millis(1000),
+        Sourced::new(Duration::from_millis(1000), ParamSource::Cli, "1000"),
         extract_duration(
             &headers,
             MINIMUM_SLEEP_TIME_MS_HEADER,
@@ -145,7 +170,7 @@ fn test_extract() {
     );
 
     assert_eq!(
-        Duration::from_millis(2000),
+        Sourced::new(Duration::from_millis(2000), ParamSource::Header, "2000"),
         extract_duration(
             &headers,
             MINIMUM_SLEEP_TIME_MS_HEADER,
@@ -158,7 +183,7 @@ fn test_extract() {
     query.min = Some(3000);
 
     assert_eq!(
-        Duration::from_millis(3000),
+        Sourced::new(Duration::from_millis(3000), ParamSource::Query, "3000"),
         extract_duration(
             &headers,
             MINIMUM_SLEEP_TIME_MS_HEADER,
@@ -178,14 +203,14 @@ fn test_extract_sleep_kind() {
     // test defaults
     assert_eq!(
         SlumberKind::Fixed,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     args.random = true;
 
     assert_eq!(
         SlumberKind::Random,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     // test headers
@@ -196,7 +221,7 @@ fn test_extract_sleep_kind() {
 
     assert_eq!(
         SlumberKind::Fixed,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     headers.insert(
@@ -206,7 +231,7 @@ fn test_extract_sleep_kind() {
 
     assert_eq!(
         SlumberKind::Random,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     headers.insert(
@@ -216,14 +241,14 @@ fn test_extract_sleep_kind() {
 
     assert_eq!(
         SlumberKind::Random,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     args.random = false;
 
     assert_eq!(
         SlumberKind::Fixed,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     // test query string
@@ -231,14 +256,14 @@ fn test_extract_sleep_kind() {
 
     assert_eq!(
         SlumberKind::Random,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 
     query.kind = Some(SlumberKind::Fixed);
 
     assert_eq!(
         SlumberKind::Fixed,
-        extract_sleep_kind(&headers, &query, &args)
+        extract_sleep_kind(&headers, &query, &args).value
     );
 }
 
@@ -320,3 +345,524 @@ fn test_sleep_bounds_duration() {
         SleepBounds::duration(&Duration::from_millis(5000), &min, &max)
     );
 }
+
+#[test]
+fn test_extract_sleep_kind_source() {
+    let mut query: SleepQueryParams = Default::default();
+    let mut headers = HeaderMap::new();
+
+    let mut args = CliArgs::default();
+    args.random = true;
+
+    // test fallback to cli args
+    assert_eq!(
+        Sourced::new(SlumberKind::Random, ParamSource::Cli, "random"),
+        extract_sleep_kind(&headers, &query, &args)
+    );
+
+    // test invalid header falls back to cli args
+    headers.insert(
+        HeaderName::from_bytes(SLEEP_KIND_HEADER.to_lowercase().as_bytes()).unwrap(),
+        HeaderValue::from_static("unknown"),
+    );
+
+    assert_eq!(
+        Sourced::new(SlumberKind::Random, ParamSource::Cli, "random"),
+        extract_sleep_kind(&headers, &query, &args)
+    );
+
+    // test headers
+    headers.insert(
+        HeaderName::from_bytes(SLEEP_KIND_HEADER.to_lowercase().as_bytes()).unwrap(),
+        HeaderValue::from_static("fixed"),
+    );
+
+    assert_eq!(
+        Sourced::new(SlumberKind::Fixed, ParamSource::Header, "fixed"),
+        extract_sleep_kind(&headers, &query, &args)
+    );
+
+    // test query string
+    query.kind = Some(SlumberKind::Random);
+
+    assert_eq!(
+        Sourced::new(SlumberKind::Random, ParamSource::Query, "random"),
+        extract_sleep_kind(&headers, &query, &args)
+    );
+}
+
+#[test]
+fn test_extract_flag() {
+    let mut headers = HeaderMap::new();
+
+    // test default
+    assert!(!extract_flag(&headers, EXPLAIN_HEADER, None));
+
+    // test headers
+    headers.insert(
+        HeaderName::from_bytes(EXPLAIN_HEADER.to_lowercase().as_bytes()).unwrap(),
+        HeaderValue::from_static("true"),
+    );
+
+    assert!(extract_flag(&headers, EXPLAIN_HEADER, None));
+
+    // test query string takes priority
+    assert!(!extract_flag(&headers, EXPLAIN_HEADER, Some("0")));
+
+    // test invalid query string falls back to headers
+    assert!(extract_flag(&headers, EXPLAIN_HEADER, Some("maybe")));
+}
+
+#[test]
+fn test_explain_trace() {
+    let mut query: SleepQueryParams = Default::default();
+    let headers = HeaderMap::new();
+
+    let mut args = CliArgs::default();
+    args.min_sleep_ms = 1000;
+    args.max_sleep_ms = 4000;
+
+    // test that explain is off by default
+    let slumber = SlumberParams::extract(&headers, &query, &args)
+        .unwrap()
+        .resolve(&args);
+
+    assert!(slumber.explain.is_none());
+
+    // test clamping of a fixed duration
+    query.explain = Some("1".to_string());
+    query.duration = Some(500);
+
+    let slumber = SlumberParams::extract(&headers, &query, &args)
+        .unwrap()
+        .resolve(&args);
+    let explain = slumber.explain.expect("explain trace missing");
+    let time = explain.time;
+
+    assert_eq!(ParamSource::Cli, explain.kind.source);
+    assert_eq!(ParamSource::Query, time.source);
+    assert_eq!("500", time.raw);
+    assert!(time.used);
+    assert!(time.clamped);
+    assert_eq!(Some(1000), time.clamped_millis);
+
+    // test that the bounds are still traced in fixed mode, but marked as unused
+    assert_eq!(ParamSource::Cli, explain.min.source);
+    assert_eq!("1000", explain.min.raw);
+    assert!(!explain.min.used);
+    assert!(!explain.min.clamped);
+    assert_eq!(ParamSource::Cli, explain.max.source);
+    assert!(!explain.max.used);
+
+    // test clamping of random bounds
+    query.kind = Some(SlumberKind::Random);
+    query.min = Some(2000);
+    query.max = Some(5000);
+
+    let slumber = SlumberParams::extract(&headers, &query, &args)
+        .unwrap()
+        .resolve(&args);
+    let explain = slumber.explain.expect("explain trace missing");
+    let (min, max) = (explain.min, explain.max);
+
+    assert_eq!(ParamSource::Query, explain.time.source);
+    assert!(!explain.time.used);
+    assert!(!explain.time.clamped);
+    assert!(min.used);
+    assert!(!min.clamped);
+    assert_eq!(None, min.clamped_millis);
+    assert!(max.clamped);
+    assert_eq!(Some(4000), max.clamped_millis);
+}
+
+#[test]
+fn test_time_scale() {
+    let query: SleepQueryParams = Default::default();
+    let headers = HeaderMap::new();
+
+    let mut args = CliArgs::default();
+    args.sleep_ms = 2000;
+
+    let scale = TimeScale::new(0.1);
+
+    // test that the nominal duration is kept and the scaled duration is applied after bounds
+    let slumber = SlumberParams::extract(&headers, &query, &args)
+        .unwrap()
+        .resolve(&args)
+        .scaled(scale.get());
+
+    assert_eq!(Duration::from_millis(2000), slumber.duration);
+    assert_eq!(200, slumber.scaled.as_millis());
+
+    // test rejection of nonsensical factors
+    assert!(scale.set(0.0).is_err());
+    assert!(scale.set(-1.0).is_err());
+    assert!(scale.set(f64::NAN).is_err());
+    assert!(scale.set(f64::INFINITY).is_err());
+    assert!(scale.set(1e20).is_err());
+    assert!((scale.get() - 0.1).abs() < f64::EPSILON);
+
+    // test that a duration scaled beyond what can be represented saturates rather than panicking
+    let slumber = SlumberConfig::fixed(&Duration::from_millis(20000), &args).scaled(1e20);
+
+    assert_eq!(Duration::MAX, slumber.scaled);
+
+    assert!(scale.set(10.0).is_ok());
+    assert!((scale.get() - 10.0).abs() < f64::EPSILON);
+}
+
+#[test]
+fn test_extract_profile() {
+    let mut query: SleepQueryParams = Default::default();
+    let mut headers = HeaderMap::new();
+
+    assert_eq!(None, extract_profile(&headers, &query));
+
+    // test headers
+    headers.insert(
+        HeaderName::from_static("x-slumber-profile"),
+        HeaderValue::from_static("db-slow"),
+    );
+
+    assert_eq!(
+        Some(Sourced::new(
+            "db-slow".to_string(),
+            ParamSource::Header,
+            "db-slow"
+        )),
+        extract_profile(&headers, &query)
+    );
+
+    // test query string priority
+    query.profile = Some("cdn-fast".to_string());
+
+    assert_eq!(
+        Some(Sourced::new(
+            "cdn-fast".to_string(),
+            ParamSource::Query,
+            "cdn-fast"
+        )),
+        extract_profile(&headers, &query)
+    );
+}
+
+#[test]
+fn test_profiles() {
+    let mut query: SleepQueryParams = Default::default();
+    let mut headers = HeaderMap::new();
+
+    let mut args = CliArgs::default();
+    args.profiles.insert(
+        "db-slow".to_string(),
+        Profile {
+            kind: Some(SlumberKind::Random),
+            min_ms: Some(1000),
+            max_ms: Some(3000),
+            ..Default::default()
+        },
+    );
+    args.profiles.insert(
+        "broken".to_string(),
+        Profile {
+            time_ms: Some(100),
+            fault: Some(FaultSettings {
+                probability: 1.0,
+                status: 500,
+                body: None,
+            }),
+            ..Default::default()
+        },
+    );
+
+    // test that the profile fills in parameters which weren't given
+    headers.insert(
+        HeaderName::from_static("x-slumber-profile"),
+        HeaderValue::from_static("db-slow"),
+    );
+
+    let params = SlumberParams::extract(&headers, &query, &args).unwrap();
+
+    assert_eq!(
+        Sourced::new(SlumberKind::Random, ParamSource::Profile, "random"),
+        params.kind
+    );
+    assert_eq!(
+        Sourced::new(Duration::from_millis(1000), ParamSource::Profile, "1000"),
+        params.min
+    );
+    assert_eq!(ParamSource::Cli, params.time.source);
+    assert_eq!(
+        Some(Sourced::new(
+            "db-slow".to_string(),
+            ParamSource::Header,
+            "db-slow"
+        )),
+        params.profile
+    );
+
+    // test that explicit parameters override the profile
+    query.max = Some(2000);
+    headers.insert(
+        HeaderName::from_static("x-slumber-type"),
+        HeaderValue::from_static("fixed"),
+    );
+
+    let params = SlumberParams::extract(&headers, &query, &args).unwrap();
+
+    assert_eq!(ParamSource::Header, params.kind.source);
+    assert_eq!(
+        Sourced::new(Duration::from_millis(2000), ParamSource::Query, "2000"),
+        params.max
+    );
+
+    // test that faults are injected
+    query = Default::default();
+    query.profile = Some("broken".to_string());
+
+    let slumber = SlumberParams::extract(&headers, &query, &args)
+        .unwrap()
+        .resolve(&args);
+
+    assert_eq!(Duration::from_millis(100), slumber.duration);
+    assert_eq!(Some(500), slumber.fault.map(|f| f.status));
+
+    // test that unknown profiles are rejected
+    query.profile = Some("missing".to_string());
+
+    assert!(SlumberParams::extract(&headers, &query, &args).is_err());
+}
+
+#[test]
+fn test_rule_profile() {
+    let file = ConfigFile::parse(
+        r#"
+        [profiles.cdn-fast]
+        time_ms = 20
+
+        [[rules]]
+        path = "/cdn/**"
+        profile = "cdn-fast"
+        "#,
+        ConfigFormat::Toml,
+    )
+    .unwrap();
+
+    let mut args = CliArgs::default();
+    args.profiles = file.profiles;
+    args.rules = file.rules;
+
+    let mut app = test::init_service(
+        App::new()
+            .register_data(Data::new(SharedConfig::new(args)))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .default_service(web::route().to_async(default)),
+    );
+
+    // test that a profile selected by a rule is explained as coming from the rule
+    let req = TestRequest::with_uri("/cdn/app.js?explain=1").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!("rule", json["explain"]["profile"]["source"]);
+    assert_eq!("cdn-fast", json["explain"]["profile"]["raw"]);
+    assert_eq!("profile", json["explain"]["time"]["source"]);
+
+    // test that a profile selected by the request is explained as coming from the request
+    let req = TestRequest::with_uri("/cdn/app.js?explain=1&profile=cdn-fast").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!("query", json["explain"]["profile"]["source"]);
+}
+
+#[test]
+fn test_path_query() {
+    let mut app = test::init_service(
+        App::new()
+            .register_data(Data::new(SharedConfig::new(CliArgs::default())))
+            .register_data(Data::new(TimeScale::new(1.0)))
+            .route("/sleep/{millis}", web::get().to_async(path::specific))
+            .route(
+                "/random/{min}/{max}",
+                web::get().to_async(path::random_range),
+            ),
+    );
+
+    // test that path routes still read a well-formed query string
+    let req = TestRequest::with_uri("/sleep/20?explain=1").to_request();
+    let json: serde_json::Value = test::read_response_json(&mut app, req);
+
+    assert_eq!("path", json["explain"]["time"]["source"]);
+
+    // test that a malformed query string is ignored, rather than failing the request
+    for uri in &["/sleep/20?time=soon", "/random/20/30?min=-1"] {
+        let req = TestRequest::with_uri(uri).to_request();
+        let resp = test::call_service(&mut app, req);
+
+        assert!(resp.status().is_success(), "{} failed", uri);
+    }
+}
+
+#[test]
+fn test_echo_request() {
+    let mut config = CliArgs::default();
+    config.redact_headers = vec!["X-Api-Key".to_string()];
+
+    let req = TestRequest::with_header("Authorization", "Bearer secret")
+        .uri("/anything?a=1&b=two")
+        .header("x-api-key", "secret")
+        .header("Accept", "text/plain")
+        .to_http_request();
+
+    let echo = echo_request(&req, &config);
+
+    assert_eq!("GET", echo.method);
+    assert_eq!("/anything", echo.path);
+    assert_eq!(Some(&"two".to_string()), echo.query.get("b"));
+    assert_eq!("HTTP/1.1", echo.http_version);
+
+    // test that sensitive headers are redacted, whatever their case
+    assert_eq!("********", echo.headers["authorization"]);
+    assert_eq!("********", echo.headers["x-api-key"]);
+    assert_eq!("text/plain", echo.headers["accept"]);
+
+    // test that the body digest is filled in once the body has been read
+    assert_eq!(0, echo.body.length);
+    assert_eq!(None, echo.body.sha256);
+
+    let echo = echo.with_body(b"hello");
+
+    assert_eq!(5, echo.body.length);
+    assert_eq!(
+        Some("2cf24dba5fb0a30e26e83b2ac5b9e29e1b161e5c1fa7425e73043362938b9824".to_string()),
+        echo.body.sha256
+    );
+}
+
+#[test]
+fn test_extract_echo() {
+    let config = CliArgs::default();
+
+    let mut query = SleepQueryParams::default();
+    query.echo = Some("1".to_string());
+
+    let params = SlumberParams::extract(&HeaderMap::new(), &query, &config).unwrap();
+    assert!(params.echo);
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("x-slumber-echo"),
+        HeaderValue::from_static("true"),
+    );
+
+    let params = SlumberParams::extract(&headers, &SleepQueryParams::default(), &config).unwrap();
+    assert!(params.echo);
+    assert!(headers.contains_key(ECHO_HEADER));
+}
+
+#[test]
+fn test_extract_request_id() {
+    let mut config = CliArgs::default();
+    let trace =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+
+    // test that a fresh id is generated without an incoming id
+    let id = extract_request_id(&HeaderMap::new(), None, &config);
+    assert_eq!(36, id.len());
+    assert_ne!(id, extract_request_id(&HeaderMap::new(), None, &config));
+
+    // test that the trace id is used without an incoming request id
+    assert_eq!(
+        "4bf92f3577b34da6a3ce929d0e0e4736",
+        extract_request_id(&HeaderMap::new(), Some(&trace), &config)
+    );
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("x-request-id"),
+        HeaderValue::from_static("gateway-123"),
+    );
+    headers.insert(
+        HeaderName::from_static("x-correlation-id"),
+        HeaderValue::from_static("correlation-456"),
+    );
+
+    // test that an incoming request id takes priority over the trace id
+    assert_eq!(
+        "gateway-123",
+        extract_request_id(&headers, Some(&trace), &config)
+    );
+
+    // test that the header name is configurable
+    config.request_id_header = "X-Correlation-Id".to_string();
+    assert_eq!(
+        "correlation-456",
+        extract_request_id(&headers, Some(&trace), &config)
+    );
+
+    // test that blank and oversized ids are replaced
+    headers.insert(
+        HeaderName::from_static("x-correlation-id"),
+        HeaderValue::from_static(" "),
+    );
+    assert_eq!(36, extract_request_id(&headers, None, &config).len());
+
+    let long = "a".repeat(300);
+    headers.insert(
+        HeaderName::from_static("x-correlation-id"),
+        HeaderValue::from_str(&long).unwrap(),
+    );
+    assert_eq!(36, extract_request_id(&headers, None, &config).len());
+}
+
+#[test]
+fn test_extract_trace() {
+    let config = CliArgs::default();
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("traceparent"),
+        HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+    );
+
+    let params = SlumberParams::extract(&headers, &SleepQueryParams::default(), &config).unwrap();
+    let trace = params.trace.unwrap();
+
+    // test that the response continues the incoming trace with a new span
+    assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", params.request_id);
+    assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", trace.trace_id);
+    assert_ne!("00f067aa0ba902b7", trace.parent_id);
+}
+
+#[test]
+fn test_resolve_served_metrics() {
+    let config = CliArgs::default();
+    let metrics = Data::new(Metrics::new());
+
+    let mut query = SleepQueryParams::default();
+    query.duration = Some(1);
+
+    // test that a duration below the minimum is counted as clamped
+    let params = SlumberParams::extract(&HeaderMap::new(), &query, &config).unwrap();
+    let slumber =
+        params.resolve_served(&config, 1.0, "default", Some(metrics.clone()), Span::none());
+
+    assert_eq!("default", slumber.route);
+    assert!(slumber.metrics.is_some());
+
+    // test that a duration within the bounds isn't
+    query.duration = Some(100);
+
+    let params = SlumberParams::extract(&HeaderMap::new(), &query, &config).unwrap();
+    params.resolve_served(&config, 1.0, "default", Some(metrics.clone()), Span::none());
+
+    // test that a gated request isn't counted as clamped, even though it reports its gate timeout as its duration
+    query.gate = Some("start".to_string());
+
+    let params = SlumberParams::extract(&HeaderMap::new(), &query, &config).unwrap();
+    let slumber =
+        params.resolve_served(&config, 1.0, "default", Some(metrics.clone()), Span::none());
+
+    assert_eq!(config.max_sleep(), slumber.duration);
+
+    let rendered = String::from_utf8(metrics.render()).unwrap();
+    assert!(rendered.contains(r#"slumberd_clamped_total{param="time"} 1"#));
+}
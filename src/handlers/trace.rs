@@ -0,0 +1,83 @@
+#[cfg(test)]
+mod tests;
+
+use rand::{thread_rng, Rng};
+
+use std::fmt;
+
+/// A W3C trace context, as carried by the `traceparent` header.
+///
+/// See https://www.w3.org/TR/trace-context/ for the format.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TraceContext {
+    pub trace_id: String,
+    pub parent_id: String,
+    pub flags: u8,
+}
+
+impl TraceContext {
+    /// Parse a `traceparent` header, returning nothing if it's malformed.
+    ///
+    /// Versions other than `00` are parsed as far as `00` defines, as the specification requires.
+    pub fn parse(header: &str) -> Option<TraceContext> {
+        let mut parts = header.trim().split('-');
+
+        let version = parts.next().filter(|v| is_hex(v, 2) && *v != "ff")?;
+        let trace_id = parts.next().filter(|v| is_hex(v, 32) && !is_zero(v))?;
+        let parent_id = parts.next().filter(|v| is_hex(v, 16) && !is_zero(v))?;
+        let flags = parts.next().filter(|v| is_hex(v, 2))?;
+
+        // version 00 has exactly four fields, while later versions may append more
+        if version == "00" && parts.next().is_some() {
+            return None;
+        }
+
+        Some(TraceContext {
+            trace_id: trace_id.to_string(),
+            parent_id: parent_id.to_string(),
+            flags: u8::from_str_radix(flags, 16).ok()?,
+        })
+    }
+
+    /// Create a span in the same trace whose parent is this context's span.
+    pub fn child(&self) -> TraceContext {
+        // the span id must not be all zeroes
+        let span_id = loop {
+            let id: u64 = thread_rng().gen();
+
+            if id != 0 {
+                break id;
+            }
+        };
+
+        TraceContext {
+            trace_id: self.trace_id.clone(),
+            parent_id: format!("{:016x}", span_id),
+            flags: self.flags,
+        }
+    }
+}
+
+impl fmt::Display for TraceContext {
+    /// Format the context as a version `00` `traceparent` header.
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "00-{}-{}-{:02x}",
+            self.trace_id, self.parent_id, self.flags
+        )
+    }
+}
+
+/// Check whether a value is made up of exactly `len` lowercase hex digits.
+fn is_hex(value: &str, len: usize) -> bool {
+    value.len() == len
+        && value
+            .bytes()
+            .all(|b| matches!(b, b'0'..=b'9' | b'a'..=b'f'))
+}
+
+/// Check whether a hex value is all zeroes, which the specification forbids for ids.
+fn is_zero(value: &str) -> bool {
+    value.bytes().all(|b| b == b'0')
+}
@@ -0,0 +1,49 @@
+use super::TraceContext;
+
+#[test]
+fn test_parse() {
+    let context =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+
+    assert_eq!("4bf92f3577b34da6a3ce929d0e0e4736", context.trace_id);
+    assert_eq!("00f067aa0ba902b7", context.parent_id);
+    assert_eq!(1, context.flags);
+
+    // test that later versions may carry extra fields
+    assert!(
+        TraceContext::parse("01-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-00-extra")
+            .is_some()
+    );
+}
+
+#[test]
+fn test_parse_invalid() {
+    for header in &[
+        "",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01-extra",
+        "ff-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01",
+        "00-00000000000000000000000000000000-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-0000000000000000-01",
+        "00-4BF92F3577B34DA6A3CE929D0E0E4736-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e473-00f067aa0ba902b7-01",
+        "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902bz-01",
+    ] {
+        assert_eq!(None, TraceContext::parse(header), "{}", header);
+    }
+}
+
+#[test]
+fn test_child() {
+    let context =
+        TraceContext::parse("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01").unwrap();
+    let child = context.child();
+
+    assert_eq!(context.trace_id, child.trace_id);
+    assert_eq!(context.flags, child.flags);
+    assert_ne!(context.parent_id, child.parent_id);
+    assert_eq!(16, child.parent_id.len());
+
+    // test that the child round trips through the header format
+    assert_eq!(Some(child.clone()), TraceContext::parse(&child.to_string()));
+}
@@ -1,3 +1,8 @@
+pub mod access;
 pub mod config;
 pub mod handlers;
 pub mod logging;
+pub mod metrics;
+pub mod reload;
+#[cfg(feature = "otlp")]
+pub mod telemetry;
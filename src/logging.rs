@@ -1,50 +1,140 @@
-use crate::config::CliArgs;
+#[cfg(test)]
+mod tests;
 
-use log4rs::encode::json::JsonEncoder;
-use log4rs::encode::pattern::PatternEncoder;
-use log4rs::encode::Encode;
+mod level;
+mod rolling;
 
-use log::LevelFilter;
-use log4rs::append::console::{ConsoleAppender, Target};
-use log4rs::append::Append;
-use log4rs::config::{Appender, Config, Logger, Root};
-use parking_lot::Once;
+pub use self::level::{watch_signals, LogLevel};
+pub use self::rolling::{RollingFile, RollingPolicy};
 
-static LOG_FORMAT: &'static str =
-    "{d(%Y-%m-%dT%H:%M:%S%.3f)} [{level:5.5}] {T} {M} \\({f}:{L}\\): {m}{n}";
+use crate::config::{CliArgs, LogFormat};
 
-static INIT: Once = Once::new();
+use tracing::Subscriber;
+use tracing_subscriber::fmt::{self, MakeWriter};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{reload, Layer};
 
-pub fn init(cli: &CliArgs) {
-    INIT.call_once(|| init_internal(cli));
+use std::io;
+use std::sync::{Mutex, OnceLock};
+
+static LEVEL: OnceLock<LogLevel> = OnceLock::new();
+
+/// Set up logging, returning the filter which can be changed at runtime.
+pub fn init(cli: &CliArgs) -> LogLevel {
+    LEVEL.get_or_init(|| init_internal(cli)).clone()
 }
 
-fn init_internal(cli: &CliArgs) {
-    let encoder: Box<dyn Encode> = if cli.json {
-        Box::new(JsonEncoder::new())
+/// The format of logs written to stderr.
+fn console_format(cli: &CliArgs) -> LogFormat {
+    if cli.json {
+        LogFormat::Json
     } else {
-        Box::new(PatternEncoder::new(LOG_FORMAT))
+        LogFormat::Pattern
+    }
+}
+
+fn init_internal(cli: &CliArgs) -> LogLevel {
+    let level = LogLevel::new(cli.verbosity);
+
+    let (file, file_error) = match file_layer(cli, &level) {
+        Ok(file) => (file, None),
+        Err(e) => (None, Some(e)),
     };
 
-    let appender: Box<dyn Append> = Box::new(
-        ConsoleAppender::builder()
-            .encoder(encoder)
-            .target(Target::Stderr)
-            .build(),
-    );
-
-    let level = match cli.verbosity {
-        0 => LevelFilter::Info,
-        1 => LevelFilter::Debug,
-        _ => LevelFilter::Trace,
+    let registry = tracing_subscriber::registry()
+        .with(layer(&level, console_format(cli), io::stderr))
+        .with(file);
+
+    #[cfg(feature = "otlp")]
+    {
+        let (telemetry, error) = match crate::telemetry::layer(cli) {
+            Ok(telemetry) => (telemetry, None),
+            Err(e) => (None, Some(e)),
+        };
+
+        registry.with(telemetry).init();
+
+        if let Some(e) = error {
+            tracing::warn!("Unable to export traces to {:?}: {}", cli.otlp_endpoint, e);
+        }
+    }
+
+    #[cfg(not(feature = "otlp"))]
+    {
+        registry.init();
+
+        if cli.otlp_endpoint.is_some() {
+            tracing::warn!(
+                "Traces can't be exported, as slumberd was built without the otlp feature."
+            );
+        }
+    }
+
+    if let (Some(path), Some(e)) = (&cli.log_file, file_error) {
+        tracing::warn!("Unable to log to {}: {}", path.display(), e);
+    }
+
+    level
+}
+
+/// Build the layer which writes to the log file, if one is configured.
+fn file_layer<S>(
+    cli: &CliArgs,
+    level: &LogLevel,
+) -> io::Result<Option<Box<dyn Layer<S> + Send + Sync>>>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    let path = match &cli.log_file {
+        Some(path) => path,
+        None => return Ok(None),
     };
 
-    log4rs::init_config(
-        Config::builder()
-            .appender(Appender::builder().build("stderr", appender))
-            .logger(Logger::builder().build("slumberd", level))
-            .build(Root::builder().appender("stderr").build(LevelFilter::Warn))
-            .unwrap(),
-    )
-    .unwrap();
+    let file = RollingFile::open(
+        path,
+        RollingPolicy {
+            max_size: cli.log_max_size.map(|size| size.0),
+            rotation: cli.log_rotation,
+            keep: cli.log_keep,
+            compress: cli.log_compress,
+        },
+    )?;
+
+    let format = cli.log_file_format.unwrap_or_else(|| console_format(cli));
+
+    Ok(Some(layer(level, format, Mutex::new(file))))
+}
+
+/// Build the layer which formats events for a writer in the given format, following changes to the log level.
+fn layer<S, W>(level: &LogLevel, format: LogFormat, writer: W) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let (filter, handle) = reload::Layer::new(level.filter());
+
+    level.watch(move |targets| {
+        let _ = handle.reload(targets.clone());
+    });
+
+    let layer = fmt::layer()
+        .with_writer(writer)
+        .with_ansi(false)
+        .with_thread_names(true)
+        .with_file(true)
+        .with_line_number(true);
+
+    // span and event fields are structured in JSON, rather than being formatted into the message
+    if format == LogFormat::Json {
+        layer
+            .json()
+            .flatten_event(true)
+            .with_current_span(true)
+            .with_span_list(false)
+            .with_filter(filter)
+            .boxed()
+    } else {
+        layer.with_filter(filter).boxed()
+    }
 }
@@ -0,0 +1,174 @@
+#[cfg(test)]
+mod tests;
+
+use parking_lot::Mutex;
+
+use signal_hook::consts::{SIGUSR1, SIGUSR2};
+use signal_hook::iterator::Signals;
+
+use tracing::Level;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+
+use std::fmt;
+use std::sync::Arc;
+use std::thread;
+
+/// The target of slumberd's own events, which verbosity and signals apply to.
+const TARGET: &str = "slumberd";
+
+/// The levels which signals step through, from the quietest.
+const LEVELS: [LevelFilter; 6] = [
+    LevelFilter::OFF,
+    LevelFilter::ERROR,
+    LevelFilter::WARN,
+    LevelFilter::INFO,
+    LevelFilter::DEBUG,
+    LevelFilter::TRACE,
+];
+
+/// A function which applies a new filter to one of the log destinations.
+type Reloader = Box<dyn Fn(&Targets) + Send + Sync>;
+
+/// The runtime-adjustable filter which decides the events logged by every log destination.
+///
+/// The filter is written as comma-separated directives, such as `warn,slumberd=info,slumberd::handlers=debug`, where a
+/// directive without a target sets the level of every other target.
+#[derive(Clone)]
+pub struct LogLevel(Arc<Inner>);
+
+struct Inner {
+    filter: Mutex<Targets>,
+    reloaders: Mutex<Vec<Reloader>>,
+}
+
+impl LogLevel {
+    /// Log slumberd's own events at the level for a verbosity, and warnings and errors from its dependencies.
+    pub fn new(verbosity: u64) -> Self {
+        LogLevel(Arc::new(Inner {
+            filter: Mutex::new(filter(verbosity)),
+            reloaders: Mutex::new(Vec::new()),
+        }))
+    }
+
+    /// The current filter.
+    pub fn filter(&self) -> Targets {
+        self.0.filter.lock().clone()
+    }
+
+    /// Apply changes to the filter to a log destination.
+    pub fn watch<F>(&self, reload: F)
+    where
+        F: Fn(&Targets) + Send + Sync + 'static,
+    {
+        self.0.reloaders.lock().push(Box::new(reload));
+    }
+
+    /// Replace the filter with the given directives.
+    pub fn set(&self, directives: &str) -> Result<(), String> {
+        if directives.trim().is_empty() {
+            return Err("Log level directives can't be empty.".to_string());
+        }
+
+        let filter = directives
+            .split(',')
+            .map(str::trim)
+            .collect::<Vec<_>>()
+            .join(",")
+            .parse::<Targets>()
+            .map_err(|e| format!("Invalid log level directives '{}': {}", directives, e))?;
+
+        self.replace(filter);
+
+        Ok(())
+    }
+
+    /// Reset the filter to the default for a verbosity, dropping any per-module levels.
+    pub fn set_verbosity(&self, verbosity: u64) {
+        self.replace(filter(verbosity));
+    }
+
+    /// Log more or less of slumberd's own events, keeping the levels of other targets, and return the new level.
+    pub fn step(&self, louder: bool) -> LevelFilter {
+        let current = self.filter();
+
+        let level = current
+            .iter()
+            .find(|(target, _)| *target == TARGET)
+            .map(|(_, level)| level)
+            .or_else(|| current.default_level())
+            .unwrap_or(LevelFilter::OFF);
+
+        let index = LEVELS.iter().position(|l| *l == level).unwrap_or(0);
+        let next = if louder {
+            LEVELS[(index + 1).min(LEVELS.len() - 1)]
+        } else {
+            LEVELS[index.saturating_sub(1)]
+        };
+
+        let mut filter: Targets = current
+            .iter()
+            .filter(|(target, _)| *target != TARGET)
+            .collect();
+
+        filter = filter.with_target(TARGET, next);
+
+        if let Some(default) = current.default_level() {
+            filter = filter.with_default(default);
+        }
+
+        self.replace(filter);
+
+        next
+    }
+
+    fn replace(&self, filter: Targets) {
+        // hold the filter throughout, so that concurrent changes reach every destination in the same order
+        let mut current = self.0.filter.lock();
+
+        for reload in self.0.reloaders.lock().iter() {
+            reload(&filter);
+        }
+
+        *current = filter;
+    }
+}
+
+impl fmt::Display for LogLevel {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.filter.lock())
+    }
+}
+
+/// The default filter for a verbosity.
+fn filter(verbosity: u64) -> Targets {
+    // slumberd logs at the requested verbosity, while dependencies only log warnings and errors
+    Targets::new()
+        .with_target(TARGET, level(verbosity))
+        .with_default(LevelFilter::WARN)
+}
+
+/// The level at which slumberd's own events are logged for a verbosity.
+fn level(verbosity: u64) -> Level {
+    match verbosity {
+        0 => Level::INFO,
+        1 => Level::DEBUG,
+        _ => Level::TRACE,
+    }
+}
+
+/// Log more of slumberd's own events on SIGUSR1, and fewer on SIGUSR2.
+pub fn watch_signals(level: LogLevel) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGUSR1, SIGUSR2])?;
+
+    thread::Builder::new()
+        .name("log-level-signals".to_string())
+        .spawn(move || {
+            for signal in signals.forever() {
+                let next = level.step(signal == SIGUSR1);
+
+                tracing::warn!("Log level of slumberd changed to {} on signal.", next);
+            }
+        })?;
+
+    Ok(())
+}
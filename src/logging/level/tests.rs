@@ -0,0 +1,76 @@
+use super::LogLevel;
+
+use tracing_subscriber::filter::{LevelFilter, Targets};
+
+use parking_lot::Mutex;
+
+use std::sync::Arc;
+
+#[test]
+fn test_default() {
+    assert_eq!("slumberd=info,warn", LogLevel::new(0).to_string());
+    assert_eq!("slumberd=debug,warn", LogLevel::new(1).to_string());
+    assert_eq!("slumberd=trace,warn", LogLevel::new(5).to_string());
+}
+
+#[test]
+fn test_set() {
+    let level = LogLevel::new(0);
+
+    // test that changes are applied to every watcher
+    let applied = Arc::new(Mutex::new(Vec::new()));
+    let watched = applied.clone();
+
+    level.watch(move |targets: &Targets| watched.lock().push(targets.to_string()));
+
+    level
+        .set("error, slumberd=info, slumberd::handlers=trace")
+        .unwrap();
+
+    let filter = level.filter();
+
+    assert!(filter.would_enable("slumberd::handlers", &tracing::Level::TRACE));
+    assert!(!filter.would_enable("slumberd::config", &tracing::Level::DEBUG));
+    assert!(!filter.would_enable("actix_web", &tracing::Level::WARN));
+    assert_eq!(vec![level.to_string()], *applied.lock());
+
+    // test that invalid directives leave the filter untouched
+    assert!(level.set("").is_err());
+    assert!(level.set("slumberd=loud").is_err());
+    assert_eq!(1, applied.lock().len());
+
+    level.set_verbosity(1);
+
+    assert_eq!("slumberd=debug,warn", level.to_string());
+}
+
+#[test]
+fn test_step() {
+    let level = LogLevel::new(0);
+    level
+        .set("warn,slumberd=info,slumberd::handlers=trace")
+        .unwrap();
+
+    assert_eq!(LevelFilter::DEBUG, level.step(true));
+    assert_eq!(LevelFilter::TRACE, level.step(true));
+    assert_eq!(LevelFilter::TRACE, level.step(true));
+
+    // test that other targets keep their levels
+    assert_eq!(
+        "slumberd::handlers=trace,slumberd=trace,warn",
+        level.to_string()
+    );
+
+    for _ in 0..10 {
+        level.step(false);
+    }
+
+    assert_eq!(LevelFilter::OFF, level.step(false));
+    assert_eq!(LevelFilter::ERROR, level.step(true));
+
+    // test that stepping without a slumberd directive starts from the default level
+    level.set("info").unwrap();
+
+    assert_eq!(LevelFilter::DEBUG, level.step(true));
+    assert_eq!("slumberd=debug,info", level.to_string());
+}
@@ -0,0 +1,214 @@
+#[cfg(test)]
+mod tests;
+
+use crate::config::LogRotation;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How long to wait before trying again after a rotation fails.
+const RETRY_DELAY: Duration = Duration::from_secs(60);
+
+/// When a log file is rotated, and what happens to the rotated files.
+#[derive(Clone, Debug)]
+pub struct RollingPolicy {
+    /// Rotate the file once a write would grow it beyond this many bytes.
+    pub max_size: Option<u64>,
+    /// Rotate the file when a new period starts.
+    pub rotation: LogRotation,
+    /// The number of rotated files to keep.
+    pub keep: usize,
+    /// Whether rotated files are compressed with gzip.
+    pub compress: bool,
+}
+
+/// A log file which is rotated according to a policy.
+///
+/// Rotated files are numbered from the newest, so `slumberd.log` is rotated to `slumberd.log.1` (or
+/// `slumberd.log.1.gz`), which is in turn moved to `slumberd.log.2` by the next rotation, and so on.
+pub struct RollingFile {
+    path: PathBuf,
+    policy: RollingPolicy,
+    file: File,
+    /// The current size of the file in bytes.
+    size: u64,
+    /// The period which the file's contents belong to, if it's rotated periodically.
+    period: Option<u64>,
+    /// The time before which rotation isn't attempted again, after it failed.
+    retry_at: Option<SystemTime>,
+    /// The compression of the newest rotated file, which runs in the background.
+    compressing: Option<JoinHandle<()>>,
+}
+
+impl RollingFile {
+    /// Open a log file for appending, creating it if necessary.
+    pub fn open<P: AsRef<Path>>(path: P, policy: RollingPolicy) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let file = OpenOptions::new().create(true).append(true).open(&path)?;
+        let metadata = file.metadata()?;
+
+        // an existing file belongs to the period it was last written in, so stale files are rotated on the first write
+        let period = period(
+            policy.rotation,
+            metadata.modified().unwrap_or_else(|_| SystemTime::now()),
+        );
+
+        Ok(Self {
+            path,
+            policy,
+            file,
+            size: metadata.len(),
+            period,
+            retry_at: None,
+            compressing: None,
+        })
+    }
+
+    /// Whether writing the given number of bytes at the given time requires a rotation first.
+    fn should_rotate(&self, len: usize, now: SystemTime) -> bool {
+        if self.retry_at.is_some_and(|retry_at| now < retry_at) {
+            return false;
+        }
+
+        let too_large = self
+            .policy
+            .max_size
+            .is_some_and(|max_size| self.size > 0 && self.size + len as u64 > max_size);
+
+        too_large || period(self.policy.rotation, now) != self.period
+    }
+
+    /// Move the current file out of the way, shifting and pruning the rotated files, and start a new file.
+    fn rotate(&mut self, now: SystemTime) -> io::Result<()> {
+        self.file.flush()?;
+
+        // the previous rotated file must be in place before it's shifted along
+        self.wait_for_compression();
+
+        // make room for the newest rotated file by shifting each of the others along, dropping the oldest
+        for index in (1..=self.policy.keep).rev() {
+            for compressed in &[false, true] {
+                let from = archive(&self.path, index, *compressed);
+
+                if !from.exists() {
+                    continue;
+                }
+
+                if index == self.policy.keep {
+                    fs::remove_file(&from)?;
+                } else {
+                    fs::rename(&from, archive(&self.path, index + 1, *compressed))?;
+                }
+            }
+        }
+
+        if self.policy.keep == 0 {
+            fs::remove_file(&self.path)?;
+        } else {
+            fs::rename(&self.path, archive(&self.path, 1, false))?;
+        }
+
+        // compress the rotated file off of the logging path, leaving it uncompressed if that fails
+        if self.policy.keep > 0 && self.policy.compress {
+            let (from, to) = (archive(&self.path, 1, false), archive(&self.path, 1, true));
+
+            self.compressing = Some(thread::spawn(move || {
+                if let Err(e) = compress(&from, &to).and_then(|_| fs::remove_file(&from)) {
+                    eprintln!("slumberd: unable to compress {}: {}", from.display(), e);
+                }
+            }));
+        }
+
+        self.file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)?;
+        self.size = 0;
+        self.period = period(self.policy.rotation, now);
+
+        Ok(())
+    }
+
+    /// Block until the newest rotated file has been compressed, if it's being compressed.
+    fn wait_for_compression(&mut self) {
+        if let Some(compressing) = self.compressing.take() {
+            let _ = compressing.join();
+        }
+    }
+}
+
+impl Drop for RollingFile {
+    fn drop(&mut self) {
+        self.wait_for_compression();
+    }
+}
+
+impl Write for RollingFile {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let now = SystemTime::now();
+
+        if self.should_rotate(buf.len(), now) {
+            // keep logging to the current file rather than losing events if rotation fails, and don't try again on
+            // every write
+            match self.rotate(now) {
+                Ok(()) => self.retry_at = None,
+                Err(e) => {
+                    eprintln!(
+                        "slumberd: unable to rotate {}, retrying in {:?}: {}",
+                        self.path.display(),
+                        RETRY_DELAY,
+                        e
+                    );
+                    self.period = period(self.policy.rotation, now);
+                    self.retry_at = Some(now + RETRY_DELAY);
+                }
+            }
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+/// The path of a rotated log file.
+pub fn archive(path: &Path, index: usize, compressed: bool) -> PathBuf {
+    let mut name = path.as_os_str().to_os_string();
+
+    name.push(format!(".{}", index));
+
+    if compressed {
+        name.push(".gz");
+    }
+
+    PathBuf::from(name)
+}
+
+/// The period which a time falls in, counted from the epoch, if the log file is rotated periodically.
+fn period(rotation: LogRotation, time: SystemTime) -> Option<u64> {
+    let seconds = time
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs())
+        .unwrap_or(0);
+
+    rotation.period().map(|length| seconds / length)
+}
+
+/// Write a gzipped copy of a file.
+fn compress(from: &Path, to: &Path) -> io::Result<()> {
+    let mut encoder = GzEncoder::new(File::create(to)?, Compression::default());
+
+    io::copy(&mut File::open(from)?, &mut encoder)?;
+    encoder.finish()?.sync_all()
+}
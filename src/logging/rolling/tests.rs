@@ -0,0 +1,179 @@
+use super::{archive, RollingFile, RollingPolicy};
+
+use crate::config::LogRotation;
+
+use flate2::read::GzDecoder;
+
+use std::fs;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+use std::time::UNIX_EPOCH;
+
+/// Create an empty directory for a test's log files.
+fn log_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("slumberd-{}-{}", std::process::id(), name));
+
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+
+    dir
+}
+
+fn policy() -> RollingPolicy {
+    RollingPolicy {
+        max_size: Some(10),
+        rotation: LogRotation::Never,
+        keep: 2,
+        compress: false,
+    }
+}
+
+#[test]
+fn test_rotate_size() {
+    let dir = log_dir("rotate-size");
+    let path = dir.join("slumberd.log");
+
+    let mut file = RollingFile::open(&path, policy()).unwrap();
+
+    for line in &["first\n", "second\n", "third\n", "fourth\n"] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+
+    // test that each line which would overflow the file starts a new one, keeping the two newest rotated files
+    assert_eq!("fourth\n", fs::read_to_string(&path).unwrap());
+    assert_eq!(
+        "third\n",
+        fs::read_to_string(archive(&path, 1, false)).unwrap()
+    );
+    assert_eq!(
+        "second\n",
+        fs::read_to_string(archive(&path, 2, false)).unwrap()
+    );
+    assert!(!archive(&path, 3, false).exists());
+
+    // test that an existing file is appended to, counting its size
+    let mut file = RollingFile::open(&path, policy()).unwrap();
+    file.write_all(b"fifth\n").unwrap();
+
+    assert_eq!("fifth\n", fs::read_to_string(&path).unwrap());
+    assert_eq!(
+        "fourth\n",
+        fs::read_to_string(archive(&path, 1, false)).unwrap()
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rotate_compressed() {
+    let dir = log_dir("rotate-compressed");
+    let path = dir.join("slumberd.log");
+
+    let mut policy = policy();
+    policy.compress = true;
+
+    let mut file = RollingFile::open(&path, policy).unwrap();
+
+    for line in &["first\n", "second\n", "third\n", "fourth\n"] {
+        file.write_all(line.as_bytes()).unwrap();
+    }
+
+    // the newest rotated file is compressed in the background, until the file is dropped
+    drop(file);
+
+    let mut contents = String::new();
+
+    GzDecoder::new(fs::File::open(archive(&path, 1, true)).unwrap())
+        .read_to_string(&mut contents)
+        .unwrap();
+
+    assert_eq!("third\n", contents);
+    assert!(archive(&path, 2, true).exists());
+    assert!(!archive(&path, 1, false).exists());
+    assert!(!archive(&path, 3, true).exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rotate_period() {
+    let dir = log_dir("rotate-period");
+    let path = dir.join("slumberd.log");
+
+    let mut policy = policy();
+    policy.max_size = None;
+    policy.rotation = LogRotation::Daily;
+
+    let mut file = RollingFile::open(&path, policy).unwrap();
+    file.write_all(b"today\n").unwrap();
+
+    assert!(!archive(&path, 1, false).exists());
+
+    // test that a new period rotates the file, whatever its size
+    file.period = Some(0);
+    file.write_all(b"tomorrow\n").unwrap();
+
+    assert_eq!("tomorrow\n", fs::read_to_string(&path).unwrap());
+    assert_eq!(
+        "today\n",
+        fs::read_to_string(archive(&path, 1, false)).unwrap()
+    );
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_keep_none() {
+    let dir = log_dir("keep-none");
+    let path = dir.join("slumberd.log");
+
+    let mut policy = policy();
+    policy.keep = 0;
+
+    let mut file = RollingFile::open(&path, policy).unwrap();
+
+    file.write_all(b"first\n").unwrap();
+    file.write_all(b"second\n").unwrap();
+
+    assert_eq!("second\n", fs::read_to_string(&path).unwrap());
+    assert!(!archive(&path, 1, false).exists());
+
+    fs::remove_dir_all(dir).unwrap();
+}
+
+#[test]
+fn test_rotate_failed() {
+    let dir = log_dir("rotate-failed");
+    let path = dir.join("slumberd.log");
+
+    // a directory in place of the oldest rotated file can't be removed, so rotation fails
+    let blocker = archive(&path, 2, false);
+    fs::create_dir_all(blocker.join("nested")).unwrap();
+
+    let mut file = RollingFile::open(&path, policy()).unwrap();
+
+    file.write_all(b"first\n").unwrap();
+    file.write_all(b"second\n").unwrap();
+
+    assert!(file.retry_at.is_some());
+
+    // test that the file keeps growing without retrying the rotation on every write
+    file.write_all(b"third\n").unwrap();
+
+    assert_eq!("first\nsecond\nthird\n", fs::read_to_string(&path).unwrap());
+    assert!(!archive(&path, 1, false).exists());
+
+    // test that rotation is retried once the delay has passed
+    fs::remove_dir_all(&blocker).unwrap();
+    file.retry_at = Some(UNIX_EPOCH);
+    file.write_all(b"fourth\n").unwrap();
+
+    assert_eq!("fourth\n", fs::read_to_string(&path).unwrap());
+    assert_eq!(
+        "first\nsecond\nthird\n",
+        fs::read_to_string(archive(&path, 1, false)).unwrap()
+    );
+    assert!(file.retry_at.is_none());
+
+    fs::remove_dir_all(dir).unwrap();
+}
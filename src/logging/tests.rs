@@ -0,0 +1,153 @@
+#![allow(clippy::field_reassign_with_default)]
+
+use super::{console_format, file_layer, layer, LogLevel};
+
+use crate::config::{CliArgs, LogFormat};
+
+use parking_lot::Mutex;
+
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::prelude::*;
+
+use std::io;
+use std::sync::Arc;
+
+/// A writer which collects everything written to it.
+#[derive(Clone, Default)]
+struct Buffer(Arc<Mutex<Vec<u8>>>);
+
+impl Buffer {
+    fn lines(&self) -> Vec<String> {
+        String::from_utf8(self.0.lock().clone())
+            .unwrap()
+            .lines()
+            .map(str::to_string)
+            .collect()
+    }
+}
+
+impl io::Write for Buffer {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.0.lock().extend_from_slice(buf);
+
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+impl<'a> MakeWriter<'a> for Buffer {
+    type Writer = Buffer;
+
+    fn make_writer(&'a self) -> Self::Writer {
+        self.clone()
+    }
+}
+
+/// Log a request's events with the given configuration, returning what was written.
+fn log(cli: &CliArgs) -> Vec<String> {
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::registry().with(layer(
+        &LogLevel::new(cli.verbosity),
+        console_format(cli),
+        buffer.clone(),
+    ));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("slumber", request_id = "abc", kind = "fixed");
+        let _entered = span.enter();
+
+        tracing::info!(status = 200, "Sending response.");
+        tracing::debug!("Sleep started.");
+    });
+
+    buffer.lines()
+}
+
+#[test]
+fn test_json() {
+    let mut cli = CliArgs::default();
+    cli.json = true;
+
+    let lines = log(&cli);
+    assert_eq!(1, lines.len());
+
+    // test that event and span fields are structured
+    let line: serde_json::Value = serde_json::from_str(&lines[0]).unwrap();
+
+    assert_eq!("Sending response.", line["message"]);
+    assert_eq!(200, line["status"]);
+    assert_eq!("abc", line["span"]["request_id"]);
+    assert_eq!("fixed", line["span"]["kind"]);
+}
+
+#[test]
+fn test_verbosity() {
+    let mut cli = CliArgs::default();
+    cli.verbosity = 1;
+
+    let lines = log(&cli);
+
+    // test that debug events are logged, within their span
+    assert_eq!(2, lines.len());
+    assert!(lines[1].contains(r#"slumber{request_id="abc" kind="fixed"}"#));
+    assert!(lines[1].ends_with("Sleep started."));
+}
+
+#[test]
+fn test_file_format() {
+    let path =
+        std::env::temp_dir().join(format!("slumberd-{}-file-format.log", std::process::id()));
+    let _ = std::fs::remove_file(&path);
+
+    let mut cli = CliArgs::default();
+    cli.log_file = Some(path.clone());
+    cli.log_file_format = Some(LogFormat::Json);
+
+    let level = LogLevel::new(cli.verbosity);
+    let buffer = Buffer::default();
+    let subscriber = tracing_subscriber::registry()
+        .with(layer(&level, console_format(&cli), buffer.clone()))
+        .with(file_layer(&cli, &level).unwrap());
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::info!(status = 200, "Sending response.");
+    });
+
+    // test that the console stays human-readable while the file is JSON
+    assert!(buffer.lines()[0].ends_with("Sending response. status=200"));
+
+    let contents = std::fs::read_to_string(&path).unwrap();
+    let line: serde_json::Value = serde_json::from_str(contents.trim()).unwrap();
+
+    assert_eq!("Sending response.", line["message"]);
+    assert_eq!(200, line["status"]);
+
+    std::fs::remove_file(path).unwrap();
+}
+
+#[test]
+fn test_change_level() {
+    let level = LogLevel::new(0);
+    let buffer = Buffer::default();
+    let subscriber =
+        tracing_subscriber::registry().with(layer(&level, LogFormat::Pattern, buffer.clone()));
+
+    tracing::subscriber::with_default(subscriber, || {
+        tracing::debug!("Hidden.");
+
+        // test that a changed level applies to events which were previously filtered out
+        level.set("warn,slumberd=debug").unwrap();
+        tracing::debug!("Shown.");
+
+        level.step(false);
+        tracing::debug!("Hidden again.");
+    });
+
+    let lines = buffer.lines();
+
+    assert_eq!(1, lines.len());
+    assert!(lines[0].ends_with("Shown."));
+}
@@ -1,21 +1,41 @@
 use actix_web::{web, App, HttpServer};
 
-use slumberd::config::CliArgs;
-use slumberd::handlers;
+use slumberd::access::AccessLog;
+use slumberd::config::{
+    CliArgs, Command, ConfigCommand, ConfigFile, ConfigFormat, SharedConfig, TimeScale,
+};
+use slumberd::handlers::{self, gate::Gates, history::History, inflight::InFlightRequests};
 use slumberd::logging;
+use slumberd::metrics::{self, Metrics, Statsd};
+use slumberd::reload::{self, Reloader};
 
-use structopt::StructOpt;
+use std::process;
 
 fn main() {
-    // parse CLI args
-    let cli = CliArgs::from_args();
+    // parse CLI args and the configuration file
+    let cli = match CliArgs::load() {
+        Ok(cli) => cli,
+        Err(e) => {
+            eprintln!("slumberd: {}", e);
+            process::exit(1);
+        }
+    };
 
     // setup logging real quick
-    logging::init(&cli);
+    let log_level = web::Data::new(logging::init(&cli));
+
+    // log where each option came from
+    if let Some(path) = &cli.config {
+        tracing::info!("Loaded configuration from {}.", path.display());
+    }
+
+    for (name, value, source) in cli.describe() {
+        tracing::info!("Using {} = {} from {}.", name, value, source);
+    }
 
     // log a warning if bounds are violated
     if cli.min_sleep_ms > cli.max_sleep_ms {
-        log::warn!(
+        tracing::warn!(
             "Minimum sleep time ({}ms) is greater than maximum sleep time ({}ms), normalizing to {:?}.",
             cli.min_sleep_ms,
             cli.max_sleep_ms,
@@ -24,7 +44,7 @@ fn main() {
     }
 
     if cli.sleep_ms < cli.min_sleep_ms || cli.sleep_ms > cli.max_sleep_ms {
-        log::warn!(
+        tracing::warn!(
             "Sleep time ({}ms) is outside of minimum/maximum range ({:?}-{:?}), normalizing to {:?}.",
             cli.sleep_ms,
             cli.min_sleep(),
@@ -33,32 +53,228 @@ fn main() {
         );
     }
 
+    if cli.time_scale() != cli.time_scale {
+        tracing::warn!(
+            "Time scale ({}) must be a positive number no greater than {}, normalizing to {}.",
+            cli.time_scale,
+            TimeScale::MAX,
+            cli.time_scale(),
+        );
+    }
+
+    if let Some(Command::Config(ConfigCommand::Check)) = &cli.command {
+        if let Err(e) = cli.validate() {
+            eprintln!("slumberd: {}", e);
+            process::exit(1);
+        }
+
+        // print the effective configuration in the format of the configuration file
+        let format = cli
+            .config
+            .as_ref()
+            .map(|p| ConfigFormat::from_path(p))
+            .unwrap_or(ConfigFormat::Toml);
+
+        match ConfigFile::from(&cli)
+            .normalized(&cli)
+            .redacted()
+            .render(format)
+        {
+            Ok(rendered) => print!("{}", rendered),
+            Err(e) => {
+                eprintln!("slumberd: {}", e);
+                process::exit(1);
+            }
+        }
+
+        return;
+    }
+
     let bind_addr = format!("{}:{}", cli.host, cli.port);
 
-    log::info!(
-        "Starting slumberd (min sleep time: {:?}, default sleep time: {:?}, max sleep time: {:?}, random: {}).",
+    tracing::info!(
+        "Starting slumberd (min sleep time: {:?}, default sleep time: {:?}, max sleep time: {:?}, random: {}, time scale: {}).",
         cli.min_sleep(),
         cli.sleep(),
         cli.max_sleep(),
         cli.random,
+        cli.time_scale(),
     );
 
-    // establish a maximum shutdown timeout based on the maximum sleep duration
-    let shutdown_timeout = cli.max_sleep().as_secs_f64().ceil() as u64;
+    // establish a maximum shutdown timeout based on the maximum (possibly dilated) sleep duration, which can't follow a
+    // scale changed at runtime, as the server is built once
+    let shutdown_timeout =
+        (cli.max_sleep().as_secs_f64() * cli.time_scale().max(1.0)).ceil() as u64;
 
     // let's rock and fucking roll
-    log::info!("Listening on {}.", bind_addr);
+    tracing::info!("Listening on {}.", bind_addr);
+
+    let watch_path = cli.config.clone().filter(|_| cli.watch_config);
+
+    let scale = web::Data::new(TimeScale::new(cli.time_scale()));
+    let state = web::Data::new(SharedConfig::new(cli));
+
+    // reload configuration on demand, keeping the original command-line
+    let reloader = web::Data::new(Reloader::new(
+        std::env::args_os().collect(),
+        state.clone(),
+        scale.clone(),
+        log_level.clone(),
+    ));
+
+    if let Err(e) = reload::watch_signals(reloader.clone()) {
+        tracing::warn!("Unable to reload configuration on SIGHUP: {}", e);
+    }
 
-    let state = web::Data::new(cli);
+    if let Err(e) = logging::watch_signals(log_level.get_ref().clone()) {
+        tracing::warn!(
+            "Unable to change the log level on SIGUSR1 and SIGUSR2: {}",
+            e
+        );
+    }
+
+    if let Some(path) = watch_path {
+        tracing::info!("Watching {} for changes.", path.display());
+
+        if let Err(e) = reload::watch_file(reloader.clone(), path) {
+            tracing::warn!("Unable to watch configuration file: {}", e);
+        }
+    }
 
-    let _s = HttpServer::new(move || {
+    let admin_port = state.current().admin_port;
+    let metrics_port = state.current().metrics_port;
+    let mut metrics = Metrics::new();
+    let current = state.current();
+
+    if let Some(addr) = &current.statsd {
+        match Statsd::connect(
+            addr,
+            &current.statsd_prefix,
+            &current.statsd_tags,
+            current.statsd_flavor,
+        ) {
+            Ok(statsd) => {
+                tracing::info!("Pushing metrics to StatsD at {}.", addr);
+                metrics = metrics.with_statsd(statsd);
+            }
+            Err(e) => tracing::warn!("Unable to push metrics to StatsD at {}: {}", addr, e),
+        }
+    }
+
+    let metrics = web::Data::new(metrics);
+    let history = web::Data::new(History::new(current.history_size));
+    let inflight = web::Data::new(InFlightRequests::new());
+    let gates = web::Data::new(Gates::new());
+
+    if state.current().admin_token.is_none() {
+        if state.current().admin_insecure {
+            tracing::warn!("No admin token is configured, so the admin API is unauthenticated.");
+        } else {
+            tracing::warn!("No admin token is configured, so the admin API is disabled.");
+        }
+    }
+
+    // the access log covers the main listener, which sleeping requests are served on
+    let access_log = match &state.current().access_log {
+        Some(target) => match AccessLog::open(target, state.current().access_log_format.clone()) {
+            Ok(access_log) => {
+                tracing::info!("Writing the access log to {}.", target);
+                access_log
+            }
+            Err(e) => {
+                eprintln!("slumberd: unable to open access log {}: {}", target, e);
+                process::exit(1);
+            }
+        },
+        None => AccessLog::disabled(),
+    };
+
+    let sys = actix_rt::System::new("slumberd");
+
+    // serve the admin API on its own listener if requested
+    if let Some(admin_port) = admin_port {
+        let admin_addr = format!("{}:{}", state.current().host, admin_port);
+
+        tracing::info!("Serving the admin API on {}.", admin_addr);
+
+        let (state, scale, reloader, log_level, inflight, gates) = (
+            state.clone(),
+            scale.clone(),
+            reloader.clone(),
+            log_level.clone(),
+            inflight.clone(),
+            gates.clone(),
+        );
+
+        HttpServer::new(move || {
+            App::new()
+                .register_data(state.clone())
+                .register_data(scale.clone())
+                .register_data(reloader.clone())
+                .register_data(log_level.clone())
+                .register_data(inflight.clone())
+                .register_data(gates.clone())
+                .configure(handlers::admin::configure)
+        })
+        .bind(admin_addr)
+        .unwrap()
+        .start();
+    }
+
+    // serve metrics on their own listener if requested, so that scraping doesn't compete with sleeping requests
+    if let Some(metrics_port) = metrics_port {
+        let metrics_addr = format!("{}:{}", state.current().host, metrics_port);
+
+        tracing::info!("Serving metrics on {}.", metrics_addr);
+
+        let metrics = metrics.clone();
+
+        HttpServer::new(move || {
+            App::new()
+                .register_data(metrics.clone())
+                .configure(metrics::configure)
+        })
+        .bind(metrics_addr)
+        .unwrap()
+        .start();
+    }
+
+    HttpServer::new(move || {
         App::new()
             .register_data(state.clone())
+            .register_data(scale.clone())
+            .register_data(reloader.clone())
+            .register_data(metrics.clone())
+            .register_data(log_level.clone())
+            .register_data(history.clone())
+            .register_data(inflight.clone())
+            .register_data(gates.clone())
+            .wrap(access_log.clone())
+            // runtime administration, unless it has its own listener
+            .configure(|cfg| {
+                if admin_port.is_none() {
+                    handlers::admin::configure(cfg);
+                }
+            })
+            // metrics, unless they have their own listener
+            .configure(|cfg| {
+                if metrics_port.is_none() {
+                    metrics::configure(cfg);
+                }
+            })
+            // recent requests, and a live feed of requests as they complete
+            .configure(handlers::history::configure)
             // provide help via http
             .route("/_help", web::route().to(handlers::help))
             .route("/_help/", web::route().to(handlers::help))
             .route("/_usage", web::route().to(handlers::help))
             .route("/_usage/", web::route().to(handlers::help))
+            // sample sleep durations without sleeping
+            .route("/_sample", web::route().to(handlers::sample))
+            .route("/_sample/", web::route().to(handlers::sample))
+            // path-specified profile
+            .route("/profile/{name}", web::to_async(handlers::path::profile))
+            .route("/profile/{name}/", web::to_async(handlers::path::profile))
             // path-specified random
             .route(
                 "/random/{min}/{max}",
@@ -80,6 +296,10 @@ fn main() {
     .shutdown_timeout(shutdown_timeout)
     .bind(bind_addr)
     .unwrap()
-    .run()
-    .unwrap();
+    .start();
+
+    sys.run().unwrap();
+
+    #[cfg(feature = "otlp")]
+    slumberd::telemetry::shutdown();
 }
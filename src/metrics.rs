@@ -0,0 +1,236 @@
+#[cfg(test)]
+mod tests;
+
+mod statsd;
+
+pub use self::statsd::Statsd;
+
+use actix_web::web::{self, Data, ServiceConfig};
+use actix_web::HttpResponse;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts, Registry, TextEncoder,
+};
+
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The histogram buckets for sleep durations in seconds, covering the default maximum sleep time.
+const DURATION_BUCKETS: &[f64] = &[
+    0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0, 10.0, 30.0, 60.0,
+];
+
+/// Register the metrics route, either on the main listener or on a separate metrics listener.
+pub fn configure(cfg: &mut ServiceConfig) {
+    cfg.route("/_metrics", web::get().to(metrics))
+        .route("/_metrics/", web::get().to(metrics));
+}
+
+/// The metrics collected about sleepy requests, exported in the Prometheus text format and optionally pushed to StatsD.
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    requested: HistogramVec,
+    actual: HistogramVec,
+    in_flight: IntGauge,
+    clamped: IntCounterVec,
+    faults: IntCounterVec,
+    statsd: Option<Arc<Statsd>>,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+
+        let requests = IntCounterVec::new(
+            Opts::new("slumberd_requests_total", "Sleep requests served."),
+            &["kind", "route", "status"],
+        )
+        .unwrap();
+
+        let requested = HistogramVec::new(
+            HistogramOpts::new(
+                "slumberd_requested_sleep_seconds",
+                "Sleep durations resolved for requests, before time scaling.",
+            )
+            .buckets(DURATION_BUCKETS.to_vec()),
+            &["kind"],
+        )
+        .unwrap();
+
+        let actual = HistogramVec::new(
+            HistogramOpts::new(
+                "slumberd_actual_sleep_seconds",
+                "Time requests actually spent sleeping.",
+            )
+            .buckets(DURATION_BUCKETS.to_vec()),
+            &["kind"],
+        )
+        .unwrap();
+
+        let in_flight = IntGauge::new(
+            "slumberd_in_flight_requests",
+            "Requests which are currently sleeping.",
+        )
+        .unwrap();
+
+        let clamped = IntCounterVec::new(
+            Opts::new(
+                "slumberd_clamped_total",
+                "Requested durations which were coerced to fit the minimum and maximum sleep times.",
+            ),
+            &["param"],
+        )
+        .unwrap();
+
+        let faults = IntCounterVec::new(
+            Opts::new("slumberd_faults_total", "Faults injected by profiles."),
+            &["profile", "status"],
+        )
+        .unwrap();
+
+        registry.register(Box::new(requests.clone())).unwrap();
+        registry.register(Box::new(requested.clone())).unwrap();
+        registry.register(Box::new(actual.clone())).unwrap();
+        registry.register(Box::new(in_flight.clone())).unwrap();
+        registry.register(Box::new(clamped.clone())).unwrap();
+        registry.register(Box::new(faults.clone())).unwrap();
+
+        Self {
+            registry,
+            requests,
+            requested,
+            actual,
+            in_flight,
+            clamped,
+            faults,
+            statsd: None,
+        }
+    }
+
+    /// Also push every metric to StatsD as it's recorded.
+    pub fn with_statsd(mut self, statsd: Statsd) -> Self {
+        self.statsd = Some(Arc::new(statsd));
+        self
+    }
+
+    /// Record a request which was served, along with how long it was resolved to sleep for.
+    ///
+    /// Requests which waited at a gate rather than sleeping have no requested duration.
+    pub fn request(&self, kind: &str, route: &str, status: u16, requested: Option<&Duration>) {
+        let status = status.to_string();
+
+        self.requests
+            .with_label_values(&[kind, route, &status])
+            .inc();
+
+        if let Some(requested) = requested {
+            self.requested
+                .with_label_values(&[kind])
+                .observe(requested.as_secs_f64());
+        }
+
+        if let Some(statsd) = &self.statsd {
+            statsd.count(
+                "requests",
+                1,
+                &[("kind", kind), ("route", route), ("status", &status)],
+            );
+
+            if let Some(requested) = requested {
+                statsd.timing("requested_sleep", requested, &[("kind", kind)]);
+            }
+        }
+    }
+
+    /// Record the time a request actually spent sleeping.
+    pub fn slept(&self, kind: &str, actual: &Duration) {
+        self.actual
+            .with_label_values(&[kind])
+            .observe(actual.as_secs_f64());
+
+        if let Some(statsd) = &self.statsd {
+            statsd.timing("actual_sleep", actual, &[("kind", kind)]);
+        }
+    }
+
+    /// Record a requested duration which was coerced to fit the configured bounds.
+    pub fn clamped(&self, param: &str) {
+        self.clamped.with_label_values(&[param]).inc();
+
+        if let Some(statsd) = &self.statsd {
+            statsd.count("clamped", 1, &[("param", param)]);
+        }
+    }
+
+    /// Record a fault injected by a profile.
+    pub fn fault(&self, profile: &str, status: u16) {
+        let status = status.to_string();
+
+        self.faults.with_label_values(&[profile, &status]).inc();
+
+        if let Some(statsd) = &self.statsd {
+            statsd.count("faults", 1, &[("profile", profile), ("status", &status)]);
+        }
+    }
+
+    /// Count a request as sleeping until the returned guard is dropped.
+    pub fn sleeping(&self) -> InFlight {
+        self.in_flight.inc();
+
+        let in_flight = InFlight {
+            gauge: self.in_flight.clone(),
+            statsd: self.statsd.clone(),
+        };
+
+        in_flight.push();
+        in_flight
+    }
+
+    /// Render every metric in the Prometheus text format.
+    pub fn render(&self) -> Vec<u8> {
+        let mut buffer = Vec::new();
+
+        // encoding into a buffer can only fail on malformed metrics, which are defined statically above
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .unwrap();
+
+        buffer
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A guard which counts a request as in-flight for as long as it lives, including if the client goes away.
+pub struct InFlight {
+    gauge: IntGauge,
+    statsd: Option<Arc<Statsd>>,
+}
+
+impl InFlight {
+    /// Push the number of in-flight requests to StatsD, if enabled.
+    fn push(&self) {
+        if let Some(statsd) = &self.statsd {
+            statsd.gauge("in_flight", self.gauge.get(), &[]);
+        }
+    }
+}
+
+impl Drop for InFlight {
+    fn drop(&mut self) {
+        self.gauge.dec();
+        self.push();
+    }
+}
+
+/// Handler for exporting metrics in the Prometheus text format.
+pub fn metrics(metrics: Data<Metrics>) -> HttpResponse {
+    HttpResponse::Ok()
+        .content_type(TextEncoder::new().format_type())
+        .body(metrics.render())
+}
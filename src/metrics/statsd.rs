@@ -0,0 +1,109 @@
+#[cfg(test)]
+mod tests;
+
+use crate::config::StatsdFlavor;
+
+use std::io;
+use std::net::{ToSocketAddrs, UdpSocket};
+use std::time::Duration;
+
+/// Pushes metrics to a StatsD or DogStatsD server over UDP.
+///
+/// Metrics are sent as they're recorded, one datagram each. Sending never blocks, and metrics which can't be sent are
+/// dropped, as is usual for StatsD.
+pub struct Statsd {
+    socket: UdpSocket,
+    prefix: String,
+    flavor: StatsdFlavor,
+    /// Tags added to every metric, already formatted.
+    tags: Vec<String>,
+}
+
+impl Statsd {
+    /// Send metrics to the server at an address such as `127.0.0.1:8125`, adding `key:value` tags to every metric.
+    pub fn connect(
+        addr: &str,
+        prefix: &str,
+        tags: &[String],
+        flavor: StatsdFlavor,
+    ) -> io::Result<Self> {
+        let target = addr.to_socket_addrs()?.next().ok_or_else(|| {
+            io::Error::new(io::ErrorKind::InvalidInput, "address resolved to nothing")
+        })?;
+
+        let socket = if target.is_ipv4() {
+            UdpSocket::bind("0.0.0.0:0")?
+        } else {
+            UdpSocket::bind("[::]:0")?
+        };
+
+        socket.connect(target)?;
+        socket.set_nonblocking(true)?;
+
+        Ok(Self {
+            socket,
+            prefix: prefix.trim_end_matches('.').to_string(),
+            flavor,
+            tags: tags.iter().map(|tag| sanitize(tag)).collect(),
+        })
+    }
+
+    /// Add to a counter.
+    pub fn count(&self, name: &str, value: i64, tags: &[(&str, &str)]) {
+        self.send(name, &value.to_string(), "c", tags);
+    }
+
+    /// Record a duration in milliseconds.
+    pub fn timing(&self, name: &str, duration: &Duration, tags: &[(&str, &str)]) {
+        let millis = duration.as_secs_f64() * 1000.0;
+
+        self.send(
+            name,
+            &format!("{}", (millis * 1000.0).round() / 1000.0),
+            "ms",
+            tags,
+        );
+    }
+
+    /// Set a gauge to a value.
+    pub fn gauge(&self, name: &str, value: i64, tags: &[(&str, &str)]) {
+        self.send(name, &value.to_string(), "g", tags);
+    }
+
+    fn send(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) {
+        let line = self.line(name, value, kind, tags);
+
+        if let Err(e) = self.socket.send(line.as_bytes()) {
+            tracing::trace!(error = %e, "Unable to send a StatsD metric.");
+        }
+    }
+
+    /// Format a metric in the StatsD line protocol, with DogStatsD tags if enabled.
+    fn line(&self, name: &str, value: &str, kind: &str, tags: &[(&str, &str)]) -> String {
+        let mut line = if self.prefix.is_empty() {
+            format!("{}:{}|{}", name, value, kind)
+        } else {
+            format!("{}.{}:{}|{}", self.prefix, name, value, kind)
+        };
+
+        if self.flavor == StatsdFlavor::Dogstatsd {
+            let tags: Vec<String> = tags
+                .iter()
+                .map(|(key, value)| format!("{}:{}", key, sanitize(value)))
+                .chain(self.tags.iter().cloned())
+                .collect();
+
+            if !tags.is_empty() {
+                line.push_str("|#");
+                line.push_str(&tags.join(","));
+            }
+        }
+
+        line
+    }
+}
+
+/// Replace the characters which delimit the parts of a DogStatsD line.
+fn sanitize(tag: &str) -> String {
+    tag.replace(['|', ',', '#', '\n'], "_")
+}
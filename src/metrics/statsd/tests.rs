@@ -0,0 +1,93 @@
+use super::Statsd;
+
+use crate::config::StatsdFlavor;
+use crate::metrics::Metrics;
+
+use std::net::UdpSocket;
+use std::time::Duration;
+
+/// Listen for metrics on a local port, standing in for a StatsD server.
+fn listener() -> (UdpSocket, String) {
+    let socket = UdpSocket::bind("127.0.0.1:0").unwrap();
+    socket
+        .set_read_timeout(Some(Duration::from_secs(5)))
+        .unwrap();
+
+    let addr = socket.local_addr().unwrap().to_string();
+
+    (socket, addr)
+}
+
+/// Receive the next metric line.
+fn receive(socket: &UdpSocket) -> String {
+    let mut buf = [0; 1024];
+    let len = socket.recv(&mut buf).unwrap();
+
+    String::from_utf8(buf[..len].to_vec()).unwrap()
+}
+
+#[test]
+fn test_lines() {
+    let (socket, addr) = listener();
+    let tags = vec!["env:test".to_string()];
+    let statsd = Statsd::connect(&addr, "slumberd.", &tags, StatsdFlavor::Dogstatsd).unwrap();
+
+    // test that tag values can't break the line apart
+    statsd.count("requests", 1, &[("kind", "fixed"), ("route", "/a,b|c")]);
+    assert_eq!(
+        "slumberd.requests:1|c|#kind:fixed,route:/a_b_c,env:test",
+        receive(&socket)
+    );
+
+    statsd.timing("actual_sleep", &Duration::from_micros(1500), &[]);
+    assert_eq!("slumberd.actual_sleep:1.5|ms|#env:test", receive(&socket));
+
+    statsd.gauge("in_flight", 3, &[]);
+    assert_eq!("slumberd.in_flight:3|g|#env:test", receive(&socket));
+}
+
+#[test]
+fn test_plain_statsd() {
+    let (socket, addr) = listener();
+    let tags = vec!["env:test".to_string()];
+    let statsd = Statsd::connect(&addr, "", &tags, StatsdFlavor::Statsd).unwrap();
+
+    // test that tags are left out, as plain StatsD doesn't support them
+    statsd.count("faults", 2, &[("profile", "flaky")]);
+    assert_eq!("faults:2|c", receive(&socket));
+}
+
+#[test]
+fn test_metrics() {
+    let (socket, addr) = listener();
+    let statsd = Statsd::connect(&addr, "slumberd", &[], StatsdFlavor::Dogstatsd).unwrap();
+    let metrics = Metrics::new().with_statsd(statsd);
+
+    metrics.request("fixed", "/{time}", 200, Some(&Duration::from_millis(250)));
+    assert_eq!(
+        "slumberd.requests:1|c|#kind:fixed,route:/{time},status:200",
+        receive(&socket)
+    );
+    assert_eq!(
+        "slumberd.requested_sleep:250|ms|#kind:fixed",
+        receive(&socket)
+    );
+
+    metrics.slept("fixed", &Duration::from_millis(251));
+    assert_eq!("slumberd.actual_sleep:251|ms|#kind:fixed", receive(&socket));
+
+    metrics.clamped("time");
+    assert_eq!("slumberd.clamped:1|c|#param:time", receive(&socket));
+
+    metrics.fault("flaky", 503);
+    assert_eq!(
+        "slumberd.faults:1|c|#profile:flaky,status:503",
+        receive(&socket)
+    );
+
+    let in_flight = metrics.sleeping();
+    assert_eq!("slumberd.in_flight:1|g", receive(&socket));
+
+    drop(in_flight);
+    assert_eq!("slumberd.in_flight:0|g", receive(&socket));
+}
@@ -0,0 +1,62 @@
+use super::Metrics;
+
+use std::time::Duration;
+
+#[test]
+fn test_render() {
+    let metrics = Metrics::new();
+
+    metrics.request(
+        "fixed",
+        "/sleep/{millis}",
+        200,
+        Some(&Duration::from_millis(150)),
+    );
+    metrics.request(
+        "fixed",
+        "/sleep/{millis}",
+        200,
+        Some(&Duration::from_millis(150)),
+    );
+    metrics.request("fixed", "/sleep/{millis}", 200, None);
+    metrics.slept("fixed", &Duration::from_millis(152));
+    metrics.clamped("time");
+    metrics.fault("flaky", 503);
+
+    let rendered = String::from_utf8(metrics.render()).unwrap();
+
+    assert!(rendered.contains(
+        r#"slumberd_requests_total{kind="fixed",route="/sleep/{millis}",status="200"} 3"#
+    ));
+    assert!(
+        rendered.contains(r#"slumberd_requested_sleep_seconds_bucket{kind="fixed",le="0.25"} 2"#)
+    );
+    assert!(
+        rendered.contains(r#"slumberd_requested_sleep_seconds_bucket{kind="fixed",le="0.1"} 0"#)
+    );
+    assert!(rendered.contains(r#"slumberd_requested_sleep_seconds_count{kind="fixed"} 2"#));
+    assert!(rendered.contains(r#"slumberd_actual_sleep_seconds_count{kind="fixed"} 1"#));
+    assert!(rendered.contains(r#"slumberd_clamped_total{param="time"} 1"#));
+    assert!(rendered.contains(r#"slumberd_faults_total{profile="flaky",status="503"} 1"#));
+    assert!(rendered.contains("slumberd_in_flight_requests 0"));
+}
+
+#[test]
+fn test_in_flight() {
+    let metrics = Metrics::new();
+
+    let first = metrics.sleeping();
+    let second = metrics.sleeping();
+
+    assert!(String::from_utf8(metrics.render())
+        .unwrap()
+        .contains("slumberd_in_flight_requests 2"));
+
+    // test that dropping the guard, as happens when a client goes away, stops counting the request
+    drop(first);
+    drop(second);
+
+    assert!(String::from_utf8(metrics.render())
+        .unwrap()
+        .contains("slumberd_in_flight_requests 0"));
+}
@@ -0,0 +1,180 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::web::Data;
+
+use crate::config::{CliArgs, ConfigError, SharedConfig, TimeScale};
+use crate::logging::LogLevel;
+
+use signal_hook::consts::SIGHUP;
+use signal_hook::iterator::Signals;
+
+use std::ffi::OsString;
+use std::fs;
+use std::path::PathBuf;
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+/// How often the configuration file is checked for changes when watching it.
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Reloads configuration from the original command-line, the environment, and the configuration file.
+pub struct Reloader {
+    args: Vec<OsString>,
+    config: Data<SharedConfig>,
+    scale: Data<TimeScale>,
+    level: Data<LogLevel>,
+}
+
+impl Reloader {
+    pub fn new(
+        args: Vec<OsString>,
+        config: Data<SharedConfig>,
+        scale: Data<TimeScale>,
+        level: Data<LogLevel>,
+    ) -> Self {
+        Self {
+            args,
+            config,
+            scale,
+            level,
+        }
+    }
+
+    /// Reload configuration and swap it in for new requests.
+    ///
+    /// If the configuration is invalid, the current configuration is kept.
+    pub fn reload(&self) -> Result<(), ConfigError> {
+        self.reload_from(std::env::vars())
+    }
+
+    /// Reload configuration using the given environment variables, rather than those of the process.
+    pub fn reload_from<V>(&self, vars: V) -> Result<(), ConfigError>
+    where
+        V: IntoIterator<Item = (String, String)>,
+    {
+        let next = CliArgs::load_from(self.args.clone(), vars)?;
+        let current = self.config.current();
+
+        if next.host != current.host || next.port != current.port {
+            tracing::warn!(
+                "Listen address changed to {}:{}, which requires a restart to take effect.",
+                next.host,
+                next.port
+            );
+        }
+
+        let logging_changed = next.json != current.json
+            || next.log_file != current.log_file
+            || next.log_file_format != current.log_file_format
+            || next.log_max_size != current.log_max_size
+            || next.log_rotation != current.log_rotation
+            || next.log_keep != current.log_keep
+            || next.log_compress != current.log_compress;
+
+        if logging_changed {
+            tracing::warn!(
+                "Logging configuration changed, which requires a restart to take effect."
+            );
+        }
+
+        if next.access_log != current.access_log
+            || next.access_log_format != current.access_log_format
+        {
+            tracing::warn!(
+                "Access log configuration changed, which requires a restart to take effect."
+            );
+        }
+
+        if next.history_size != current.history_size {
+            tracing::warn!("History size changed, which requires a restart to take effect.");
+        }
+
+        if next.statsd != current.statsd
+            || next.statsd_prefix != current.statsd_prefix
+            || next.statsd_tags != current.statsd_tags
+            || next.statsd_flavor != current.statsd_flavor
+        {
+            tracing::warn!(
+                "StatsD configuration changed, which requires a restart to take effect."
+            );
+        }
+
+        // only override a runtime-adjusted log level if the configured verbosity changed
+        if next.verbosity != current.verbosity {
+            self.level.set_verbosity(next.verbosity);
+
+            tracing::info!("Log level changed to {}.", self.level.get_ref());
+        }
+
+        // the time scale set through the admin API is kept in the configuration, so this also replaces it
+        if (next.time_scale - current.time_scale).abs() > f64::EPSILON {
+            let _ = self.scale.set(next.time_scale());
+        }
+
+        tracing::info!(
+            "Reloaded configuration (min sleep time: {:?}, default sleep time: {:?}, max sleep time: {:?}, random: {}, time scale: {}).",
+            next.min_sleep(),
+            next.sleep(),
+            next.max_sleep(),
+            next.random,
+            self.scale.get(),
+        );
+
+        self.config.replace(next);
+
+        Ok(())
+    }
+
+    /// Reload configuration, logging rather than returning any errors.
+    fn reload_logged(&self, trigger: &str) {
+        tracing::info!("Reloading configuration on {}.", trigger);
+
+        if let Err(e) = self.reload() {
+            tracing::error!(
+                "Unable to reload configuration, keeping the current configuration: {}",
+                e
+            );
+        }
+    }
+}
+
+/// Reload configuration whenever SIGHUP is received.
+pub fn watch_signals(reloader: Data<Reloader>) -> std::io::Result<()> {
+    let mut signals = Signals::new([SIGHUP])?;
+
+    thread::Builder::new()
+        .name("reload-signals".to_string())
+        .spawn(move || {
+            for _ in signals.forever() {
+                reloader.reload_logged("SIGHUP");
+            }
+        })?;
+
+    Ok(())
+}
+
+/// Reload configuration whenever the configuration file is modified.
+pub fn watch_file(reloader: Data<Reloader>, path: PathBuf) -> std::io::Result<()> {
+    let modified = |path: &PathBuf| -> Option<SystemTime> {
+        fs::metadata(path).and_then(|m| m.modified()).ok()
+    };
+
+    let mut last = modified(&path);
+
+    thread::Builder::new()
+        .name("reload-watcher".to_string())
+        .spawn(move || loop {
+            thread::sleep(WATCH_INTERVAL);
+
+            let current = modified(&path);
+
+            if current.is_some() && current != last {
+                last = current;
+
+                reloader.reload_logged(&format!("change to {}", path.display()));
+            }
+        })?;
+
+    Ok(())
+}
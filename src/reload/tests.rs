@@ -0,0 +1,79 @@
+use super::Reloader;
+
+use crate::config::{CliArgs, SharedConfig, TimeScale};
+use crate::logging::LogLevel;
+
+use actix_web::web::Data;
+
+use std::fs;
+
+/// A fixed environment, so that the test doesn't depend on the variables of the process running it.
+fn vars() -> Vec<(String, String)> {
+    vec![("SLUMBERD_MAX_SLEEP_MS".to_string(), "60000".to_string())]
+}
+
+#[test]
+fn test_reload() {
+    let path = std::env::temp_dir().join(format!("slumberd-{}-reload.toml", std::process::id()));
+
+    fs::write(&path, "sleep_ms = 100\ntime_scale = 1.0\n").unwrap();
+
+    let args = vec![
+        "slumberd".into(),
+        "-c".into(),
+        path.clone().into_os_string(),
+    ];
+
+    let config = Data::new(SharedConfig::new(
+        CliArgs::load_from(args.clone(), vars()).unwrap(),
+    ));
+    let scale = Data::new(TimeScale::new(1.0));
+    let level = Data::new(LogLevel::new(0));
+    let reloader = Reloader::new(args, config.clone(), scale.clone(), level.clone());
+
+    // take a snapshot as an in-flight request would
+    let snapshot = config.current();
+
+    // test that new requests see the change while in-flight requests keep their snapshot
+    fs::write(&path, "sleep_ms = 200\ntime_scale = 0.5\n").unwrap();
+
+    assert!(reloader.reload_from(vars()).is_ok());
+    assert_eq!(200, config.current().sleep_ms);
+    assert_eq!(60000, config.current().max_sleep_ms);
+    assert_eq!(100, snapshot.sleep_ms);
+    assert!((scale.get() - 0.5).abs() < f64::EPSILON);
+
+    // test that a time scale set through the admin API is replaced by the configured one
+    scale.set(0.1).unwrap();
+    config
+        .update(|next| -> Result<(), ()> {
+            next.time_scale = 0.1;
+            Ok(())
+        })
+        .unwrap();
+    fs::write(&path, "sleep_ms = 300\ntime_scale = 0.5\n").unwrap();
+
+    assert!(reloader.reload_from(vars()).is_ok());
+    assert_eq!(300, config.current().sleep_ms);
+    assert!((scale.get() - 0.5).abs() < f64::EPSILON);
+
+    // test that a runtime-adjusted log level survives a reload which doesn't change the verbosity
+    level.set("warn,slumberd::handlers=debug").unwrap();
+    fs::write(&path, "sleep_ms = 300\ntime_scale = 0.5\nrandom = true\n").unwrap();
+
+    assert!(reloader.reload_from(vars()).is_ok());
+    assert_eq!("slumberd::handlers=debug,warn", level.to_string());
+
+    fs::write(&path, "sleep_ms = 300\ntime_scale = 0.5\nverbosity = 1\n").unwrap();
+
+    assert!(reloader.reload_from(vars()).is_ok());
+    assert_eq!("slumberd=debug,warn", level.to_string());
+
+    // test that an invalid configuration is rejected and the current configuration kept
+    fs::write(&path, "sleep_ms = \"lots\"\n").unwrap();
+
+    assert!(reloader.reload_from(vars()).is_err());
+    assert_eq!(300, config.current().sleep_ms);
+
+    fs::remove_file(path).unwrap();
+}
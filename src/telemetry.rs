@@ -0,0 +1,173 @@
+#[cfg(test)]
+mod tests;
+
+use actix_web::http::HeaderMap;
+
+use crate::config::{CliArgs, OtlpProtocol};
+
+use opentelemetry::propagation::{Extractor, TextMapPropagator};
+use opentelemetry::trace::{TraceContextExt, TracerProvider};
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::runtime;
+use opentelemetry_sdk::trace::span_processor_with_async_runtime::BatchSpanProcessor;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use opentelemetry_sdk::Resource;
+
+use tokio1::runtime::Runtime;
+
+use tracing::{Level, Span, Subscriber};
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use tracing_subscriber::filter::{LevelFilter, Targets};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::Layer;
+
+use std::sync::OnceLock;
+
+/// The path which traces are sent to when exporting over HTTP.
+const HTTP_TRACES_PATH: &str = "/v1/traces";
+
+/// The provider which exports spans, kept so that pending spans can be flushed on shutdown.
+static PROVIDER: OnceLock<SdkTracerProvider> = OnceLock::new();
+
+/// The runtime which gRPC exports are driven by, as the sleep handlers run on an older runtime.
+static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+
+/// Build the layer which exports spans over OTLP, if an endpoint is configured.
+pub fn layer<S>(cli: &CliArgs) -> Result<Option<Box<dyn Layer<S> + Send + Sync>>, String>
+where
+    S: Subscriber + for<'a> LookupSpan<'a> + Send + Sync,
+{
+    let endpoint = match &cli.otlp_endpoint {
+        Some(endpoint) => endpoint,
+        None => return Ok(None),
+    };
+
+    let provider = provider(
+        endpoint,
+        cli.otlp_protocol,
+        &cli.otlp_service_name,
+        cli.otlp_sample_ratio,
+    )?;
+
+    let tracer = provider.tracer("slumberd");
+    let _ = PROVIDER.set(provider);
+
+    // events are exported as span events regardless of the log verbosity, as they make up the request's timeline
+    let filter = Targets::new()
+        .with_target("slumberd", Level::DEBUG)
+        .with_default(LevelFilter::OFF);
+
+    Ok(Some(
+        tracing_opentelemetry::layer()
+            .with_tracer(tracer)
+            .with_filter(filter)
+            .boxed(),
+    ))
+}
+
+/// Build a provider which exports spans to a collector, sampling new traces at the given ratio.
+pub fn provider(
+    endpoint: &str,
+    protocol: OtlpProtocol,
+    service_name: &str,
+    ratio: f64,
+) -> Result<SdkTracerProvider, String> {
+    let builder = SdkTracerProvider::builder()
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(service_name.to_string())
+                .build(),
+        );
+
+    let builder = match protocol {
+        // the blocking HTTP client is driven by the batch processor's own thread
+        OtlpProtocol::Http => {
+            let exporter = SpanExporter::builder()
+                .with_http()
+                .with_endpoint(http_endpoint(endpoint))
+                .build()
+                .map_err(|e| e.to_string())?;
+
+            builder.with_batch_exporter(exporter)
+        }
+        // while tonic needs a tokio runtime of its own
+        OtlpProtocol::Grpc => {
+            let runtime = RUNTIME.get_or_init(|| {
+                tokio1::runtime::Builder::new_multi_thread()
+                    .worker_threads(1)
+                    .thread_name("otlp")
+                    .enable_all()
+                    .build()
+                    .expect("unable to start the OTLP runtime")
+            });
+            let _entered = runtime.enter();
+
+            let exporter = SpanExporter::builder()
+                .with_tonic()
+                .with_endpoint(endpoint)
+                .build()
+                .map_err(|e| e.to_string())?;
+
+            builder
+                .with_span_processor(BatchSpanProcessor::builder(exporter, runtime::Tokio).build())
+        }
+    };
+
+    Ok(builder.build())
+}
+
+/// Append the traces path to an HTTP endpoint, unless it's already there.
+fn http_endpoint(endpoint: &str) -> String {
+    let endpoint = endpoint.trim_end_matches('/');
+
+    if endpoint.ends_with(HTTP_TRACES_PATH) {
+        endpoint.to_string()
+    } else {
+        format!("{}{}", endpoint, HTTP_TRACES_PATH)
+    }
+}
+
+/// Make a request's span a child of the caller's span from its `traceparent` header, returning the id of the
+/// request's span if it's being exported.
+pub fn link(span: &Span, headers: &HeaderMap) -> Option<String> {
+    let parent = TraceContextPropagator::new().extract(&HeaderExtractor(headers));
+
+    if parent.span().span_context().is_valid() {
+        let _ = span.set_parent(parent);
+    }
+
+    let context = span.context();
+    let span_context = context.span().span_context().clone();
+
+    if span_context.is_valid() {
+        Some(span_context.span_id().to_string())
+    } else {
+        None
+    }
+}
+
+/// Export any spans which are still pending.
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get() {
+        if let Err(e) = provider.shutdown() {
+            tracing::warn!("Unable to export pending spans: {}", e);
+        }
+    }
+}
+
+/// Reads trace context from request headers.
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl<'a> Extractor for HeaderExtractor<'a> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(|name| name.as_str()).collect()
+    }
+}
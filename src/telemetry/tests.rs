@@ -0,0 +1,153 @@
+use super::{http_endpoint, link, provider};
+
+use crate::config::OtlpProtocol;
+
+use actix_web::http::{HeaderMap, HeaderName, HeaderValue};
+
+use opentelemetry::trace::TracerProvider;
+
+use tracing_subscriber::prelude::*;
+
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::TcpListener;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
+
+/// A stand-in for an OTLP collector, which accepts a single HTTP export and sends its request line and body back.
+fn collector() -> (String, mpsc::Receiver<(String, Vec<u8>)>) {
+    let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+    let endpoint = format!("http://{}", listener.local_addr().unwrap());
+    let (tx, rx) = mpsc::channel();
+
+    thread::spawn(move || {
+        let (stream, _) = listener.accept().unwrap();
+        let mut reader = BufReader::new(stream);
+
+        let mut request_line = String::new();
+        reader.read_line(&mut request_line).unwrap();
+
+        let mut length = 0;
+
+        loop {
+            let mut header = String::new();
+            reader.read_line(&mut header).unwrap();
+
+            if header.trim().is_empty() {
+                break;
+            }
+
+            if let Some((name, value)) = header.split_once(':') {
+                if name.eq_ignore_ascii_case("content-length") {
+                    length = value.trim().parse().unwrap();
+                }
+            }
+        }
+
+        let mut body = vec![0; length];
+        reader.read_exact(&mut body).unwrap();
+
+        reader
+            .get_mut()
+            .write_all(b"HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n")
+            .unwrap();
+
+        tx.send((request_line.trim().to_string(), body)).unwrap();
+    });
+
+    (endpoint, rx)
+}
+
+/// Check whether a byte string contains another.
+fn contains(haystack: &[u8], needle: &[u8]) -> bool {
+    haystack
+        .windows(needle.len())
+        .any(|window| window == needle)
+}
+
+#[test]
+fn test_http_endpoint() {
+    assert_eq!(
+        "http://localhost:4318/v1/traces",
+        http_endpoint("http://localhost:4318")
+    );
+    assert_eq!(
+        "http://localhost:4318/v1/traces",
+        http_endpoint("http://localhost:4318/")
+    );
+    assert_eq!(
+        "http://localhost:4318/v1/traces",
+        http_endpoint("http://localhost:4318/v1/traces")
+    );
+}
+
+#[test]
+fn test_export_http() {
+    let (endpoint, exports) = collector();
+    let provider = provider(&endpoint, OtlpProtocol::Http, "slumberd-test", 1.0).unwrap();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("slumberd")));
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("slumber", request_id = "abc123", kind = "fixed");
+        let _entered = span.enter();
+
+        tracing::info!("Sleep started.");
+    });
+
+    provider.force_flush().unwrap();
+
+    let (request_line, body) = exports.recv_timeout(Duration::from_secs(10)).unwrap();
+
+    // test that the span is exported with the service name and its fields as attributes
+    assert_eq!("POST /v1/traces HTTP/1.1", request_line);
+    assert!(contains(&body, b"slumberd-test"));
+    assert!(contains(&body, b"slumber"));
+    assert!(contains(&body, b"request_id"));
+    assert!(contains(&body, b"abc123"));
+    assert!(contains(&body, b"Sleep started."));
+}
+
+#[test]
+fn test_link() {
+    let provider = provider(
+        "http://127.0.0.1:9",
+        OtlpProtocol::Http,
+        "slumberd-test",
+        0.0,
+    )
+    .unwrap();
+
+    let subscriber = tracing_subscriber::registry()
+        .with(tracing_opentelemetry::layer().with_tracer(provider.tracer("slumberd")));
+
+    let mut headers = HeaderMap::new();
+    headers.insert(
+        HeaderName::from_static("traceparent"),
+        HeaderValue::from_static("00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01"),
+    );
+
+    tracing::subscriber::with_default(subscriber, || {
+        let span = tracing::info_span!("slumber");
+        let span_id = link(&span, &headers).unwrap();
+
+        // test that the span continues the caller's trace with a span of its own
+        assert_eq!(16, span_id.len());
+        assert_ne!("00f067aa0ba902b7", span_id);
+
+        let context = opentelemetry::trace::TraceContextExt::span(
+            &tracing_opentelemetry::OpenTelemetrySpanExt::context(&span),
+        )
+        .span_context()
+        .clone();
+
+        assert_eq!(
+            "4bf92f3577b34da6a3ce929d0e0e4736",
+            context.trace_id().to_string()
+        );
+
+        // test that the caller's sampling decision is followed, despite the sample ratio
+        assert!(context.is_sampled());
+    });
+}